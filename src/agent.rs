@@ -0,0 +1,515 @@
+use crate::bots::{ForfeitAgent, GreedyAgent, RandomAgent};
+use crate::game_state::{factories_from_json, factories_to_json, legal_actions_for, parse_color};
+use crate::mcts::{MctsAgent, SearchBudget};
+use crate::protocol;
+use crate::rules::rules_from_json;
+use crate::{
+    Action, GameState, InvalidMoveError, PlayerBoard, RuleError, RuleSet, TileColor,
+    NUM_COLORS_AS_USIZE, NUM_TILE_COLORS,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// What a seat gets to see when it's asked to move.
+#[derive(Clone, Debug)]
+pub struct Observation {
+    pub seat: usize, // 0-based
+    pub num_players: usize,
+    pub factories: Vec<HashMap<TileColor, i32>>,
+    pub pool: HashMap<TileColor, i32>,
+    pub first_player_marker_in_pool: bool,
+    pub my_board: PlayerBoard,
+    pub opponents: Vec<PlayerBoard>, // in turn order after us; empty if the table doesn't share them
+    pub legal_actions: Vec<Action>,
+    pub time_left: Option<Duration>, // to make this move, if the game is on the clock
+    pub rules: RuleSet,              // the house rules the game is played under
+}
+
+impl Observation {
+    pub fn from_state(state: &GameState) -> Self {
+        let seat = state.current_player();
+        let num_players = state.num_players();
+        Observation {
+            seat,
+            num_players,
+            factories: state.factories().to_vec(),
+            pool: state.pool().clone(),
+            first_player_marker_in_pool: state.first_player_marker_in_pool(),
+            my_board: state.player_board(seat).clone(),
+            opponents: (1..num_players)
+                .map(|ii| state.player_board((seat + ii) % num_players).clone())
+                .collect(),
+            legal_actions: state.legal_actions(),
+            time_left: None,
+            rules: state.rules().clone(),
+        }
+    } // fn from_state
+    pub(crate) fn new(
+        seat: usize,
+        num_players: usize,
+        factories: Vec<HashMap<TileColor, i32>>,
+        pool: HashMap<TileColor, i32>,
+        first_player_marker_in_pool: bool,
+        my_board: PlayerBoard,
+        opponents: Vec<PlayerBoard>,
+    ) -> Self {
+        let legal_actions = legal_actions_for(&factories, &pool, &my_board);
+        Observation {
+            seat,
+            num_players,
+            factories,
+            pool,
+            first_player_marker_in_pool,
+            my_board,
+            opponents,
+            legal_actions,
+            time_left: None,
+            rules: RuleSet::default(),
+        }
+    } // fn new
+
+    // Ok if `action` is one of the legal actions, otherwise the rule it breaks
+    pub fn check(&self, action: &Action) -> Result<(), RuleError> {
+        if self.legal_actions.contains(action) {
+            return Ok(());
+        }
+        let color = action.color();
+        if color == TileColor::NOCOLOR {
+            return Err(RuleError::BadColor);
+        }
+        let (source, empty) = match action {
+            Action::FactoryToRow { factory_idx, .. }
+            | Action::FactoryToFloor { factory_idx, .. } => (
+                self.factories
+                    .get(*factory_idx)
+                    .ok_or(RuleError::NoSuchFactory)?,
+                RuleError::BadFactoryRequest,
+            ),
+            Action::PoolToRow { .. } | Action::PoolToFloor { .. } => {
+                (&self.pool, RuleError::BadPoolRequest)
+            }
+        };
+        if *source.get(&color).unwrap_or(&0) == 0 {
+            return Err(empty);
+        }
+        if let Some(row_idx) = action.row_idx() {
+            self.my_board.check_valid_move(color, row_idx)?;
+        }
+        Err(RuleError::NotAllowed)
+    } // fn check
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut pool = json::JsonValue::new_object();
+        for (color, count) in self.pool.iter() {
+            let _ = pool.insert(&color.to_string(), *count);
+        }
+        let opponents: Vec<json::JsonValue> = self.opponents.iter().map(|b| b.to_json()).collect();
+        let legal_actions: Vec<json::JsonValue> =
+            self.legal_actions.iter().map(|a| a.to_json()).collect();
+        json::object! {
+            "seat": self.seat,
+            "num_players": self.num_players,
+            "factories": factories_to_json(&self.factories),
+            "pool": pool,
+            "first_player_marker_in_pool": self.first_player_marker_in_pool,
+            "my_board": self.my_board.to_json(),
+            "opponents": opponents,
+            "legal_actions": legal_actions,
+            "time_left_ms": self.time_left.map(|left| left.as_millis() as u64),
+            "rules": protocol::to_json(&self.rules),
+        }
+    } // fn to_json
+    pub fn from_json(val: &json::JsonValue) -> Result<Self, String> {
+        let mut pool = HashMap::new();
+        for (color_str, num) in val["pool"].entries() {
+            pool.insert(
+                parse_color(color_str)?,
+                num.as_i32().ok_or("pool counts are numbers")?,
+            );
+        }
+        Ok(Observation {
+            seat: val["seat"].as_usize().ok_or("seat is missing")?,
+            num_players: val["num_players"]
+                .as_usize()
+                .ok_or("num_players is missing")?,
+            factories: factories_from_json(&val["factories"])?,
+            pool,
+            first_player_marker_in_pool: val["first_player_marker_in_pool"]
+                .as_bool()
+                .ok_or("first_player_marker_in_pool is missing")?,
+            my_board: PlayerBoard::from_json(&val["my_board"])?,
+            opponents: val["opponents"]
+                .members()
+                .map(PlayerBoard::from_json)
+                .collect::<Result<Vec<PlayerBoard>, String>>()?,
+            legal_actions: val["legal_actions"]
+                .members()
+                .map(Action::from_json)
+                .collect::<Result<Vec<Action>, String>>()?,
+            time_left: val["time_left_ms"].as_u64().map(Duration::from_millis),
+            rules: rules_from_json(&val["rules"])?,
+        })
+    } // fn from_json
+} // impl Observation
+
+/// Anything that can sit at the table - a person at a terminal, a bot, a remote client.
+pub trait PlayerAgent: Send {
+    fn name(&self) -> String;
+    fn choose_action(&mut self, obs: &Observation) -> Action;
+    // the table turned down the last action because of `_error`; we'll be asked again
+    fn on_invalid_action(&mut self, _action: Action, _error: RuleError) {}
+    // gray wall only: which of `columns` the full pattern line `row_idx` gets tiled into, at the
+    // end of the round. by default, wherever it scores the most
+    fn choose_column(&mut self, board: &PlayerBoard, row_idx: usize, columns: &[usize]) -> usize {
+        board.best_column(row_idx, columns)
+    }
+    // `_col_idx` wasn't one of the columns; we'll be asked again
+    fn on_invalid_column(&mut self, _col_idx: usize, _error: RuleError) {}
+    fn on_round_end(&mut self, _board: &PlayerBoard) {}
+    fn on_game_end(&mut self, _final_score: i32) {}
+}
+impl fmt::Debug for dyn PlayerAgent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PlayerAgent({})", self.name())
+    }
+}
+
+const DEFAULT_MCTS_ITERATIONS: u32 = 1000;
+
+// "human", "random", "greedy" or "mcts". mcts takes an optional budget after a colon,
+// either iterations ("mcts:5000") or milliseconds per move ("mcts:500ms").
+// bots that roll dice get their own rng seeded with `seed`
+pub fn agent_from_name(name: &str, seed: u64) -> Option<Box<dyn PlayerAgent>> {
+    let (kind, budget) = match name.split_once(':') {
+        Some((kind, budget)) => (kind, Some(budget)),
+        None => (name, None),
+    };
+    match (kind, budget) {
+        ("human", None) => Some(Box::new(TerminalAgent::new())),
+        ("random", None) => Some(Box::new(RandomAgent::with_rng(StdRng::seed_from_u64(seed)))),
+        ("greedy", None) => Some(Box::new(GreedyAgent::new())),
+        ("mcts", None) => Some(Box::new(MctsAgent::with_rng(
+            SearchBudget::Iterations(DEFAULT_MCTS_ITERATIONS),
+            StdRng::seed_from_u64(seed),
+        ))),
+        ("mcts", Some(budget)) => {
+            let budget = match budget.strip_suffix("ms") {
+                Some(ms) => SearchBudget::Millis(ms.parse().ok()?),
+                None => SearchBudget::Iterations(budget.parse().ok()?),
+            };
+            Some(Box::new(MctsAgent::with_rng(
+                budget,
+                StdRng::seed_from_u64(seed),
+            )))
+        }
+        _ => None,
+    }
+} // fn agent_from_name
+
+pub fn print_board(obs: &Observation) {
+    // TODO - some sort of text stream?
+    println!("---------------------------");
+    print!(
+        "{}",
+        draw_shared_tiles(&obs.factories, &obs.pool, obs.first_player_marker_in_pool)
+    );
+    println!("{}", draw_player_board(&obs.my_board));
+    if obs.my_board.has_first_player_marker {
+        println!("PLAYER {}*", obs.seat + 1);
+    } else {
+        println!("PLAYER {}", obs.seat + 1);
+    }
+} // fn print_board
+
+// the factories and the pool, as print_board shows them
+pub(crate) fn draw_shared_tiles(
+    factories: &[HashMap<TileColor, i32>],
+    pool: &HashMap<TileColor, i32>,
+    first_player_marker_in_pool: bool,
+) -> String {
+    let mut lines = String::from("Factories:\n");
+    for (counter, factory) in (1..).zip(factories.iter()) {
+        lines.push_str(format! {"{}) ", counter}.as_str());
+        for (color, num) in factory.iter() {
+            for _ in 0..*num {
+                lines.push_str(format! {"{} ", color.color_string()}.as_str());
+            }
+        }
+        lines.push('\n');
+    } // iter over factories
+    lines.push_str("\nPOOL:\n");
+    if first_player_marker_in_pool {
+        lines.push_str("[-1]\n");
+    }
+    for ii in 0..NUM_TILE_COLORS {
+        let color = TileColor::from_integer(ii);
+        let num = *pool.get(&color).unwrap_or(&0);
+        // no point listing jokers in a game that doesn't have any
+        if color == TileColor::JOKER && num == 0 {
+            continue;
+        }
+        lines.push_str(format! {"{} x {}\n", color.color_string(), num}.as_str());
+    }
+    lines
+} // fn draw_shared_tiles
+
+// pattern lines next to the wall, then the floor and score
+pub(crate) fn draw_player_board(board: &PlayerBoard) -> String {
+    let mut lines = String::new();
+    for ii in 0..NUM_COLORS_AS_USIZE {
+        lines.push_str(format! {"{}) ", ii+1}.as_str());
+        for _ in ii + 1..NUM_COLORS_AS_USIZE {
+            lines.push(' ');
+        }
+        for jj in (0..ii + 1).rev() {
+            if board.rows[ii].1 == TileColor::NOCOLOR || jj >= board.rows[ii].0 as usize {
+                lines.push('_');
+            } else if jj >= (board.rows[ii].0 - board.jokers[ii]) as usize {
+                lines.push_str(TileColor::JOKER.color_string().as_str());
+            } else {
+                lines.push_str(board.rows[ii].1.color_string().as_str());
+            }
+        }
+        // print grid row
+        lines.push_str("  |");
+        for jj in 0..NUM_COLORS_AS_USIZE {
+            match board.wall_color(ii, jj) {
+                // print colored string
+                Some(color) => lines.push_str(color.color_string().as_str()),
+                // the gray wall has no symbols to show
+                None if board.wall_colors.is_some() => lines.push(' '),
+                // print symbol only
+                None => {
+                    lines.push_str(TileColor::from_integer(((ii + jj) % 5) as i32).to_char_symbol())
+                }
+            }
+            lines.push('|');
+        }
+        lines.push('\n');
+    } // iterate over rows
+    lines.push_str(format! {"FLOOR: {}\n", board.num_penalties()}.as_str());
+    lines.push_str(format! {"CURRENT SCORE: {}", board.score}.as_str());
+    lines
+} // fn draw_player_board
+
+// somebody typing moves in at the terminal
+#[derive(Debug, Default)]
+pub struct TerminalAgent {}
+
+impl<'a> TerminalAgent {
+    const PROMPT_FACTORY_DRAW: &'a str = "[f] take from factory ";
+    const PROMPT_POOL_DRAW: &'a str = "[p] take from pool ";
+    const PROMPT_DISCARD: &'a str = "[d] discard tile(s) ";
+    const PROMPT_PRINT_BOARD: &'a str = "[P] print game board";
+    pub fn new() -> Self {
+        TerminalAgent {}
+    }
+    fn read_line() -> Result<String, InvalidMoveError> {
+        let mut read_buf = String::new();
+        let num_bytes = std::io::stdin()
+            .read_line(&mut read_buf)
+            .map_err(InvalidMoveError::from_io)?;
+        if num_bytes == 0 {
+            return Err(InvalidMoveError::InputClosedError);
+        }
+        Ok(read_buf)
+    }
+    fn prompt_for_factory_idx(num_factories: usize) -> Result<usize, InvalidMoveError> {
+        println!("Which factory? enter index [1-{}]", num_factories);
+        let idx = Self::read_line()?
+            .trim()
+            .parse::<usize>()
+            .map_err(InvalidMoveError::from_parse)?;
+        if idx > num_factories || idx < 1 {
+            return Err(InvalidMoveError::BadFactoryRequestError);
+        }
+        Ok(idx - 1)
+    } // fn prompt_for_factory_idx
+    fn prompt_for_tile_color() -> Result<TileColor, InvalidMoveError> {
+        println!("Which color? [r|b|g|y|w|j]");
+        match Self::read_line()?.chars().next() {
+            Some('r') => Ok(TileColor::RED),
+            Some('b') => Ok(TileColor::BLUE),
+            Some('g') => Ok(TileColor::GREEN),
+            Some('y') => Ok(TileColor::YELLOW),
+            Some('w') => Ok(TileColor::WHITE),
+            Some('j') => Ok(TileColor::JOKER),
+            Some(_) => Err(InvalidMoveError::BadColorError),
+            None => Err(InvalidMoveError::UnknownError),
+        }
+    }
+    fn prompt_for_row_idx() -> Result<usize, InvalidMoveError> {
+        println!("Which row? enter number [1-5]");
+        let row_idx = Self::read_line()?
+            .trim()
+            .parse::<usize>()
+            .map_err(InvalidMoveError::from_parse)?;
+        if !(1..=5).contains(&row_idx) {
+            return Err(InvalidMoveError::BadInputRowIdxError);
+        }
+        Ok(row_idx - 1)
+    }
+    // one pass through the menu; Ok(None) means nothing was picked yet
+    fn prompt_for_action(obs: &Observation) -> Result<Option<Action>, InvalidMoveError> {
+        let num_factories = obs.factories.len();
+        let mut write_buf = String::new();
+        if num_factories > 0 {
+            write_buf += Self::PROMPT_FACTORY_DRAW;
+        }
+        if obs.pool.values().sum::<i32>() > 0 {
+            write_buf += Self::PROMPT_POOL_DRAW;
+        }
+        write_buf += Self::PROMPT_DISCARD;
+        write_buf += Self::PROMPT_PRINT_BOARD;
+        println!("{write_buf}");
+        let action = match Self::read_line()?.chars().next() {
+            Some('f') => {
+                let factory_idx = Self::prompt_for_factory_idx(num_factories)?;
+                let color = Self::prompt_for_tile_color()?;
+                let row_idx = Self::prompt_for_row_idx()?;
+                Action::FactoryToRow {
+                    factory_idx,
+                    color,
+                    row_idx,
+                }
+            } // 'f'
+            Some('p') => {
+                let color = Self::prompt_for_tile_color()?;
+                let row_idx = Self::prompt_for_row_idx()?;
+                Action::PoolToRow { color, row_idx }
+            } // 'p'
+            Some('d') => {
+                println!("From factory or pool? [f|p]");
+                match Self::read_line()?.chars().next() {
+                    Some('f') => {
+                        let factory_idx = Self::prompt_for_factory_idx(num_factories)?;
+                        let color = Self::prompt_for_tile_color()?;
+                        Action::FactoryToFloor { factory_idx, color }
+                    }
+                    Some('p') => {
+                        let color = Self::prompt_for_tile_color()?;
+                        Action::PoolToFloor { color }
+                    }
+                    _ => return Err(InvalidMoveError::UnknownError),
+                }
+            } // 'd'
+            Some('P') => {
+                print_board(obs);
+                return Ok(None);
+            }
+            _ => return Err(InvalidMoveError::UnknownError),
+        };
+        Ok(Some(action))
+    } // fn prompt_for_action
+} // impl TerminalAgent
+
+impl PlayerAgent for TerminalAgent {
+    fn name(&self) -> String {
+        String::from("human")
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        print_board(obs);
+        loop {
+            match Self::prompt_for_action(obs) {
+                Ok(Some(action)) => match obs.check(&action) {
+                    Ok(()) => return action,
+                    Err(error) => self.on_invalid_action(action, error),
+                },
+                Ok(None) => continue,
+                // piped input ran out, or the terminal went away. the table still needs a move
+                Err(InvalidMoveError::InputClosedError) => {
+                    println!("stdin closed, player {} forfeits the turn", obs.seat + 1);
+                    return ForfeitAgent {}.choose_action(obs);
+                }
+                Err(error) => println!("ERROR: {:?}; try again", error),
+            }
+        }
+    } // fn choose_action
+    fn on_invalid_action(&mut self, action: Action, error: RuleError) {
+        println!("ERROR: selected move: {:?} FAILED: {}", action, error);
+    }
+    fn choose_column(&mut self, board: &PlayerBoard, row_idx: usize, columns: &[usize]) -> usize {
+        println!("{}", draw_player_board(board));
+        let choices: Vec<String> = columns
+            .iter()
+            .map(|col_idx| (col_idx + 1).to_string())
+            .collect();
+        loop {
+            println!(
+                "Row {} is full. Which column does it go in? [{}]",
+                row_idx + 1,
+                choices.join("|")
+            );
+            match Self::read_line().map(|line| line.trim().parse::<usize>()) {
+                Ok(Ok(col_idx)) if col_idx >= 1 => return col_idx - 1,
+                Err(InvalidMoveError::InputClosedError) => return columns[0],
+                _ => println!("ERROR: that's not a column; try again"),
+            }
+        }
+    } // fn choose_column
+    fn on_invalid_column(&mut self, col_idx: usize, error: RuleError) {
+        println!("ERROR: column {} FAILED: {}", col_idx + 1, error);
+    }
+} // impl PlayerAgent for TerminalAgent
+
+#[cfg(test)]
+use crate::NUM_COLORS;
+
+#[test]
+fn test_observation_from_state() {
+    let mut state = GameState::new(3, 11);
+    let action = state.legal_actions()[0];
+    state.apply(action).unwrap();
+    let obs = Observation::from_state(&state);
+    assert_eq!(obs.seat, 1);
+    assert_eq!(obs.opponents.len(), 2);
+    // seat 0 moved last, so it comes up after seat 2
+    assert_eq!(&obs.opponents[1], state.player_board(0));
+    assert_eq!(obs.legal_actions, state.legal_actions());
+    assert_eq!(obs.factories.len(), state.factories().len());
+}
+#[test]
+fn test_observation_says_why_a_move_is_illegal() {
+    let mut obs = Observation::from_state(&GameState::new(2, 5));
+    for action in obs.legal_actions.iter() {
+        assert_eq!(obs.check(action), Ok(()));
+    }
+    let color = *obs.factories[0].keys().next().unwrap();
+    let missing = TileColor::from_integer(
+        (0..NUM_COLORS)
+            .find(|ii| !obs.factories[0].contains_key(&TileColor::from_integer(*ii)))
+            .unwrap(),
+    );
+    let draw = |factory_idx, color, row_idx| Action::FactoryToRow {
+        factory_idx,
+        color,
+        row_idx,
+    };
+    assert_eq!(
+        obs.check(&draw(99, color, 0)),
+        Err(RuleError::NoSuchFactory)
+    );
+    assert_eq!(
+        obs.check(&draw(0, missing, 0)),
+        Err(RuleError::BadFactoryRequest)
+    );
+    assert_eq!(
+        obs.check(&Action::PoolToFloor { color }),
+        Err(RuleError::BadPoolRequest)
+    );
+    assert_eq!(obs.check(&draw(0, color, 7)), Err(RuleError::BadRowIdx));
+    // legal_actions has to catch up with the board, or check would let these through
+    obs.my_board.rows[1] = (1, missing);
+    obs.my_board.grid[[3, crate::get_col_idx(3, color)]] = true;
+    obs.legal_actions = legal_actions_for(&obs.factories, &obs.pool, &obs.my_board);
+    assert_eq!(
+        obs.check(&draw(0, color, 1)),
+        Err(RuleError::RowHasDifferentColor)
+    );
+    assert_eq!(
+        obs.check(&draw(0, color, 3)),
+        Err(RuleError::ColorAlreadyOnWall)
+    );
+}
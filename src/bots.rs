@@ -0,0 +1,224 @@
+use crate::{Action, Observation, PlayerAgent, PlayerBoard, RuleSet, TileColor};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashMap;
+
+// picks any legal move, uniformly
+#[derive(Debug)]
+pub struct RandomAgent {
+    rng: StdRng,
+}
+impl Default for RandomAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl RandomAgent {
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+    pub fn with_rng(rng: StdRng) -> Self {
+        RandomAgent { rng }
+    }
+}
+impl PlayerAgent for RandomAgent {
+    fn name(&self) -> String {
+        String::from("random")
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        *obs.legal_actions
+            .choose(&mut self.rng)
+            .expect("no legal actions to choose from")
+    }
+    fn choose_column(&mut self, _board: &PlayerBoard, _row_idx: usize, columns: &[usize]) -> usize {
+        *columns
+            .choose(&mut self.rng)
+            .expect("no columns to choose from")
+    }
+} // impl PlayerAgent for RandomAgent
+
+// takes whatever scores best right now: wall points from a completed row, minus what the floor costs us
+#[derive(Debug, Default)]
+pub struct GreedyAgent {}
+impl GreedyAgent {
+    pub fn new() -> Self {
+        GreedyAgent {}
+    }
+    // (immediate score, tiles that made it onto a pattern line)
+    pub fn evaluate(obs: &Observation, action: &Action) -> (i32, i32) {
+        evaluate_move(
+            &obs.factories,
+            &obs.pool,
+            obs.first_player_marker_in_pool,
+            &obs.my_board,
+            &obs.rules,
+            action,
+        )
+    } // fn evaluate
+}
+// split out from GreedyAgent::evaluate so search can score moves without building an Observation
+pub(crate) fn evaluate_move(
+    factories: &[HashMap<TileColor, i32>],
+    pool: &HashMap<TileColor, i32>,
+    first_player_marker_in_pool: bool,
+    board: &PlayerBoard,
+    rules: &RuleSet,
+    action: &Action,
+) -> (i32, i32) {
+    let color = action.color();
+    let (num_tiles, from_pool) = match *action {
+        Action::FactoryToRow { factory_idx, .. } | Action::FactoryToFloor { factory_idx, .. } => {
+            (*factories[factory_idx].get(&color).unwrap_or(&0), false)
+        }
+        Action::PoolToRow { .. } | Action::PoolToFloor { .. } => {
+            (*pool.get(&color).unwrap_or(&0), true)
+        }
+    };
+    let old_penalties = board.num_penalties();
+    let mut new_penalties = old_penalties;
+    if from_pool && first_player_marker_in_pool {
+        new_penalties += 1;
+    }
+    let mut points = 0;
+    let mut num_placed = 0;
+    match action.row_idx() {
+        Some(row_idx) => {
+            let room = row_idx as i32 + 1 - board.rows[row_idx].0;
+            num_placed = std::cmp::min(num_tiles, room);
+            new_penalties += num_tiles - num_placed;
+            // a joker goes on the wall as whatever color its line already is
+            let line_color = match color {
+                TileColor::JOKER => board.rows[row_idx].1,
+                color => color,
+            };
+            // a line with no column left on the gray wall scores nothing
+            let columns = board.wall_columns(row_idx, line_color);
+            if room > 0 && num_placed == room && !columns.is_empty() {
+                points = board.score_at(row_idx, board.best_column(row_idx, &columns));
+            }
+        }
+        None => new_penalties += num_tiles,
+    }
+    points -= rules.penalty(new_penalties) - rules.penalty(old_penalties);
+    (points, num_placed)
+} // fn evaluate_move
+impl PlayerAgent for GreedyAgent {
+    fn name(&self) -> String {
+        String::from("greedy")
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        *obs.legal_actions
+            .iter()
+            .max_by_key(|action| Self::evaluate(obs, action))
+            .expect("no legal actions to choose from")
+    }
+} // impl PlayerAgent for GreedyAgent
+
+// gives up a seat nobody is playing any more, whether they never came back to a remote seat or
+// closed the terminal: everything goes straight to the floor
+#[derive(Debug)]
+pub(crate) struct ForfeitAgent {}
+impl PlayerAgent for ForfeitAgent {
+    fn name(&self) -> String {
+        String::from("forfeit")
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        *obs.legal_actions
+            .iter()
+            .find(|action| {
+                matches!(
+                    action,
+                    Action::FactoryToFloor { .. } | Action::PoolToFloor { .. }
+                )
+            })
+            .unwrap_or(&obs.legal_actions[0])
+    }
+}
+
+#[cfg(test)]
+use crate::{GameState, Variant};
+
+#[test]
+fn test_bots_play_legal_moves() {
+    let mut state = GameState::new(3, 5);
+    let mut agents: Vec<Box<dyn PlayerAgent>> = vec![
+        Box::new(GreedyAgent::new()),
+        Box::new(RandomAgent::new()),
+        Box::new(GreedyAgent::new()),
+    ];
+    let mut num_turns = 0;
+    while !state.is_terminal() && num_turns < 2000 {
+        let obs = Observation::from_state(&state);
+        let action = agents[state.current_player()].choose_action(&obs);
+        assert!(obs.legal_actions.contains(&action));
+        state.apply(action).unwrap();
+        num_turns += 1;
+    }
+    assert!(state.is_terminal());
+}
+#[test]
+fn test_greedy_completes_row_over_discard() {
+    let state = GameState::new(2, 9);
+    let mut obs = Observation::from_state(&state);
+    let (factory_idx, color, num_tiles) = obs
+        .factories
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, fact)| fact.iter().map(move |(color, num)| (idx, *color, *num)))
+        .next()
+        .unwrap();
+    // leave exactly enough room in row 3 for what's on the factory
+    let row_idx = 3;
+    obs.my_board.rows[row_idx] = (row_idx as i32 + 1 - num_tiles, color);
+    let completes = Action::FactoryToRow {
+        factory_idx,
+        color,
+        row_idx,
+    };
+    let discard = Action::FactoryToFloor { factory_idx, color };
+    assert_eq!(GreedyAgent::evaluate(&obs, &completes), (1, num_tiles));
+    assert_eq!(
+        GreedyAgent::evaluate(&obs, &discard).0,
+        -obs.rules.penalty(num_tiles)
+    );
+    obs.legal_actions = vec![discard, completes];
+    assert_eq!(GreedyAgent::new().choose_action(&obs), completes);
+}
+#[test]
+fn test_joker_scores_as_its_lines_color() {
+    // a joker finishing a blue line in row 1, with tiles above and below in column 0. on the
+    // gray wall those are red, yellow and blue
+    let pool: HashMap<TileColor, i32> = [(TileColor::JOKER, 1)].into_iter().collect();
+    let finish = Action::PoolToRow {
+        color: TileColor::JOKER,
+        row_idx: 1,
+    };
+    let rules = RuleSet::default();
+    for variant in [Variant::Standard, Variant::Gray] {
+        let mut board = variant.board();
+        board.rows[1] = (1, TileColor::BLUE);
+        for (row_idx, color) in [
+            (0, TileColor::RED),
+            (2, TileColor::YELLOW),
+            (3, TileColor::BLUE),
+        ] {
+            board.grid[[row_idx, 0]] = true;
+            if let Some(wall_colors) = board.wall_colors.as_mut() {
+                wall_colors[[row_idx, 0]] = color;
+            }
+        }
+        // blue's spot on the standard wall is column 0, four in a column. the gray wall has
+        // a blue in column 0 already, so the best it can do is a tile on its own
+        let points = match variant {
+            Variant::Standard => 4,
+            Variant::Gray => 1,
+        };
+        assert_eq!(
+            evaluate_move(&[], &pool, false, &board, &rules, &finish),
+            (points, 1),
+            "{:?}",
+            variant
+        );
+    }
+}
@@ -0,0 +1,375 @@
+// time controls: how long each player gets to move, and what happens when they take longer. the
+// table keeps the clocks; each Player only hears how long it's got for the move in front of it
+use crate::{agent_from_name, Action, Observation, PlayerAgent, PlayerBoard, RuleError};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// what gets played for somebody who ran out of time
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimeoutAction {
+    DiscardSmallest, // whichever pick puts the fewest tiles on the floor
+    Bot(String),     // whatever this bot would do in their place
+}
+impl TimeoutAction {
+    // "discard", or any bot agent_from_name knows
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "discard" => Some(TimeoutAction::DiscardSmallest),
+            "human" => None,
+            _ => agent_from_name(name, 0).map(|_| TimeoutAction::Bot(String::from(name))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeControl {
+    pub per_move: Option<Duration>, // the most any one move can take
+    pub game: Option<Duration>,     // each player's clock for the whole game
+    pub increment: Duration,        // goes back on the game clock after every move
+    pub on_timeout: TimeoutAction,
+}
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl {
+            per_move: None,
+            game: None,
+            increment: Duration::ZERO,
+            on_timeout: TimeoutAction::DiscardSmallest,
+        }
+    }
+}
+
+// "90", "90s", "1500ms" or "5m"
+pub fn parse_duration(val: &str) -> Result<Duration, String> {
+    let (num, unit) = match val.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => val.split_at(idx),
+        None => (val, "s"),
+    };
+    let num: u64 = num
+        .parse()
+        .map_err(|_| format!("not a length of time: {}", val))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(num)),
+        "s" => Ok(Duration::from_secs(num)),
+        "m" => Ok(Duration::from_secs(num * 60)),
+        _ => Err(format!("not a length of time: {}", val)),
+    }
+}
+// a game clock with an optional increment, like "5m+2s"
+pub fn parse_clock(val: &str) -> Result<(Duration, Duration), String> {
+    match val.split_once('+') {
+        Some((game, increment)) => Ok((parse_duration(game)?, parse_duration(increment)?)),
+        None => Ok((parse_duration(val)?, Duration::ZERO)),
+    }
+}
+
+// every player's clock, by seat. a resumed game starts them all over
+#[derive(Clone, Debug)]
+pub(crate) struct Clocks {
+    control: TimeControl,
+    game_left: Vec<Duration>, // only counts if there's a game clock
+    running: Option<(usize, Instant)>,
+}
+impl Clocks {
+    pub(crate) fn new(control: &TimeControl, num_players: usize) -> Self {
+        Clocks {
+            control: control.clone(),
+            game_left: vec![control.game.unwrap_or(Duration::ZERO); num_players],
+            running: None,
+        }
+    }
+    // how long `seat` has for its move, counting down while it's their turn
+    pub(crate) fn time_left(&self, seat: usize) -> Option<Duration> {
+        let elapsed = match self.running {
+            Some((running, started)) if running == seat => started.elapsed(),
+            _ => Duration::ZERO,
+        };
+        let game = self
+            .control
+            .game
+            .map(|_| self.game_left[seat].saturating_sub(elapsed));
+        let per_move = self
+            .control
+            .per_move
+            .map(|limit| limit.saturating_sub(elapsed));
+        match (game, per_move) {
+            (Some(game), Some(per_move)) => Some(game.min(per_move)),
+            (game, per_move) => game.or(per_move),
+        }
+    } // fn time_left
+    pub(crate) fn all_left(&self) -> Vec<Duration> {
+        (0..self.game_left.len())
+            .filter_map(|seat| self.time_left(seat))
+            .collect()
+    }
+    // starts `seat`'s clock and says how long they've got
+    pub(crate) fn start(&mut self, seat: usize) -> Option<Duration> {
+        let time_left = self.time_left(seat);
+        self.running = Some((seat, Instant::now()));
+        time_left
+    }
+    pub(crate) fn stop(&mut self) {
+        if let Some((seat, started)) = self.running.take() {
+            if self.control.game.is_some() {
+                self.game_left[seat] =
+                    self.game_left[seat].saturating_sub(started.elapsed()) + self.control.increment;
+            }
+        }
+    }
+} // impl Clocks
+
+// the floor move that costs the least
+fn discard_smallest(obs: &Observation) -> Action {
+    let num_tiles = |action: &Action| {
+        let source = match action {
+            Action::FactoryToFloor { factory_idx, .. } => &obs.factories[*factory_idx],
+            _ => &obs.pool,
+        };
+        *source.get(&action.color()).unwrap_or(&0)
+    };
+    obs.legal_actions
+        .iter()
+        .filter(|action| action.row_idx().is_none())
+        .min_by_key(|action| num_tiles(action))
+        .copied()
+        .unwrap_or(obs.legal_actions[0])
+}
+
+// what the Player wants from an agent running on its own thread
+enum Call {
+    Choose(u32, Observation), // numbered, so an answer that comes in too late can be told apart
+    Invalid(Action, RuleError),
+    Column(PlayerBoard, usize, Vec<usize>), // tiling the wall isn't on the clock
+    InvalidColumn(usize, RuleError),
+    RoundEnd(PlayerBoard),
+    GameEnd(i32),
+}
+
+// an agent on the clock. it thinks on its own thread so the Player can stop waiting on it, and
+// a move it comes up with after time ran out gets turned down with OUT_OF_TIME
+pub(crate) struct ClockedAgent {
+    name: String,
+    calls: mpsc::Sender<Call>,
+    moves: mpsc::Receiver<(u32, Action)>,
+    columns: mpsc::Receiver<usize>,
+    waiting_on: Arc<AtomicU32>, // the turn we still want an answer to; 0 for none
+    turn: u32,
+    stand_in: Option<Box<dyn PlayerAgent>>, // plays for us on a timeout; None just discards
+}
+impl ClockedAgent {
+    pub(crate) fn new(mut agent: Box<dyn PlayerAgent>, on_timeout: &TimeoutAction) -> Self {
+        let name = agent.name();
+        let (calls, call_rx) = mpsc::channel();
+        let (move_tx, moves) = mpsc::channel();
+        let (column_tx, columns) = mpsc::channel();
+        let waiting_on = Arc::new(AtomicU32::new(0));
+        let wanted = waiting_on.clone();
+        // there's nobody to join this; a person who never answers keeps it around until we exit
+        thread::spawn(move || {
+            for call in call_rx {
+                match call {
+                    Call::Choose(turn, obs) => {
+                        if wanted.load(Ordering::SeqCst) != turn {
+                            continue; // missed it while still thinking about the last one
+                        }
+                        let action = agent.choose_action(&obs);
+                        if wanted.load(Ordering::SeqCst) != turn {
+                            agent.on_invalid_action(action, RuleError::OutOfTime);
+                        } else if move_tx.send((turn, action)).is_err() {
+                            return;
+                        }
+                    }
+                    Call::Invalid(action, error) => agent.on_invalid_action(action, error),
+                    Call::Column(board, row_idx, columns) => {
+                        let col_idx = agent.choose_column(&board, row_idx, &columns);
+                        if column_tx.send(col_idx).is_err() {
+                            return;
+                        }
+                    }
+                    Call::InvalidColumn(col_idx, error) => agent.on_invalid_column(col_idx, error),
+                    Call::RoundEnd(board) => agent.on_round_end(&board),
+                    Call::GameEnd(final_score) => {
+                        agent.on_game_end(final_score);
+                        return;
+                    }
+                }
+            }
+        });
+        let stand_in = match on_timeout {
+            TimeoutAction::Bot(bot) => agent_from_name(bot, rand::random()),
+            TimeoutAction::DiscardSmallest => None,
+        };
+        ClockedAgent {
+            name,
+            calls,
+            moves,
+            columns,
+            waiting_on,
+            turn: 0,
+            stand_in,
+        }
+    } // fn new
+    fn timed_out(&mut self, obs: &Observation) -> Action {
+        self.waiting_on.store(0, Ordering::SeqCst);
+        match self.stand_in.as_mut() {
+            Some(stand_in) => stand_in.choose_action(obs),
+            None => discard_smallest(obs),
+        }
+    }
+} // impl ClockedAgent
+
+impl PlayerAgent for ClockedAgent {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        let time_left = match obs.time_left {
+            Some(time_left) if !time_left.is_zero() => time_left,
+            Some(_) => return self.timed_out(obs),
+            None => Duration::MAX,
+        };
+        let deadline = Instant::now().checked_add(time_left);
+        self.turn += 1;
+        self.waiting_on.store(self.turn, Ordering::SeqCst);
+        if self
+            .calls
+            .send(Call::Choose(self.turn, obs.clone()))
+            .is_err()
+        {
+            return self.timed_out(obs);
+        }
+        loop {
+            let wait = deadline.map_or(Duration::MAX, |deadline| {
+                deadline.saturating_duration_since(Instant::now())
+            });
+            match self.moves.recv_timeout(wait) {
+                Ok((turn, action)) if turn == self.turn => return action,
+                Ok(_) => continue, // an old answer that beat the check on the other side
+                Err(_) => return self.timed_out(obs),
+            }
+        }
+    } // fn choose_action
+    fn on_invalid_action(&mut self, action: Action, error: RuleError) {
+        let _ = self.calls.send(Call::Invalid(action, error));
+    }
+    fn choose_column(&mut self, board: &PlayerBoard, row_idx: usize, columns: &[usize]) -> usize {
+        let call = Call::Column(board.clone(), row_idx, columns.to_vec());
+        match self.calls.send(call).map(|_| self.columns.recv()) {
+            Ok(Ok(col_idx)) => col_idx,
+            _ => board.best_column(row_idx, columns),
+        }
+    }
+    fn on_invalid_column(&mut self, col_idx: usize, error: RuleError) {
+        let _ = self.calls.send(Call::InvalidColumn(col_idx, error));
+    }
+    fn on_round_end(&mut self, board: &PlayerBoard) {
+        let _ = self.calls.send(Call::RoundEnd(board.clone()));
+    }
+    fn on_game_end(&mut self, final_score: i32) {
+        let _ = self.calls.send(Call::GameEnd(final_score));
+    }
+} // impl PlayerAgent for ClockedAgent
+
+#[cfg(test)]
+use crate::{run_game, GameOptions, GameState, GreedyAgent};
+
+// thinks for a good while, then plays the first legal move
+#[cfg(test)]
+struct SlowAgent {
+    delay: Duration,
+    timeouts: mpsc::Sender<RuleError>,
+}
+#[cfg(test)]
+impl PlayerAgent for SlowAgent {
+    fn name(&self) -> String {
+        String::from("slow")
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        thread::sleep(self.delay);
+        obs.legal_actions[0]
+    }
+    fn on_invalid_action(&mut self, _action: Action, error: RuleError) {
+        let _ = self.timeouts.send(error);
+    }
+}
+
+#[test]
+fn test_time_controls_parse() {
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("1500ms"), Ok(Duration::from_millis(1500)));
+    assert_eq!(
+        parse_clock("5m+2s"),
+        Ok((Duration::from_secs(300), Duration::from_secs(2)))
+    );
+    assert!(parse_duration("5h").is_err() && parse_clock("+2s").is_err());
+    assert_eq!(
+        TimeoutAction::from_name("greedy"),
+        Some(TimeoutAction::Bot(String::from("greedy")))
+    );
+    assert_eq!(TimeoutAction::from_name("human"), None);
+}
+#[test]
+fn test_clocks_run_only_on_your_turn() {
+    let control = TimeControl {
+        per_move: Some(Duration::from_secs(10)),
+        game: Some(Duration::from_secs(60)),
+        increment: Duration::from_secs(2),
+        ..Default::default()
+    };
+    let mut clocks = Clocks::new(&control, 2);
+    // the per-move limit is the tighter one until the game clock runs down
+    assert_eq!(clocks.start(0), Some(Duration::from_secs(10)));
+    thread::sleep(Duration::from_millis(20));
+    assert!(clocks.time_left(0).unwrap() < Duration::from_secs(10));
+    assert_eq!(clocks.time_left(1), Some(Duration::from_secs(10)));
+    clocks.stop();
+    assert!(clocks.game_left[0] > Duration::from_secs(61));
+    assert!(clocks.game_left[0] < Duration::from_secs(62));
+    clocks.game_left[1] = Duration::from_secs(3);
+    assert_eq!(clocks.all_left()[1], Duration::from_secs(3));
+}
+#[test]
+fn test_slow_agent_gets_the_default_move() {
+    let (timeouts, timeout_rx) = mpsc::channel();
+    let slow = SlowAgent {
+        delay: Duration::from_millis(200),
+        timeouts,
+    };
+    let mut agent = ClockedAgent::new(Box::new(slow), &TimeoutAction::DiscardSmallest);
+    let mut obs = Observation::from_state(&GameState::new(2, 8));
+    obs.time_left = Some(Duration::from_millis(20));
+    let action = agent.choose_action(&obs);
+    assert_eq!(action, discard_smallest(&obs));
+    assert!(action.row_idx().is_none());
+    // and once it does make up its mind, it hears it was too late
+    assert_eq!(
+        timeout_rx.recv_timeout(Duration::from_secs(5)),
+        Ok(RuleError::OutOfTime)
+    );
+    // with enough time it gets to play its own move
+    obs.time_left = Some(Duration::from_secs(5));
+    assert_eq!(agent.choose_action(&obs), obs.legal_actions[0]);
+}
+#[test]
+fn test_game_on_a_move_clock() {
+    let (timeouts, _timeout_rx) = mpsc::channel();
+    let slow = SlowAgent {
+        delay: Duration::from_millis(50),
+        timeouts,
+    };
+    let options = GameOptions {
+        time_control: Some(TimeControl {
+            per_move: Some(Duration::from_millis(5)),
+            on_timeout: TimeoutAction::Bot(String::from("greedy")),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    run_game(
+        vec![Box::new(slow), Box::new(GreedyAgent::new())],
+        12,
+        &options,
+    );
+}
@@ -0,0 +1,1410 @@
+use crate::protocol::{self, Move};
+use crate::{
+    finalize_score, get_col_idx, GameBoard, Observation, Player, RuleSet, Standings, TileColor,
+    NUM_COLORS_AS_USIZE, NUM_TILE_COLORS,
+};
+use ndarray::{arr2, Array2};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// One complete turn: where the tiles come from and where they go.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Move", into = "Move")]
+pub enum Action {
+    FactoryToRow {
+        factory_idx: usize,
+        color: TileColor,
+        row_idx: usize,
+    },
+    PoolToRow {
+        color: TileColor,
+        row_idx: usize,
+    },
+    FactoryToFloor {
+        factory_idx: usize,
+        color: TileColor,
+    },
+    PoolToFloor {
+        color: TileColor,
+    },
+}
+impl Action {
+    pub fn color(&self) -> TileColor {
+        match *self {
+            Action::FactoryToRow { color, .. }
+            | Action::PoolToRow { color, .. }
+            | Action::FactoryToFloor { color, .. }
+            | Action::PoolToFloor { color } => color,
+        }
+    }
+    pub fn row_idx(&self) -> Option<usize> {
+        match *self {
+            Action::FactoryToRow { row_idx, .. } | Action::PoolToRow { row_idx, .. } => {
+                Some(row_idx)
+            }
+            Action::FactoryToFloor { .. } | Action::PoolToFloor { .. } => None,
+        }
+    }
+    // the same req_type messages a Player sends the game board, plus the row the tiles go on
+    pub fn to_json(&self) -> json::JsonValue {
+        protocol::to_json(self)
+    }
+    pub fn from_json(val: &json::JsonValue) -> Result<Self, String> {
+        protocol::from_json(val)
+    }
+} // impl Action
+
+/// Everything that happened as a result of one `GameState::apply`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    TilesTaken {
+        player: usize,
+        factory_idx: Option<usize>, // None means the pool
+        color: TileColor,
+        num_tiles: i32,
+    },
+    FirstPlayerMarkerTaken {
+        player: usize,
+    },
+    TilesPlaced {
+        player: usize,
+        row_idx: usize,
+        color: TileColor,
+        num_tiles: i32,
+    },
+    TilesToFloor {
+        player: usize,
+        color: TileColor,
+        num_tiles: i32,
+    },
+    WallTiled {
+        player: usize,
+        row_idx: usize,
+        col_idx: usize,
+        color: TileColor,
+        points: i32,
+    },
+    // gray wall only: a full pattern line with no column left for it goes on the floor
+    LineDiscarded {
+        player: usize,
+        row_idx: usize,
+        color: TileColor,
+        num_tiles: i32,
+    },
+    FloorPenalty {
+        player: usize,
+        points: i32,
+    },
+    RoundEnded {
+        round: u32,
+    },
+    RoundStarted {
+        round: u32,
+        first_player: usize,
+        factories: Vec<HashMap<TileColor, i32>>, // as dealt
+    },
+    FinalBonus {
+        player: usize,
+        points: i32,
+    },
+    GameEnded {
+        scores: Vec<i32>,
+    },
+}
+pub type Events = Vec<Event>;
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::TilesTaken {
+                player,
+                factory_idx,
+                color,
+                num_tiles,
+            } => match factory_idx {
+                Some(idx) => write!(
+                    f,
+                    "player {} took {} {} from factory {}",
+                    player + 1,
+                    num_tiles,
+                    color,
+                    idx + 1
+                ),
+                None => write!(
+                    f,
+                    "player {} took {} {} from the pool",
+                    player + 1,
+                    num_tiles,
+                    color
+                ),
+            },
+            Event::FirstPlayerMarkerTaken { player } => {
+                write!(f, "player {} took the first player marker", player + 1)
+            }
+            Event::TilesPlaced {
+                player,
+                row_idx,
+                color,
+                num_tiles,
+            } => write!(
+                f,
+                "player {} put {} {} on row {}",
+                player + 1,
+                num_tiles,
+                color,
+                row_idx + 1
+            ),
+            Event::TilesToFloor {
+                player,
+                color,
+                num_tiles,
+            } => write!(
+                f,
+                "player {} dropped {} {} on the floor",
+                player + 1,
+                num_tiles,
+                color
+            ),
+            Event::WallTiled {
+                player,
+                row_idx,
+                col_idx,
+                color,
+                points,
+            } => write!(
+                f,
+                "player {} tiled {} at row {} column {} for {} points",
+                player + 1,
+                color,
+                row_idx + 1,
+                col_idx + 1,
+                points
+            ),
+            Event::LineDiscarded {
+                player,
+                row_idx,
+                color,
+                num_tiles,
+            } => write!(
+                f,
+                "player {} had nowhere on the wall for row {}, so {} {} went on the floor",
+                player + 1,
+                row_idx + 1,
+                num_tiles,
+                color
+            ),
+            Event::FloorPenalty { player, points } => {
+                write!(
+                    f,
+                    "player {} lost {} points on the floor",
+                    player + 1,
+                    points
+                )
+            }
+            Event::RoundEnded { round } => write!(f, "round {} is over", round),
+            Event::RoundStarted {
+                round,
+                first_player,
+                ..
+            } => write!(
+                f,
+                "round {} dealt, player {} goes first",
+                round,
+                first_player + 1
+            ),
+            Event::FinalBonus { player, points } => {
+                write!(f, "player {} gets {} bonus points", player + 1, points)
+            }
+            Event::GameEnded { scores } => write!(f, "game over, final scores {:?}", scores),
+        }
+    }
+} // impl fmt::Display for Event
+impl Event {
+    pub fn to_json(&self) -> json::JsonValue {
+        match self {
+            Event::TilesTaken {
+                player,
+                factory_idx,
+                color,
+                num_tiles,
+            } => json::object! {
+                "event": "TILES_TAKEN",
+                "player": *player,
+                "factory_idx": *factory_idx, // null for the pool
+                "color": color.to_string(),
+                "num_tiles": *num_tiles,
+            },
+            Event::FirstPlayerMarkerTaken { player } => json::object! {
+                "event": "FIRST_PLAYER_MARKER_TAKEN",
+                "player": *player,
+            },
+            Event::TilesPlaced {
+                player,
+                row_idx,
+                color,
+                num_tiles,
+            } => json::object! {
+                "event": "TILES_PLACED",
+                "player": *player,
+                "row_idx": *row_idx,
+                "color": color.to_string(),
+                "num_tiles": *num_tiles,
+            },
+            Event::TilesToFloor {
+                player,
+                color,
+                num_tiles,
+            } => json::object! {
+                "event": "TILES_TO_FLOOR",
+                "player": *player,
+                "color": color.to_string(),
+                "num_tiles": *num_tiles,
+            },
+            Event::WallTiled {
+                player,
+                row_idx,
+                col_idx,
+                color,
+                points,
+            } => json::object! {
+                "event": "WALL_TILED",
+                "player": *player,
+                "row_idx": *row_idx,
+                "col_idx": *col_idx,
+                "color": color.to_string(),
+                "points": *points,
+            },
+            Event::LineDiscarded {
+                player,
+                row_idx,
+                color,
+                num_tiles,
+            } => json::object! {
+                "event": "LINE_DISCARDED",
+                "player": *player,
+                "row_idx": *row_idx,
+                "color": color.to_string(),
+                "num_tiles": *num_tiles,
+            },
+            Event::FloorPenalty { player, points } => json::object! {
+                "event": "FLOOR_PENALTY",
+                "player": *player,
+                "points": *points,
+            },
+            Event::RoundEnded { round } => json::object! {
+                "event": "ROUND_ENDED",
+                "round": *round,
+            },
+            Event::RoundStarted {
+                round,
+                first_player,
+                factories,
+            } => json::object! {
+                "event": "ROUND_STARTED",
+                "round": *round,
+                "first_player": *first_player,
+                "factories": factories_to_json(factories),
+            },
+            Event::FinalBonus { player, points } => json::object! {
+                "event": "FINAL_BONUS",
+                "player": *player,
+                "points": *points,
+            },
+            Event::GameEnded { scores } => json::object! {
+                "event": "GAME_ENDED",
+                "scores": scores.clone(),
+            },
+        }
+    } // fn to_json
+    pub fn from_json(val: &json::JsonValue) -> Result<Self, String> {
+        let usize_field = |key: &str| {
+            val[key]
+                .as_usize()
+                .ok_or(format!("{} event is missing {}", val["event"], key))
+        };
+        let i32_field = |key: &str| {
+            val[key]
+                .as_i32()
+                .ok_or(format!("{} event is missing {}", val["event"], key))
+        };
+        let color_field = || parse_color(val["color"].as_str().unwrap_or(""));
+        let event = match val["event"].as_str().unwrap_or("") {
+            "TILES_TAKEN" => Event::TilesTaken {
+                player: usize_field("player")?,
+                factory_idx: val["factory_idx"].as_usize(),
+                color: color_field()?,
+                num_tiles: i32_field("num_tiles")?,
+            },
+            "FIRST_PLAYER_MARKER_TAKEN" => Event::FirstPlayerMarkerTaken {
+                player: usize_field("player")?,
+            },
+            "TILES_PLACED" => Event::TilesPlaced {
+                player: usize_field("player")?,
+                row_idx: usize_field("row_idx")?,
+                color: color_field()?,
+                num_tiles: i32_field("num_tiles")?,
+            },
+            "TILES_TO_FLOOR" => Event::TilesToFloor {
+                player: usize_field("player")?,
+                color: color_field()?,
+                num_tiles: i32_field("num_tiles")?,
+            },
+            "WALL_TILED" => Event::WallTiled {
+                player: usize_field("player")?,
+                row_idx: usize_field("row_idx")?,
+                col_idx: usize_field("col_idx")?,
+                color: color_field()?,
+                points: i32_field("points")?,
+            },
+            "LINE_DISCARDED" => Event::LineDiscarded {
+                player: usize_field("player")?,
+                row_idx: usize_field("row_idx")?,
+                color: color_field()?,
+                num_tiles: i32_field("num_tiles")?,
+            },
+            "FLOOR_PENALTY" => Event::FloorPenalty {
+                player: usize_field("player")?,
+                points: i32_field("points")?,
+            },
+            "ROUND_ENDED" => Event::RoundEnded {
+                round: usize_field("round")? as u32,
+            },
+            "ROUND_STARTED" => Event::RoundStarted {
+                round: usize_field("round")? as u32,
+                first_player: usize_field("first_player")?,
+                factories: factories_from_json(&val["factories"])?,
+            },
+            "FINAL_BONUS" => Event::FinalBonus {
+                player: usize_field("player")?,
+                points: i32_field("points")?,
+            },
+            "GAME_ENDED" => Event::GameEnded {
+                scores: val["scores"]
+                    .members()
+                    .map(|score| score.as_i32())
+                    .collect::<Option<Vec<i32>>>()
+                    .ok_or("GAME_ENDED scores are numbers")?,
+            },
+            other => return Err(format!("unknown event: {}", other)),
+        };
+        Ok(event)
+    } // fn from_json
+} // impl Event
+
+// why a move got turned down. goes over the wire as its code, e.g. "ROW_HAS_DIFFERENT_COLOR",
+// next to the message
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RuleError {
+    GameOver,
+    NoSuchPlayer,
+    NoSuchFactory,
+    BadFactoryRequest,
+    BadPoolRequest,
+    BadColor,
+    BadRowIdx,
+    RowHasDifferentColor,
+    ColorAlreadyOnWall,
+    BadMessage,
+    NotAllowed,      // none of the above, but it's still not one of the legal actions
+    OutOfTime,       // the clock ran out, and the table played a move instead
+    BadColumn,       // gray wall only: a full pattern line can't go in that column
+    JokerNeedsColor, // jokers can't start a pattern line
+}
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            RuleError::GameOver => "the game is already over",
+            RuleError::NoSuchPlayer => "there's nobody in that seat",
+            RuleError::NoSuchFactory => "there's no factory with that index",
+            RuleError::BadFactoryRequest => "that factory doesn't have any tiles of that color",
+            RuleError::BadPoolRequest => "the pool doesn't have any tiles of that color",
+            RuleError::BadColor => "not a tile color",
+            RuleError::BadRowIdx => "row index out of range",
+            RuleError::RowHasDifferentColor => "that row already holds a different color",
+            RuleError::ColorAlreadyOnWall => "that color is already on the wall in that row",
+            RuleError::BadMessage => "that isn't a move",
+            RuleError::NotAllowed => "that move isn't allowed right now",
+            RuleError::OutOfTime => "ran out of time, so the table moved for you",
+            RuleError::BadColumn => "that column can't take that color",
+            RuleError::JokerNeedsColor => "a joker can only go on a line that already has a color",
+        };
+        write!(f, "{}", msg)
+    }
+}
+impl std::error::Error for RuleError {}
+
+/// Which wall everybody plays on.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Variant {
+    #[default]
+    Standard, // every color has its own spot in each row
+    Gray, // any spot will do, as long as the color isn't in that row or column yet
+}
+impl Variant {
+    // the names the lobby and the command line go by
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(Variant::Standard),
+            "gray" => Some(Variant::Gray),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "standard",
+            Variant::Gray => "gray",
+        }
+    }
+    // an empty board with this variant's wall
+    pub fn board(&self) -> PlayerBoard {
+        match self {
+            Variant::Standard => PlayerBoard::new(),
+            Variant::Gray => PlayerBoard {
+                wall_colors: Some(Array2::from_elem(
+                    (NUM_COLORS_AS_USIZE, NUM_COLORS_AS_USIZE),
+                    TileColor::NOCOLOR,
+                )),
+                ..PlayerBoard::new()
+            },
+        }
+    }
+} // impl Variant
+
+/// A single player's wall, pattern lines and floor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerBoard {
+    pub score: i32,
+    pub has_first_player_marker: bool,
+    pub floor_tiles: Vec<TileColor>,
+    pub grid: Array2<bool>,
+    pub rows: [(i32, TileColor); NUM_COLORS_AS_USIZE],
+    // how many of each pattern line's tiles are jokers. the rest are the line's own color, and
+    // there's always at least one of those, since a joker can't start a line
+    pub jokers: [i32; NUM_COLORS_AS_USIZE],
+    // gray wall only: the color of every tile on it, NOCOLOR where there isn't one. None is the
+    // standard wall, where the spot says what color goes there
+    pub wall_colors: Option<Array2<TileColor>>,
+}
+impl Default for PlayerBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl PlayerBoard {
+    pub fn new() -> Self {
+        PlayerBoard {
+            score: 0,
+            has_first_player_marker: false,
+            floor_tiles: Vec::new(),
+            grid: arr2(&[[false; NUM_COLORS_AS_USIZE]; NUM_COLORS_AS_USIZE]),
+            rows: [(0, TileColor::NOCOLOR); NUM_COLORS_AS_USIZE],
+            jokers: [0; NUM_COLORS_AS_USIZE],
+            wall_colors: None,
+        }
+    }
+    pub fn variant(&self) -> Variant {
+        match self.wall_colors {
+            Some(_) => Variant::Gray,
+            None => Variant::Standard,
+        }
+    }
+    pub fn num_penalties(&self) -> i32 {
+        self.floor_tiles.len() as i32 + self.has_first_player_marker as i32
+    }
+    pub fn check_valid_move(&self, color: TileColor, row_idx: usize) -> Result<(), RuleError> {
+        if color == TileColor::NOCOLOR {
+            return Err(RuleError::BadColor);
+        }
+        if row_idx >= NUM_COLORS_AS_USIZE {
+            return Err(RuleError::BadRowIdx);
+        }
+        if color == TileColor::JOKER {
+            // it goes wherever the line's color could
+            return match self.rows[row_idx].1 {
+                TileColor::NOCOLOR => Err(RuleError::JokerNeedsColor),
+                _ => Ok(()),
+            };
+        }
+        if self.has_on_wall(row_idx, color) {
+            return Err(RuleError::ColorAlreadyOnWall);
+        }
+        if !(self.rows[row_idx].1 == color || self.rows[row_idx].1 == TileColor::NOCOLOR) {
+            return Err(RuleError::RowHasDifferentColor);
+        }
+        Ok(())
+    } // fn check_valid_move
+
+    // what color the tile at row_idx, col_idx is, if there's one there
+    pub fn wall_color(&self, row_idx: usize, col_idx: usize) -> Option<TileColor> {
+        if !self.grid[[row_idx, col_idx]] {
+            return None;
+        }
+        match &self.wall_colors {
+            Some(wall_colors) => Some(wall_colors[[row_idx, col_idx]]),
+            None => Some(TileColor::from_integer(
+                ((row_idx + col_idx) % NUM_COLORS_AS_USIZE) as i32,
+            )),
+        }
+    } // fn wall_color
+    pub fn has_on_wall(&self, row_idx: usize, color: TileColor) -> bool {
+        (0..NUM_COLORS_AS_USIZE).any(|col_idx| self.wall_color(row_idx, col_idx) == Some(color))
+    }
+    // the columns a full pattern line of `color` could go in. never more than one on the
+    // standard wall, and none if there's no room for it
+    pub fn wall_columns(&self, row_idx: usize, color: TileColor) -> Vec<usize> {
+        if self.has_on_wall(row_idx, color) {
+            return Vec::new();
+        }
+        if self.wall_colors.is_none() {
+            return vec![get_col_idx(row_idx, color)];
+        }
+        (0..NUM_COLORS_AS_USIZE)
+            .filter(|col_idx| {
+                !self.grid[[row_idx, *col_idx]]
+                    && (0..NUM_COLORS_AS_USIZE)
+                        .all(|row| self.wall_color(row, *col_idx) != Some(color))
+            })
+            .collect()
+    } // fn wall_columns
+
+    // what a tile at row_idx, col_idx would score if it went on the wall now
+    pub fn score_at(&self, row_idx: usize, col_idx: usize) -> i32 {
+        let mut grid = self.grid.clone();
+        grid[[row_idx, col_idx]] = true;
+        Player::score_tile(&grid.view(), &row_idx, &col_idx)
+    }
+    // the column out of `columns` that scores the most right now, leftmost on a tie
+    pub fn best_column(&self, row_idx: usize, columns: &[usize]) -> usize {
+        let mut best = columns[0];
+        for col_idx in columns.iter().skip(1) {
+            if self.score_at(row_idx, *col_idx) > self.score_at(row_idx, best) {
+                best = *col_idx;
+            }
+        }
+        best
+    } // fn best_column
+
+    // returns the number of tiles that spilled onto the floor
+    pub(crate) fn place_tiles(&mut self, row_idx: usize, color: TileColor, num_tiles: i32) -> i32 {
+        let max_num_in_row: i32 = row_idx as i32 + 1;
+        self.rows[row_idx].0 += num_tiles;
+        let overflow = std::cmp::max(self.rows[row_idx].0 - max_num_in_row, 0);
+        self.rows[row_idx].0 -= overflow;
+        match color {
+            TileColor::JOKER => self.jokers[row_idx] += num_tiles - overflow,
+            color => self.rows[row_idx].1 = color,
+        }
+        self.drop_tiles_on_floor(color, overflow);
+        overflow
+    } // fn place_tiles
+
+    // empties a pattern line, and says what was on it: (tiles of its color, jokers)
+    pub(crate) fn clear_row(&mut self, row_idx: usize) -> (i32, i32) {
+        let (num_tiles, _) = self.rows[row_idx];
+        let jokers = self.jokers[row_idx];
+        self.rows[row_idx] = (0, TileColor::NOCOLOR);
+        self.jokers[row_idx] = 0;
+        (num_tiles - jokers, jokers)
+    }
+    pub(crate) fn drop_tiles_on_floor(&mut self, color: TileColor, num_tiles: i32) {
+        for _ in 0..num_tiles {
+            self.floor_tiles.push(color);
+        }
+    } // fn drop_tiles_on_floor
+
+    // moves full pattern lines onto the wall, top row first, and scores them, then takes the
+    // floor penalty. `choose` picks the column whenever there's more than one it could go in,
+    // which only happens on the gray wall. returns the tiles that go to the box lid
+    pub(crate) fn tile_wall(
+        &mut self,
+        player: usize,
+        rules: &RuleSet,
+        events: &mut Events,
+        choose: &mut dyn FnMut(&PlayerBoard, usize, &[usize]) -> usize,
+    ) -> Vec<TileColor> {
+        let mut num_penalties = self.num_penalties();
+        let mut returns = std::mem::take(&mut self.floor_tiles);
+        for row_idx in 0..NUM_COLORS_AS_USIZE {
+            let (num_tiles, color) = self.rows[row_idx];
+            if num_tiles != row_idx as i32 + 1 {
+                continue;
+            }
+            let columns = self.wall_columns(row_idx, color);
+            if columns.is_empty() {
+                // the whole line is stuck, so it all counts against us
+                num_penalties += num_tiles;
+                let (num_colored, num_jokers) = self.clear_row(row_idx);
+                returns.extend(vec![color; num_colored as usize]);
+                returns.extend(vec![TileColor::JOKER; num_jokers as usize]);
+                events.push(Event::LineDiscarded {
+                    player,
+                    row_idx,
+                    color,
+                    num_tiles,
+                });
+                continue;
+            }
+            let col_idx = match columns.len() {
+                1 => columns[0],
+                _ => choose(self, row_idx, &columns),
+            };
+            assert!(
+                columns.contains(&col_idx),
+                "row {} can't go in column {}",
+                row_idx + 1,
+                col_idx + 1
+            );
+            self.grid[[row_idx, col_idx]] = true;
+            if let Some(wall_colors) = self.wall_colors.as_mut() {
+                wall_colors[[row_idx, col_idx]] = color;
+            }
+            let points = Player::score_tile(&self.grid.view(), &row_idx, &col_idx);
+            self.score += points;
+            events.push(Event::WallTiled {
+                player,
+                row_idx,
+                col_idx,
+                color,
+                points,
+            });
+            // one of the line's own tiles goes on the wall, never a joker
+            let (num_colored, num_jokers) = self.clear_row(row_idx);
+            returns.extend(vec![color; num_colored as usize - 1]);
+            returns.extend(vec![TileColor::JOKER; num_jokers as usize]);
+        }
+        let penalty = rules.penalty(num_penalties);
+        if penalty > 0 {
+            events.push(Event::FloorPenalty {
+                player,
+                points: penalty,
+            });
+        }
+        self.score = std::cmp::max(self.score - penalty, 0);
+        self.has_first_player_marker = false;
+        returns
+    } // fn tile_wall
+    pub fn has_full_row(&self) -> bool {
+        self.grid
+            .rows()
+            .into_iter()
+            .any(|row| row.iter().all(|x| *x))
+    }
+    pub fn to_json(&self) -> json::JsonValue {
+        let floor_tiles: Vec<String> = self.floor_tiles.iter().map(|c| c.to_string()).collect();
+        let grid: Vec<Vec<bool>> = self
+            .grid
+            .rows()
+            .into_iter()
+            .map(|row| row.to_vec())
+            .collect();
+        // a third entry for the jokers, if the line has any
+        let rows: Vec<json::JsonValue> = self
+            .rows
+            .iter()
+            .zip(self.jokers.iter())
+            .map(|((count, color), jokers)| match jokers {
+                0 => json::array![*count, color.to_string()],
+                _ => json::array![*count, color.to_string(), *jokers],
+            })
+            .collect();
+        let mut val = json::object! {
+            "score": self.score,
+            "has_first_player_marker": self.has_first_player_marker,
+            "floor_tiles": floor_tiles,
+            "grid": grid,
+            "rows": rows,
+        };
+        // null where the wall's still empty
+        if let Some(wall_colors) = &self.wall_colors {
+            let wall_colors: Vec<Vec<json::JsonValue>> = wall_colors
+                .rows()
+                .into_iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|color| match color {
+                            TileColor::NOCOLOR => json::JsonValue::Null,
+                            color => color.to_string().into(),
+                        })
+                        .collect()
+                })
+                .collect();
+            val["wall_colors"] = wall_colors.into();
+        }
+        val
+    } // fn to_json
+    pub fn from_json(val: &json::JsonValue) -> Result<Self, String> {
+        let mut board = PlayerBoard::new();
+        board.score = val["score"].as_i32().ok_or("board is missing its score")?;
+        board.has_first_player_marker = val["has_first_player_marker"]
+            .as_bool()
+            .ok_or("board is missing has_first_player_marker")?;
+        for tile in val["floor_tiles"].members() {
+            board
+                .floor_tiles
+                .push(parse_color(tile.as_str().unwrap_or(""))?);
+        }
+        if val["grid"].len() != NUM_COLORS_AS_USIZE || val["rows"].len() != NUM_COLORS_AS_USIZE {
+            return Err(format!("board needs {} rows", NUM_COLORS_AS_USIZE));
+        }
+        for (row_idx, row) in val["grid"].members().enumerate() {
+            if row.len() != NUM_COLORS_AS_USIZE {
+                return Err(format!("grid row {} is the wrong size", row_idx + 1));
+            }
+            for (col_idx, cell) in row.members().enumerate() {
+                board.grid[[row_idx, col_idx]] =
+                    cell.as_bool().ok_or("grid cells are true or false")?;
+            }
+        }
+        for (row_idx, row) in val["rows"].members().enumerate() {
+            let count = row[0].as_i32().ok_or("row is missing its tile count")?;
+            if !(0..=row_idx as i32 + 1).contains(&count) {
+                return Err(format!("row {} can't hold {} tiles", row_idx + 1, count));
+            }
+            let color = if count == 0 {
+                TileColor::NOCOLOR
+            } else {
+                parse_color(row[1].as_str().unwrap_or(""))?
+            };
+            let jokers = row[2].as_i32().unwrap_or(0);
+            if jokers < 0 || (jokers > 0 && jokers >= count) {
+                return Err(format!("row {} can't hold {} jokers", row_idx + 1, jokers));
+            }
+            board.rows[row_idx] = (count, color);
+            board.jokers[row_idx] = jokers;
+        }
+        if !val["wall_colors"].is_null() {
+            let mut wall_colors = Variant::Gray.board().wall_colors.unwrap();
+            for (row_idx, row) in val["wall_colors"]
+                .members()
+                .enumerate()
+                .take(NUM_COLORS_AS_USIZE)
+            {
+                for (col_idx, cell) in row.members().enumerate().take(NUM_COLORS_AS_USIZE) {
+                    if let Some(color) = cell.as_str() {
+                        wall_colors[[row_idx, col_idx]] = parse_color(color)?;
+                    }
+                }
+            }
+            for ((row_idx, col_idx), filled) in board.grid.indexed_iter() {
+                if *filled != (wall_colors[[row_idx, col_idx]] != TileColor::NOCOLOR) {
+                    return Err(format!(
+                        "wall_colors doesn't match the grid at row {} column {}",
+                        row_idx + 1,
+                        col_idx + 1
+                    ));
+                }
+            }
+            board.wall_colors = Some(wall_colors);
+        }
+        Ok(board)
+    } // fn from_json
+} // impl PlayerBoard
+
+pub(crate) fn factories_to_json(factories: &[HashMap<TileColor, i32>]) -> json::JsonValue {
+    let mut fact_array = json::JsonValue::new_array();
+    for fact in factories {
+        let mut one_factory = json::JsonValue::new_object();
+        for (color, count) in fact.iter() {
+            let _ = one_factory.insert(&color.to_string(), *count);
+        }
+        let _ = fact_array.push(one_factory);
+    }
+    fact_array
+}
+pub(crate) fn factories_from_json(
+    val: &json::JsonValue,
+) -> Result<Vec<HashMap<TileColor, i32>>, String> {
+    let mut factories = Vec::new();
+    for factory in val.members() {
+        let mut fact: HashMap<TileColor, i32> = HashMap::new();
+        for (color_str, num) in factory.entries() {
+            fact.insert(
+                parse_color(color_str)?,
+                num.as_i32().ok_or("factory counts are numbers")?,
+            );
+        }
+        factories.push(fact);
+    }
+    Ok(factories)
+}
+
+// TileColor::from_string quietly turns junk into NOCOLOR, which we don't want when loading a file
+pub(crate) fn parse_color(val: &str) -> Result<TileColor, String> {
+    let color = TileColor::from_string(val);
+    if color == TileColor::NOCOLOR {
+        return Err(format!("not a tile color: {}", val));
+    }
+    Ok(color)
+}
+
+// every action the player could take given the shared tiles in front of them
+pub(crate) fn legal_actions_for(
+    factories: &[HashMap<TileColor, i32>],
+    pool: &HashMap<TileColor, i32>,
+    board: &PlayerBoard,
+) -> Vec<Action> {
+    let mut actions = Vec::new();
+    for (factory_idx, fact) in factories.iter().enumerate() {
+        for ii in 0..NUM_TILE_COLORS {
+            let color = TileColor::from_integer(ii);
+            if *fact.get(&color).unwrap_or(&0) == 0 {
+                continue;
+            }
+            for row_idx in 0..NUM_COLORS_AS_USIZE {
+                if board.check_valid_move(color, row_idx).is_ok() {
+                    actions.push(Action::FactoryToRow {
+                        factory_idx,
+                        color,
+                        row_idx,
+                    });
+                }
+            }
+            actions.push(Action::FactoryToFloor { factory_idx, color });
+        }
+    }
+    for ii in 0..NUM_TILE_COLORS {
+        let color = TileColor::from_integer(ii);
+        if *pool.get(&color).unwrap_or(&0) == 0 {
+            continue;
+        }
+        for row_idx in 0..NUM_COLORS_AS_USIZE {
+            if board.check_valid_move(color, row_idx).is_ok() {
+                actions.push(Action::PoolToRow { color, row_idx });
+            }
+        }
+        actions.push(Action::PoolToFloor { color });
+    }
+    actions
+} // fn legal_actions_for
+
+/// The whole game as a plain value: step it with `apply`, no threads or channels involved.
+#[derive(Clone, Debug)]
+pub struct GameState {
+    board: GameBoard,
+    players: Vec<PlayerBoard>,
+    current_player: usize,
+    next_first_player: usize,
+    round: u32,
+    game_over: bool,
+}
+
+impl GameState {
+    pub fn new(num_players: usize, seed: u64) -> Self {
+        Self::with_variant(num_players, seed, Variant::Standard)
+    }
+    pub fn with_variant(num_players: usize, seed: u64, variant: Variant) -> Self {
+        Self::with_rules(num_players, seed, variant, RuleSet::default())
+    }
+    pub fn with_rules(num_players: usize, seed: u64, variant: Variant, rules: RuleSet) -> Self {
+        let mut state = GameState {
+            board: GameBoard::with_rules(num_players as i32, StdRng::seed_from_u64(seed), rules),
+            players: vec![variant.board(); num_players],
+            current_player: 0,
+            next_first_player: 0,
+            round: 1,
+            game_over: false,
+        };
+        state.board.deal_tiles();
+        state
+    } // fn with_rules
+
+    // builds a full state out of what a seat can see. the bag is the one thing nobody can see,
+    // so it gets filled with a random draw of whatever tiles are unaccounted for
+    pub fn determinize<R: Rng>(obs: &Observation, rng: &mut R) -> Self {
+        let num_players = std::cmp::max(obs.num_players, obs.opponents.len() + 1);
+        let mut players = vec![obs.my_board.variant().board(); num_players];
+        players[obs.seat] = obs.my_board.clone();
+        for (ii, opponent) in obs.opponents.iter().enumerate() {
+            players[(obs.seat + 1 + ii) % num_players] = opponent.clone();
+        }
+        let mut board = GameBoard::with_rules(
+            num_players as i32,
+            StdRng::seed_from_u64(rng.gen()),
+            obs.rules.clone(),
+        );
+        board.tile_factories = obs.factories.clone();
+        board.tile_pool = obs.pool.clone();
+        board.white_tile_in_pool = obs.first_player_marker_in_pool;
+        let mut seen: HashMap<TileColor, i32> = HashMap::new();
+        let mut count = |color: TileColor, num: i32| {
+            *seen.entry(color).or_insert(0) += num;
+        };
+        for fact in obs.factories.iter() {
+            for (color, num) in fact.iter() {
+                count(*color, *num);
+            }
+        }
+        for (color, num) in obs.pool.iter() {
+            count(*color, *num);
+        }
+        for player in players.iter() {
+            for ((row_idx, col_idx), _) in player.grid.indexed_iter() {
+                if let Some(color) = player.wall_color(row_idx, col_idx) {
+                    count(color, 1);
+                }
+            }
+            for ((num, color), jokers) in player.rows.iter().zip(player.jokers.iter()) {
+                count(*color, *num - *jokers);
+                count(TileColor::JOKER, *jokers);
+            }
+            for color in player.floor_tiles.iter() {
+                count(*color, 1);
+            }
+        }
+        board.tile_bag.clear();
+        for ii in 0..NUM_TILE_COLORS {
+            let color = TileColor::from_integer(ii);
+            let num_in_game = match color {
+                TileColor::JOKER => obs.rules.jokers,
+                _ => obs.rules.tiles_per_color,
+            };
+            let num_unseen = num_in_game - *seen.get(&color).unwrap_or(&0);
+            for _ in 0..num_unseen {
+                board.tile_bag.push(color);
+            }
+        }
+        board.tile_bag.shuffle(rng);
+        let next_first_player = players
+            .iter()
+            .position(|player| player.has_first_player_marker)
+            .unwrap_or(obs.seat);
+        GameState {
+            board,
+            players,
+            current_player: obs.seat,
+            next_first_player,
+            round: 1,
+            game_over: false,
+        }
+    } // fn determinize
+    pub fn num_players(&self) -> usize {
+        self.players.len()
+    }
+    pub fn current_player(&self) -> usize {
+        self.current_player
+    }
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+    pub fn rules(&self) -> &RuleSet {
+        &self.board.rules
+    }
+    pub fn player_board(&self, player: usize) -> &PlayerBoard {
+        &self.players[player]
+    }
+    pub fn factories(&self) -> &[HashMap<TileColor, i32>] {
+        &self.board.tile_factories
+    }
+    pub fn pool(&self) -> &HashMap<TileColor, i32> {
+        &self.board.tile_pool
+    }
+    pub fn num_tiles_in_bag(&self) -> usize {
+        self.board.tile_bag.len()
+    }
+    pub fn num_tiles_in_lid(&self) -> usize {
+        self.board.tile_lid.len()
+    }
+    pub fn first_player_marker_in_pool(&self) -> bool {
+        self.board.white_tile_in_pool
+    }
+    pub fn scores(&self) -> Vec<i32> {
+        self.players.iter().map(|board| board.score).collect()
+    }
+    // who's ahead by the official tie-breaker. only final once the game's over
+    pub fn standings(&self) -> Standings {
+        Standings::new(&self.players, &self.board.rules, &[])
+    }
+    pub fn is_terminal(&self) -> bool {
+        self.game_over
+    }
+    pub fn legal_actions(&self) -> Vec<Action> {
+        if self.game_over {
+            return Vec::new();
+        }
+        legal_actions_for(
+            &self.board.tile_factories,
+            &self.board.tile_pool,
+            &self.players[self.current_player],
+        )
+    }
+    pub fn apply(&mut self, action: Action) -> Result<Events, RuleError> {
+        if self.game_over {
+            return Err(RuleError::GameOver);
+        }
+        let player = self.current_player;
+        let color = action.color();
+        if color == TileColor::NOCOLOR {
+            return Err(RuleError::BadColor);
+        }
+        // check the placement before we touch any tiles
+        if let Some(row_idx) = action.row_idx() {
+            self.players[player].check_valid_move(color, row_idx)?;
+        }
+        let mut events = Events::new();
+        let (factory_idx, num_tiles) = match action {
+            Action::FactoryToRow { factory_idx, .. }
+            | Action::FactoryToFloor { factory_idx, .. } => {
+                let num_tiles = self.board.take_tiles_from_factory(factory_idx, &color)?;
+                (Some(factory_idx), num_tiles)
+            }
+            Action::PoolToRow { .. } | Action::PoolToFloor { .. } => {
+                let num_tiles = self.board.take_tiles_from_pool(&color)?;
+                (None, num_tiles)
+            }
+        };
+        events.push(Event::TilesTaken {
+            player,
+            factory_idx,
+            color,
+            num_tiles,
+        });
+        if factory_idx.is_none() && self.board.white_tile_in_pool {
+            // first one into the pool takes the penalty, and goes first next round
+            self.board.white_tile_in_pool = false;
+            self.players[player].has_first_player_marker = true;
+            self.next_first_player = player;
+            events.push(Event::FirstPlayerMarkerTaken { player });
+        }
+        match action.row_idx() {
+            Some(row_idx) => {
+                let overflow = self.players[player].place_tiles(row_idx, color, num_tiles);
+                events.push(Event::TilesPlaced {
+                    player,
+                    row_idx,
+                    color,
+                    num_tiles: num_tiles - overflow,
+                });
+                if overflow > 0 {
+                    events.push(Event::TilesToFloor {
+                        player,
+                        color,
+                        num_tiles: overflow,
+                    });
+                }
+            }
+            None => {
+                self.players[player].drop_tiles_on_floor(color, num_tiles);
+                events.push(Event::TilesToFloor {
+                    player,
+                    color,
+                    num_tiles,
+                });
+            }
+        }
+        self.current_player = (player + 1) % self.players.len();
+        if self.board.end_of_round() {
+            self.end_round(&mut events);
+        }
+        Ok(events)
+    } // fn apply
+    fn end_round(&mut self, events: &mut Events) {
+        let mut game_over = false;
+        for (player, board) in self.players.iter_mut().enumerate() {
+            // nobody to ask here, so gray wall lines go wherever they score the most
+            let mut choose = |board: &PlayerBoard, row_idx: usize, columns: &[usize]| {
+                board.best_column(row_idx, columns)
+            };
+            for tile in board.tile_wall(player, &self.board.rules, events, &mut choose) {
+                self.board.return_tiles_to_lid(1, &tile);
+            }
+            game_over |= board.has_full_row();
+        }
+        events.push(Event::RoundEnded { round: self.round });
+        if !game_over {
+            self.round += 1;
+            self.current_player = self.next_first_player;
+            self.board.white_tile_in_pool = true;
+            self.board.deal_tiles();
+            // can only happen if every tile is stuck on a wall or pattern line
+            game_over = self.board.end_of_round();
+            if !game_over {
+                events.push(Event::RoundStarted {
+                    round: self.round,
+                    first_player: self.current_player,
+                    factories: self.board.tile_factories.clone(),
+                });
+            }
+        }
+        if game_over {
+            self.end_game(events);
+        }
+    } // fn end_round
+    fn end_game(&mut self, events: &mut Events) {
+        for (player, board) in self.players.iter_mut().enumerate() {
+            let points = finalize_score(board, &self.board.rules);
+            board.score += points;
+            events.push(Event::FinalBonus { player, points });
+        }
+        self.game_over = true;
+        events.push(Event::GameEnded {
+            scores: self.scores(),
+        });
+    } // fn end_game
+} // impl GameState
+
+#[cfg(test)]
+use crate::NUM_COLORS;
+
+#[test]
+fn test_game_state_plays_to_completion() {
+    for num_players in 2..=4 {
+        let mut state = GameState::new(num_players, 7);
+        let mut num_turns = 0;
+        while !state.is_terminal() {
+            let actions = state.legal_actions();
+            assert!(!actions.is_empty());
+            // always take the first option - deterministic and ends the game eventually
+            state.apply(actions[0]).unwrap();
+            num_turns += 1;
+            assert!(num_turns < 10_000, "game never ended");
+        }
+        assert!(state.players.iter().any(|board| board.has_full_row()));
+        assert!(state.legal_actions().is_empty());
+        assert_eq!(
+            state.apply(Action::PoolToFloor {
+                color: TileColor::RED
+            }),
+            Err(RuleError::GameOver)
+        );
+    }
+}
+#[test]
+fn test_game_state_same_seed_same_game() {
+    let mut state_a = GameState::new(3, 42);
+    let mut state_b = GameState::new(3, 42);
+    assert_eq!(state_a.factories(), state_b.factories());
+    while !state_a.is_terminal() {
+        let action = state_a.legal_actions()[0];
+        assert_eq!(state_a.apply(action), state_b.apply(action));
+    }
+    assert_eq!(state_a.scores(), state_b.scores());
+}
+#[test]
+fn test_game_state_rejects_bad_placement() {
+    let mut state = GameState::new(2, 1);
+    let (factory_idx, color) = state
+        .factories()
+        .iter()
+        .enumerate()
+        .find_map(|(idx, fact)| fact.keys().next().map(|color| (idx, *color)))
+        .unwrap();
+    let other_color = TileColor::from_integer((color.to_integer() + 1) % NUM_COLORS);
+    state.players[0].rows[2] = (1, other_color);
+    let action = Action::FactoryToRow {
+        factory_idx,
+        color,
+        row_idx: 2,
+    };
+    assert_eq!(state.apply(action), Err(RuleError::RowHasDifferentColor));
+    state.players[0].rows[2] = (0, TileColor::NOCOLOR);
+    state.players[0].grid[[2, get_col_idx(2, color)]] = true;
+    assert_eq!(state.apply(action), Err(RuleError::ColorAlreadyOnWall));
+    let nowhere = Action::FactoryToFloor {
+        factory_idx: 99,
+        color,
+    };
+    assert_eq!(state.apply(nowhere), Err(RuleError::NoSuchFactory));
+    // nothing changed, still player 0's turn
+    assert_eq!(state.current_player(), 0);
+    let events = state
+        .apply(Action::FactoryToFloor { factory_idx, color })
+        .unwrap();
+    assert!(matches!(events[0], Event::TilesTaken { player: 0, .. }));
+    assert_eq!(state.current_player(), 1);
+}
+#[test]
+fn test_game_state_first_player_marker() {
+    let mut state = GameState::new(2, 3);
+    // player 0 takes from a mixed factory so there's something in the pool
+    let (factory_idx, color) = state
+        .factories()
+        .iter()
+        .enumerate()
+        .find(|(_, fact)| fact.len() > 1)
+        .map(|(idx, fact)| (idx, *fact.keys().next().unwrap()))
+        .unwrap();
+    state
+        .apply(Action::FactoryToFloor { factory_idx, color })
+        .unwrap();
+    let pool_action = state
+        .legal_actions()
+        .into_iter()
+        .find(|action| matches!(action, Action::PoolToFloor { .. }))
+        .unwrap();
+    let events = state.apply(pool_action).unwrap();
+    assert!(events.contains(&Event::FirstPlayerMarkerTaken { player: 1 }));
+    assert!(state.player_board(1).has_first_player_marker);
+    assert!(!state.first_player_marker_in_pool());
+    assert_eq!(state.next_first_player, 1);
+}
+#[cfg(test)]
+fn put_on_wall(board: &mut PlayerBoard, row_idx: usize, col_idx: usize, color: TileColor) {
+    board.grid[[row_idx, col_idx]] = true;
+    board.wall_colors.as_mut().unwrap()[[row_idx, col_idx]] = color;
+}
+#[test]
+fn test_gray_wall_lets_players_pick_the_column() {
+    let mut board = Variant::Gray.board();
+    put_on_wall(&mut board, 0, 0, TileColor::RED);
+    assert_eq!(
+        board.check_valid_move(TileColor::RED, 0),
+        Err(RuleError::ColorAlreadyOnWall)
+    );
+    assert!(board.check_valid_move(TileColor::BLUE, 0).is_ok());
+    // red's already in column 0, so a red line anywhere else can't go there
+    board.rows[1] = (2, TileColor::RED);
+    assert_eq!(board.wall_columns(1, TileColor::RED), vec![1, 2, 3, 4]);
+    let mut events = Events::new();
+    let mut asked = Vec::new();
+    let rules = RuleSet::default();
+    let returns = board.tile_wall(0, &rules, &mut events, &mut |_, row_idx, columns| {
+        asked.push((row_idx, columns.to_vec()));
+        1
+    });
+    assert_eq!(asked, vec![(1, vec![1, 2, 3, 4])]);
+    assert_eq!(board.wall_color(1, 1), Some(TileColor::RED));
+    assert_eq!(
+        events,
+        vec![Event::WallTiled {
+            player: 0,
+            row_idx: 1,
+            col_idx: 1,
+            color: TileColor::RED,
+            points: 1,
+        }]
+    );
+    assert_eq!(returns, vec![TileColor::RED]);
+    assert_eq!(PlayerBoard::from_json(&board.to_json()), Ok(board.clone()));
+}
+#[test]
+fn test_gray_wall_line_with_nowhere_to_go() {
+    let mut board = Variant::Gray.board();
+    put_on_wall(&mut board, 2, 4, TileColor::GREEN);
+    for (row_idx, col_idx) in [(0, 0), (1, 1), (3, 2), (4, 3)] {
+        put_on_wall(&mut board, row_idx, col_idx, TileColor::BLUE);
+    }
+    // row 3 has no blue in it yet, but every open column does
+    board.rows[2] = (3, TileColor::BLUE);
+    assert!(board.check_valid_move(TileColor::BLUE, 2).is_ok());
+    assert!(board.wall_columns(2, TileColor::BLUE).is_empty());
+    board.score = 10;
+    let mut events = Events::new();
+    let rules = RuleSet::default();
+    let returns = board.tile_wall(1, &rules, &mut events, &mut |_, _, _| {
+        panic!("nothing to choose")
+    });
+    assert_eq!(returns, vec![TileColor::BLUE; 3]);
+    assert_eq!(board.rows[2], (0, TileColor::NOCOLOR));
+    assert_eq!(board.score, 10 - rules.penalty(3));
+    assert_eq!(
+        events[0],
+        Event::LineDiscarded {
+            player: 1,
+            row_idx: 2,
+            color: TileColor::BLUE,
+            num_tiles: 3,
+        }
+    );
+}
+#[test]
+fn test_gray_game_plays_to_completion() {
+    let mut state = GameState::with_variant(3, 5, Variant::Gray);
+    while !state.is_terminal() {
+        state.apply(state.legal_actions()[0]).unwrap();
+    }
+    for board in state.players.iter() {
+        assert_eq!(board.variant(), Variant::Gray);
+        // no color twice in a row or a column
+        for ii in 0..NUM_COLORS_AS_USIZE {
+            for color in (0..NUM_COLORS).map(TileColor::from_integer) {
+                let count = |cells: &mut dyn Iterator<Item = (usize, usize)>| {
+                    cells
+                        .filter(|(row, col)| board.wall_color(*row, *col) == Some(color))
+                        .count()
+                };
+                assert!(count(&mut (0..NUM_COLORS_AS_USIZE).map(|col| (ii, col))) <= 1);
+                assert!(count(&mut (0..NUM_COLORS_AS_USIZE).map(|row| (row, ii))) <= 1);
+            }
+        }
+    }
+    assert!(state.players.iter().any(|board| board.has_full_row()));
+}
+#[test]
+fn test_jokers_only_join_a_started_line() {
+    let mut board = PlayerBoard::new();
+    assert_eq!(
+        board.check_valid_move(TileColor::JOKER, 2),
+        Err(RuleError::JokerNeedsColor)
+    );
+    board.rows[2] = (1, TileColor::RED);
+    assert_eq!(board.check_valid_move(TileColor::JOKER, 2), Ok(()));
+    // three jokers on a line with room for two
+    assert_eq!(board.place_tiles(2, TileColor::JOKER, 3), 1);
+    assert_eq!(board.rows[2], (3, TileColor::RED));
+    assert_eq!(board.jokers[2], 2);
+    assert_eq!(board.floor_tiles, vec![TileColor::JOKER]);
+    assert_eq!(PlayerBoard::from_json(&board.to_json()), Ok(board.clone()));
+    // the one red tile goes on the wall and the jokers go back in the lid
+    let mut events = Events::new();
+    let rules = RuleSet::default();
+    let mut returns = board.tile_wall(0, &rules, &mut events, &mut |_, _, _| panic!("standard"));
+    returns.sort_by_key(|color| color.to_integer());
+    assert_eq!(returns, vec![TileColor::JOKER; 3]);
+    assert!(board.has_on_wall(2, TileColor::RED));
+    assert_eq!(board.jokers[2], 0);
+    assert_eq!(board.rows[2], (0, TileColor::NOCOLOR));
+    assert!(matches!(
+        events[0],
+        Event::WallTiled {
+            color: TileColor::RED,
+            points: 1,
+            ..
+        }
+    ));
+}
+#[test]
+fn test_joker_game_keeps_every_tile() {
+    let rules = RuleSet::preset("jokers").unwrap();
+    let num_tiles = (NUM_COLORS * rules.tiles_per_color + rules.jokers) as usize;
+    let mut state = GameState::with_rules(3, 12, Variant::Standard, rules);
+    let mut rng = StdRng::seed_from_u64(12);
+    let mut num_jokers_placed = 0;
+    while !state.is_terminal() {
+        let action = *state.legal_actions().choose(&mut rng).unwrap();
+        for event in state.apply(action).unwrap() {
+            if let Event::TilesPlaced {
+                color: TileColor::JOKER,
+                num_tiles,
+                ..
+            } = event
+            {
+                num_jokers_placed += num_tiles;
+            }
+        }
+        let num_shared: i32 = state
+            .factories()
+            .iter()
+            .chain(std::iter::once(state.pool()))
+            .map(|tiles| tiles.values().sum::<i32>())
+            .sum();
+        let num_on_boards: usize = state
+            .players
+            .iter()
+            .map(|board| {
+                board.grid.iter().filter(|x| **x).count()
+                    + board.rows.iter().map(|row| row.0 as usize).sum::<usize>()
+                    + board.floor_tiles.len()
+            })
+            .sum();
+        let num_put_away = state.num_tiles_in_bag() + state.num_tiles_in_lid();
+        assert_eq!(
+            num_put_away + num_on_boards + num_shared as usize,
+            num_tiles
+        );
+    }
+    assert!(num_jokers_placed > 0);
+}
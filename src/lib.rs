@@ -1,30 +1,80 @@
-use json;
-use json::object;
-use ndarray::{arr2, Array2, ArrayView2, Axis};
+use ndarray::{arr2, Array2, ArrayView2};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::num::ParseIntError;
+use std::path::PathBuf;
 use std::sync::mpsc;
 use std::thread;
+use std::time::{Duration, Instant};
 use strum_macros::Display;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Display)]
-enum TileColor {
+mod agent;
+mod bots;
+mod clock;
+mod game_state;
+mod lobby;
+mod mcts;
+mod net;
+mod protocol;
+mod replay;
+mod rules;
+mod save;
+mod simulate;
+mod spectate;
+mod standings;
+mod web;
+pub use agent::{agent_from_name, print_board, Observation, PlayerAgent, TerminalAgent};
+pub use bots::{GreedyAgent, RandomAgent};
+pub use clock::{parse_clock, parse_duration, TimeControl, TimeoutAction};
+pub use game_state::{Action, Event, Events, GameState, PlayerBoard, RuleError, Variant};
+pub use lobby::{
+    join_table, list_tables, run_lobby, watch_table, TableChoice, TableInfo, VARIANTS,
+};
+pub use mcts::{MctsAgent, SearchBudget};
+pub use net::{
+    accept_seats, join_game, rejoin_game, spawn_rejoin_listener, ReconnectOptions, Seat, Sessions,
+    DEFAULT_PORT,
+};
+pub use protocol::{
+    ColumnChoice, FactoryDraw, Hello, LobbyRequest, MoveResult, PoolDraw, Reply, SeatMessage,
+    TableMessage, TableRequest, TableView, FEATURES, PROTOCOL_VERSION,
+};
+pub use replay::{run_replay, GameRecord, Position};
+pub use rules::{RuleSet, RULE_PRESETS};
+pub use save::SavedGame;
+pub use simulate::{play_game, run_simulation, SeatStats, SimulationReport};
+pub use spectate::Spectators;
+pub use standings::{Bonuses, Standing, Standings};
+pub use web::{spawn_web_gateway, DEFAULT_WEB_PORT};
+
+use clock::{ClockedAgent, Clocks};
+use game_state::{factories_from_json, parse_color};
+use net::RemoteAgent;
+use replay::GameLog;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Display, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum TileColor {
     RED,
     BLUE,
     GREEN,
     YELLOW,
     WHITE,
+    JOKER, // house rules only: wild, it fills in for whatever color its pattern line is
     NOCOLOR,
 }
 impl TileColor {
-    pub fn to_integer(&self) -> i32 {
+    pub fn to_integer(self) -> i32 {
         match self {
             TileColor::RED => 0,
             TileColor::BLUE => 1,
             TileColor::GREEN => 2,
             TileColor::YELLOW => 3,
             TileColor::WHITE => 4,
+            TileColor::JOKER => 5,
             TileColor::NOCOLOR => 99,
         }
     }
@@ -35,6 +85,7 @@ impl TileColor {
             2 => TileColor::GREEN,
             3 => TileColor::YELLOW,
             4 => TileColor::WHITE,
+            5 => TileColor::JOKER,
             _ => TileColor::NOCOLOR,
         }
     }
@@ -45,6 +96,7 @@ impl TileColor {
             TileColor::GREEN => String::from("\x1B[1;42mG\x1B[0m"),
             TileColor::YELLOW => String::from("\x1B[1;30;103mY\x1B[0m"),
             TileColor::WHITE => String::from("\x1B[1;30;107mW\x1B[0m"),
+            TileColor::JOKER => String::from("\x1B[1;45mJ\x1B[0m"),
             TileColor::NOCOLOR => String::from(" "),
         }
     }
@@ -55,28 +107,31 @@ impl TileColor {
             "GREEN" => TileColor::GREEN,
             "YELLOW" => TileColor::YELLOW,
             "WHITE" => TileColor::WHITE,
+            "JOKER" => TileColor::JOKER,
             _ => TileColor::NOCOLOR,
         }
     }
-    pub fn to_char_symbol(&self) -> &str {
+    pub fn to_char_symbol(self) -> &'static str {
         match self {
             TileColor::RED => "r",
             TileColor::BLUE => "b",
             TileColor::GREEN => "g",
             TileColor::YELLOW => "y",
             TileColor::WHITE => "w",
+            TileColor::JOKER => "j",
             TileColor::NOCOLOR => "-",
         }
     }
 } // impl TileColor
 #[derive(Debug)] // TODO see if we can remove this
+#[allow(clippy::enum_variant_names, dead_code)]
 enum InvalidMoveError {
     BadColorError,
     BadFactoryRequestError,
-    BadPoolRequestError,
     BadInputIoError(std::io::Error),
     BadInputParseError(ParseIntError),
     BadInputRowIdxError,
+    InputClosedError, // stdin's gone, so nobody is left to answer
     UnknownError,
 }
 impl InvalidMoveError {
@@ -87,293 +142,422 @@ impl InvalidMoveError {
         InvalidMoveError::BadInputParseError(err)
     }
 }
-#[derive(PartialEq)]
-enum AzoolRequestType {
+// the "req_type" every message carries on the wire
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
+pub enum AzoolRequestType {
+    #[serde(rename = "DRAW_FROM_FACTORY")]
     ReqTypeDrawFromFactory,
+    #[serde(rename = "DRAW_FROM_POOL")]
     ReqTypeDrawFromPool,
+    #[serde(rename = "DISCARD_FROM_FACTORY")]
     ReqTypeDiscardFromFactory,
+    #[serde(rename = "DISCARD_FROM_POOL")]
     ReqTypeDiscardFromPool,
-    ReqTypeReturnToBag,
+    #[serde(rename = "RETURN_TO_LID")]
+    ReqTypeReturnToLid,
+    #[serde(rename = "GET_BOARD")]
     ReqTypeGetBoard,
+    #[serde(rename = "TAKE_TURN")]
     ReqTypeBeginTurn,
+    #[serde(rename = "TURN_FINISHED")]
     ReqTypeTurnFinished,
+    #[serde(rename = "END_OF_ROUND")]
     ReqTypeEndOfRound,
+    #[serde(rename = "FULL_ROW")]
     ReqTypeFullRow,
+    #[serde(rename = "END_OF_GAME")]
     ReqTypeEndOfGame,
+    #[serde(rename = "GET_PLAYER_STATE")]
+    ReqTypeGetPlayerState,
+    #[serde(rename = "JOIN_GAME")]
+    ReqTypeJoinGame,
+    #[serde(rename = "LIST_TABLES")]
+    ReqTypeListTables,
+    #[serde(rename = "CREATE_TABLE")]
+    ReqTypeCreateTable,
+    #[serde(rename = "JOIN_TABLE")]
+    ReqTypeJoinTable,
+    #[serde(rename = "LEAVE_TABLE")]
+    ReqTypeLeaveTable,
+    #[serde(rename = "WATCH_TABLE")]
+    ReqTypeWatchTable,
+    #[serde(rename = "REJOIN_GAME")]
+    ReqTypeRejoinGame,
+    #[serde(rename = "HELLO")]
+    ReqTypeHello,
+    #[serde(rename = "INVALID")]
     ReqTypeInvalid,
 }
 impl AzoolRequestType {
     fn get_string(&self) -> String {
-        match self {
-            AzoolRequestType::ReqTypeDrawFromFactory => String::from("DRAW_FROM_FACTORY"),
-            AzoolRequestType::ReqTypeDrawFromPool => String::from("DRAW_FROM_POOL"),
-            AzoolRequestType::ReqTypeDiscardFromFactory => String::from("DISCARD_FROM_FACTORY"),
-            AzoolRequestType::ReqTypeDiscardFromPool => String::from("DISCARD_FROM_POOL"),
-            AzoolRequestType::ReqTypeReturnToBag => String::from("RETURN_TO_BAG"),
-            AzoolRequestType::ReqTypeGetBoard => String::from("GET_BOARD"),
-            AzoolRequestType::ReqTypeBeginTurn => String::from("TAKE_TURN"),
-            AzoolRequestType::ReqTypeTurnFinished => String::from("TURN_FINISHED"),
-            AzoolRequestType::ReqTypeEndOfRound => String::from("END_OF_ROUND"),
-            AzoolRequestType::ReqTypeFullRow => String::from("FULL_ROW"),
-            AzoolRequestType::ReqTypeEndOfGame => String::from("END_OF_GAME"),
-            AzoolRequestType::ReqTypeInvalid => String::from("INVALID"),
-        }
-    }
-    fn from_string(val: &str) -> Self {
-        match val {
-            "DRAW_FROM_FACTORY" => AzoolRequestType::ReqTypeDrawFromFactory,
-            "DRAW_FROM_POOL" => AzoolRequestType::ReqTypeDrawFromPool,
-            "DISCARD_FROM_FACTORY" => AzoolRequestType::ReqTypeDiscardFromFactory,
-            "DISCARD_FROM_POOL" => AzoolRequestType::ReqTypeDiscardFromPool,
-            "RETURN_TO_BAG" => AzoolRequestType::ReqTypeReturnToBag,
-            "GET_BOARD" => AzoolRequestType::ReqTypeGetBoard,
-            "TAKE_TURN" => AzoolRequestType::ReqTypeBeginTurn,
-            "TURN_FINISHED" => AzoolRequestType::ReqTypeTurnFinished,
-            "END_OF_ROUND" => AzoolRequestType::ReqTypeEndOfRound,
-            "FULL_ROW" => AzoolRequestType::ReqTypeFullRow,
-            "END_OF_GAME" => AzoolRequestType::ReqTypeEndOfGame,
-            _ => AzoolRequestType::ReqTypeInvalid,
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => unreachable!("every request type serializes to its name"),
         }
     }
 }
-const NUM_TILES_PER_COLOR: i32 = 20;
 const NUM_COLORS: i32 = 5;
 const NUM_COLORS_AS_USIZE: usize = NUM_COLORS as usize;
-const PENALTY_POINTS: [i32; 9] = [0, 1, 2, 3, 5, 7, 10, 13, 15];
+const NUM_TILE_COLORS: i32 = NUM_COLORS + 1; // the wall's colors, and the joker
+pub const MIN_NUM_PLAYERS: usize = 2;
+pub const MAX_NUM_PLAYERS: usize = 4;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct GameBoard {
-    _my_num_players: i32,
+    num_players: i32,
     max_num_factories: i32,
     tile_pool: HashMap<TileColor, i32>,
     tile_bag: Vec<TileColor>,
+    tile_lid: Vec<TileColor>, // discards wait here until the bag runs out
     tile_factories: Vec<HashMap<TileColor, i32>>,
     white_tile_in_pool: bool,
+    // every player's pattern lines and wall, by seat. the table checks placements against these
+    player_boards: Vec<PlayerBoard>,
+    clocks: Option<Clocks>, // only if the game has time controls
+    rules: RuleSet,
+    rng: StdRng, // all the shuffling goes through here so a seed replays the same deals
+    // how big the bag was at every shuffle so far. the rng can't be written out, but this is
+    // everything it has been used for, so it's enough to wind a fresh one forward to the same spot
+    shuffles: Vec<usize>,
 }
 
 impl GameBoard {
-    pub fn new() -> Self {
-        let num_players = 2;
+    #[cfg(test)]
+    pub fn with_rng(num_players: i32, rng: StdRng) -> Self {
+        Self::with_rules(num_players, rng, RuleSet::default())
+    }
+    pub fn with_rules(num_players: i32, rng: StdRng, rules: RuleSet) -> Self {
         let mut gb = GameBoard {
-            _my_num_players: num_players,
+            num_players,
             max_num_factories: num_players * 2 + 1,
             tile_pool: HashMap::new(),
             tile_bag: Vec::new(),
+            tile_lid: Vec::new(),
             tile_factories: Vec::new(),
             white_tile_in_pool: true,
+            player_boards: vec![PlayerBoard::new(); num_players as usize],
+            clocks: None,
+            rules,
+            rng,
+            shuffles: Vec::new(),
         }; // GameBoard
         gb.reset_board();
         gb
-    } // fn new
+    } // fn with_rules
     fn reset_board(&mut self) {
         self.tile_factories.clear();
         self.tile_bag.clear();
-        self.tile_bag
-            .reserve((NUM_COLORS * NUM_TILES_PER_COLOR).try_into().unwrap());
+        self.tile_lid.clear();
+        self.tile_bag.reserve(
+            (NUM_COLORS * self.rules.tiles_per_color + self.rules.jokers)
+                .try_into()
+                .unwrap(),
+        );
         for ii in 0..NUM_COLORS {
             self.tile_pool
                 .entry(TileColor::from_integer(ii))
                 .and_modify(|ct| *ct = 0)
                 .or_insert(0);
-            for _ in 0..NUM_TILES_PER_COLOR {
+            for _ in 0..self.rules.tiles_per_color {
                 self.tile_bag.push(TileColor::from_integer(ii));
             }
         }
+        for _ in 0..self.rules.jokers {
+            self.tile_bag.push(TileColor::JOKER);
+        }
         self.white_tile_in_pool = true;
     } // fn reset_board
-    fn serialize_game_board(&self) -> json::JsonValue {
-        let mut fact_array: json::Array = vec![];
-        for fact in &self.tile_factories {
-            let mut one_factory = json::JsonValue::new_object();
-            for (color, count) in fact.iter() {
-                let _ = one_factory.insert(&color.to_string(), *count);
-            }
-            let _ = fact_array.push(one_factory);
+    fn view(&self) -> TableView {
+        TableView {
+            num_players: self.num_players as usize,
+            num_factories: self.tile_factories.len(),
+            factories: self.tile_factories.clone(),
+            num_tiles_in_pool: self.tile_pool.values().sum(),
+            pool: self.tile_pool.clone(),
+            end_of_round: self.end_of_round(),
+            white_tile_in_pool: self.white_tile_in_pool,
+            ..Default::default()
         }
-        let mut pool_tree = json::JsonValue::new_object();
-        let mut num_tiles_in_pool = 0;
-        for (color, count) in &self.tile_pool {
-            let _ = pool_tree.insert(&color.to_string(), *count);
-            num_tiles_in_pool += *count;
+    }
+    // every seat's clock, but not in view() - a saved game doesn't keep them
+    fn time_left_ms(&self) -> Option<Vec<u64>> {
+        let clocks = self.clocks.as_ref()?;
+        Some(
+            clocks
+                .all_left()
+                .iter()
+                .map(|left| left.as_millis() as u64)
+                .collect(),
+        )
+    }
+    // what the players see plus what's hidden in the bag and lid - enough to pick the game back up
+    fn snapshot(&self) -> json::JsonValue {
+        let mut snapshot = protocol::to_json(&self.view());
+        let bag: Vec<String> = self.tile_bag.iter().map(|c| c.to_string()).collect();
+        let lid: Vec<String> = self.tile_lid.iter().map(|c| c.to_string()).collect();
+        snapshot["bag"] = bag.into();
+        snapshot["lid"] = lid.into();
+        snapshot["shuffles"] = self.shuffles.clone().into();
+        snapshot
+    } // fn snapshot
+    fn restore(&mut self, snapshot: &json::JsonValue) -> Result<(), String> {
+        self.reset_board();
+        self.tile_bag.clear();
+        self.tile_factories = factories_from_json(&snapshot["factories"])?;
+        for (color_str, num) in snapshot["pool"].entries() {
+            self.tile_pool.insert(
+                parse_color(color_str)?,
+                num.as_i32().ok_or("pool counts are numbers")?,
+            );
         }
-        object! {
-            "req_type" : AzoolRequestType::ReqTypeGetBoard.get_string(),
-            "num_factories" : fact_array.len(),
-            "factories" : fact_array,
-            "num_tiles_in_pool": num_tiles_in_pool,
-            "pool": pool_tree,
-            "end_of_round" : self.end_of_round(),
-            "white_tile_in_pool" : self.white_tile_in_pool,
+        for tile in snapshot["bag"].members() {
+            self.tile_bag
+                .push(parse_color(tile.as_str().unwrap_or(""))?);
         }
-    }
-    fn process_msg(&mut self, msg: json::JsonValue) -> Option<json::JsonValue> {
-        if !msg.has_key("req_type") {
-            return None;
+        for tile in snapshot["lid"].members() {
+            self.tile_lid
+                .push(parse_color(tile.as_str().unwrap_or(""))?);
         }
-        let mut response = msg.clone();
-        match AzoolRequestType::from_string(msg["req_type"].as_str().unwrap()) {
-            // TODO handle error
-            AzoolRequestType::ReqTypeDrawFromFactory => {
-                let fact_idx: usize = msg["factory_idx"].as_usize().unwrap();
-                let tile_color = TileColor::from_integer(msg["tile_color"].as_i32().unwrap());
-                let result = self.take_tiles_from_factory(fact_idx, &tile_color);
-                match result {
-                    Ok(num) => {
-                        // response["status"] = "success";  // todo this was in the old version, but is it necessary?
-                        let _ = response.insert("success", true);
-                        let _ = response.insert("num_tiles_returned", num);
-                    }
-                    Err(_error) => {
-                        let _ = response.insert("success", false);
-                        let _ = response.insert("num_tiles_returned", 0);
-                    }
-                }
+        self.white_tile_in_pool = snapshot["white_tile_in_pool"]
+            .as_bool()
+            .ok_or("board is missing white_tile_in_pool")?;
+        // on a board fresh from the game's seed, shuffling the same sizes over again brings the rng
+        // up to where the save left it
+        self.shuffles.clear();
+        for len in snapshot["shuffles"].members() {
+            let len = len.as_usize().ok_or("shuffles are bag sizes")?;
+            (0..len).collect::<Vec<usize>>().shuffle(&mut self.rng);
+            self.shuffles.push(len);
+        }
+        Ok(())
+    } // fn restore
+
+    // answers whatever a Player asked of the table. the reports that drive the game along -
+    // TURN_FINISHED, FULL_ROW, END_OF_GAME, GET_PLAYER_STATE - are host_game's to handle
+    fn process_msg(&mut self, msg: TableRequest) -> Option<TableMessage> {
+        let response = match msg {
+            TableRequest::DrawFromFactory(draw) => {
+                TableMessage::DrawFromFactory(self.factory_request(draw, true))
             }
-            AzoolRequestType::ReqTypeDrawFromPool => {
-                let tile_color = TileColor::from_integer(msg["tile_color"].as_i32().unwrap());
-                let result = self.take_tiles_from_pool(&tile_color);
-                match result {
-                    Ok(num) => {
-                        // response["status"] = "success";  // todo this was in the old version, but is it necessary?
-                        let _ = response.insert("success", true);
-                        let _ = response.insert("num_tiles_returned", num);
-                        if self.white_tile_in_pool {
-                            let _ = response.insert("pool_penalty", true);
-                            self.white_tile_in_pool = false;
-                        } else {
-                            let _ = response.insert("pool_penalty", false);
-                        }
-                    }
-                    Err(_error) => {
-                        let _ = response.insert("success", false);
-                        let _ = response.insert("num_tiles_returned", 0);
-                        response["error_type"] = "Invalid move".into();
-                    }
-                }
+            TableRequest::DrawFromPool(draw) => {
+                TableMessage::DrawFromPool(self.pool_request(draw, true))
             }
-            AzoolRequestType::ReqTypeReturnToBag => {
-                for (color_str, num) in msg["returns"].entries() {
-                    self.return_tiles_to_bag(
-                        num.as_i32().unwrap(),
-                        &TileColor::from_string(color_str),
-                    );
+            TableRequest::ReturnToLid { returns, .. } => {
+                for (color, num) in returns.iter() {
+                    self.return_tiles_to_lid(*num, color);
                 }
                 return None;
             }
-            AzoolRequestType::ReqTypeGetBoard => {
-                response = self.serialize_game_board();
-                response["current_player"] = msg["current_player"].clone(); // need to keep player id
+            TableRequest::GetBoard { current_player } => TableMessage::GetBoard(TableView {
+                current_player, // need to keep player id
+                players: Some(self.player_boards.clone()),
+                time_left_ms: self.time_left_ms(),
+                ..self.view()
+            }),
+            TableRequest::DiscardFromFactory(draw) => {
+                TableMessage::DiscardFromFactory(self.factory_request(draw, false))
             }
-            // used for the gameboard to tell the player it's their turn. doesn't make sense here
-            AzoolRequestType::ReqTypeBeginTurn => return None,
-            AzoolRequestType::ReqTypeTurnFinished => return None,
-            AzoolRequestType::ReqTypeDiscardFromFactory => {
-                let fact_idx: usize = msg["factory_idx"].as_usize().unwrap();
-                let tile_color = TileColor::from_integer(msg["tile_color"].as_i32().unwrap());
-                let result = self.take_tiles_from_factory(fact_idx, &tile_color);
-                match result {
-                    Ok(num) => {
-                        // response["status"] = "success";  // todo this was in the old version, but is it necessary?
-                        let _ = response.insert("success", true);
-                        let _ = response.insert("num_tiles_returned", num);
-                    }
-                    Err(_error) => {
-                        let _ = response.insert("success", false);
-                        let _ = response.insert("num_tiles_returned", 0);
-                        response["error_type"] = "Invalid move".into();
-                    }
+            TableRequest::DiscardFromPool(draw) => {
+                TableMessage::DiscardFromPool(self.pool_request(draw, false))
+            }
+            TableRequest::TurnFinished { .. }
+            | TableRequest::FullRow { .. }
+            | TableRequest::EndOfGame { .. }
+            | TableRequest::GetPlayerState { .. } => return None,
+        }; // match msg
+        Some(response)
+    }
+    // drawing and discarding go the same way; only where the tiles end up differs. `to_row` is
+    // false for discards
+    fn factory_request(&mut self, draw: FactoryDraw, to_row: bool) -> MoveResult {
+        let mut result =
+            MoveResult::new(draw.current_player, Some(draw.factory_idx), draw.tile_color);
+        let row_idx = draw.row_idx.filter(|_| to_row);
+        let taken = self
+            .check_placement(draw.current_player, draw.tile_color, row_idx, to_row)
+            .and_then(|seat| {
+                let num = self.take_tiles_from_factory(draw.factory_idx, &draw.tile_color)?;
+                Ok((seat, num))
+            });
+        match taken {
+            Ok((seat, num)) => {
+                result.success = true;
+                result.num_tiles_returned = num;
+                self.place_tiles(seat, row_idx, draw.tile_color, num);
+            }
+            Err(error) => result.refuse(error),
+        }
+        result
+    } // fn factory_request
+    fn pool_request(&mut self, draw: PoolDraw, to_row: bool) -> MoveResult {
+        let mut result = MoveResult::new(draw.current_player, None, draw.tile_color);
+        let row_idx = draw.row_idx.filter(|_| to_row);
+        let taken = self
+            .check_placement(draw.current_player, draw.tile_color, row_idx, to_row)
+            .and_then(|seat| Ok((seat, self.take_tiles_from_pool(&draw.tile_color)?)));
+        match taken {
+            Ok((seat, num)) => {
+                result.success = true;
+                result.num_tiles_returned = num;
+                result.pool_penalty = Some(self.white_tile_in_pool);
+                if self.white_tile_in_pool {
+                    self.player_boards[seat].has_first_player_marker = true;
                 }
+                self.white_tile_in_pool = false;
+                self.place_tiles(seat, row_idx, draw.tile_color, num);
             }
-            AzoolRequestType::ReqTypeDiscardFromPool => {
-                let tile_color = TileColor::from_integer(msg["tile_color"].as_i32().unwrap());
-                let result = self.take_tiles_from_pool(&tile_color);
-                match result {
-                    Ok(num) => {
-                        // response["status"] = "success";  // todo this was in the old version, but is it necessary?
-                        let _ = response.insert("success", true);
-                        let _ = response.insert("num_tiles_returned", num);
-                        if self.white_tile_in_pool {
-                            let _ = response.insert("pool_penalty", true);
-                            self.white_tile_in_pool = false;
-                        } else {
-                            let _ = response.insert("pool_penalty", false);
-                        }
-                    }
-                    Err(_error) => {
-                        let _ = response.insert("success", false);
-                        let _ = response.insert("num_tiles_returned", 0);
-                        response["error_type"] = "Invalid move".into();
-                    }
+            Err(error) => result.refuse(error),
+        }
+        result
+    } // fn pool_request
+
+    // the table keeps every player's pattern lines and wall, so it gets the last word on where
+    // tiles can go. checked before any tiles move, so a bad placement costs nothing. returns the
+    // seat making the move
+    fn check_placement(
+        &self,
+        current_player: Option<u8>,
+        color: TileColor,
+        row_idx: Option<usize>,
+        to_row: bool,
+    ) -> Result<usize, RuleError> {
+        let seat = match current_player {
+            Some(id) if (1..=self.player_boards.len()).contains(&(id as usize)) => id as usize - 1,
+            _ => return Err(RuleError::NoSuchPlayer),
+        };
+        if to_row {
+            let row_idx = row_idx.ok_or(RuleError::BadRowIdx)?;
+            self.player_boards[seat].check_valid_move(color, row_idx)?;
+        }
+        Ok(seat)
+    } // fn check_placement
+
+    // puts the tiles on the table's copy of the board. None for the floor
+    fn place_tiles(&mut self, seat: usize, row_idx: Option<usize>, color: TileColor, num: i32) {
+        let board = &mut self.player_boards[seat];
+        match row_idx {
+            Some(row_idx) => {
+                board.place_tiles(row_idx, color, num);
+            }
+            None => board.drop_tiles_on_floor(color, num),
+        }
+    } // fn place_tiles
+
+    // end of the round on the table's copy of a board, in step with the Player's own. the Player
+    // hands back the leftover tiles itself, so the table's count of them goes nowhere. on the gray
+    // wall the columns come from what the Player reported in `events`. returns whether that board
+    // finished a row on the wall, or BadColumn if the report doesn't fit the table's copy, in
+    // which case that copy is left as it was
+    fn tile_wall(&mut self, seat: usize, events: &Events) -> Result<bool, RuleError> {
+        let mut misfit = false;
+        let mut chosen = |_: &PlayerBoard, row: usize, columns: &[usize]| {
+            let reported = events.iter().find_map(|event| match event {
+                Event::WallTiled {
+                    row_idx, col_idx, ..
+                } if *row_idx == row => Some(*col_idx),
+                _ => None,
+            });
+            match reported {
+                Some(col_idx) if columns.contains(&col_idx) => col_idx,
+                _ => {
+                    misfit = true;
+                    columns[0]
                 }
             }
-            AzoolRequestType::ReqTypeEndOfRound => return None, // don't think this should happen
-            AzoolRequestType::ReqTypeEndOfGame => return None,  // don't think this should happen
-            AzoolRequestType::ReqTypeFullRow => return None,    // can come from either player
-            AzoolRequestType::ReqTypeInvalid => return None,
-        } // match req_type
-        Some(response)
-    }
-    fn valid_factory_request(&self, factory_idx: usize, tile_color: &TileColor) -> bool {
+        };
+        let mut board = self.player_boards[seat].clone();
+        board.tile_wall(seat, &self.rules, &mut Events::new(), &mut chosen);
+        if misfit {
+            return Err(RuleError::BadColumn);
+        }
+        let full_row = board.has_full_row();
+        self.player_boards[seat] = board;
+        Ok(full_row)
+    } // fn tile_wall
+    fn check_factory_request(
+        &self,
+        factory_idx: usize,
+        tile_color: &TileColor,
+    ) -> Result<(), RuleError> {
         // index is valid, key is valid, count for that key is > 0
-        factory_idx < self.tile_factories.len()
-            && self.tile_factories[factory_idx].contains_key(&tile_color)
-            && *self.tile_factories[factory_idx]
-                .get(&tile_color)
-                .unwrap_or(&0)
-                > 0
-    } // fn valid_factory_request
+        if *tile_color == TileColor::NOCOLOR {
+            return Err(RuleError::BadColor);
+        }
+        let factory = self
+            .tile_factories
+            .get(factory_idx)
+            .ok_or(RuleError::NoSuchFactory)?;
+        if *factory.get(tile_color).unwrap_or(&0) == 0 {
+            return Err(RuleError::BadFactoryRequest);
+        }
+        Ok(())
+    } // fn check_factory_request
     fn take_tiles_from_factory(
         &mut self,
         factory_idx: usize,
         tile_color: &TileColor,
-    ) -> Result<i32, InvalidMoveError> {
-        if !self.valid_factory_request(factory_idx, tile_color) {
-            return Err(InvalidMoveError::BadFactoryRequestError);
-        }
+    ) -> Result<i32, RuleError> {
+        self.check_factory_request(factory_idx, tile_color)?;
         let mut this_factory = self.tile_factories.remove(factory_idx);
         let num_tiles = this_factory
             .remove(tile_color)
             .expect("We checked for validity, so how did this happen?");
         // move the other tiles to the pool
         for key in this_factory.keys() {
-            let tile_count: i32 = *this_factory.get(&key).unwrap();
+            let tile_count: i32 = *this_factory.get(key).unwrap();
             self.tile_pool
                 .entry(*key)
                 .and_modify(|ct| *ct += tile_count)
-                .or_insert(num_tiles);
+                .or_insert(tile_count);
         }
         Ok(num_tiles)
     } // fn take_tiles_from_factory
-    fn take_tiles_from_pool(&mut self, tile_color: &TileColor) -> Result<i32, InvalidMoveError> {
-        let num_tiles: i32 = *self.tile_pool.get(&tile_color).unwrap_or(&0);
+    fn take_tiles_from_pool(&mut self, tile_color: &TileColor) -> Result<i32, RuleError> {
+        if *tile_color == TileColor::NOCOLOR {
+            return Err(RuleError::BadColor);
+        }
+        let num_tiles: i32 = *self.tile_pool.get(tile_color).unwrap_or(&0);
         if num_tiles == 0 {
-            return Err(InvalidMoveError::BadPoolRequestError);
+            return Err(RuleError::BadPoolRequest);
         }
         self.tile_pool
             .entry(*tile_color)
             .and_modify(|tile_count| *tile_count = 0);
         Ok(num_tiles)
     } // fn take_tiles_from_pool
-    fn return_tiles_to_bag(&mut self, num_tiles: i32, color: &TileColor) {
+    fn return_tiles_to_lid(&mut self, num_tiles: i32, color: &TileColor) {
         for _ in 0..num_tiles {
-            self.tile_bag.push(*color);
+            self.tile_lid.push(*color);
         }
-    } // fn return_tiles_to_bag
+    } // fn return_tiles_to_lid
+    fn refill_bag_from_lid(&mut self) {
+        self.tile_bag.append(&mut self.tile_lid);
+    } // fn refill_bag_from_lid
+    fn shuffle_bag(&mut self) {
+        self.shuffles.push(self.tile_bag.len());
+        self.tile_bag.shuffle(&mut self.rng);
+    }
     fn deal_tiles(&mut self) {
-        let num_tiles = self.tile_bag.len() as i32;
-        let mut num_factories = std::cmp::min(num_tiles / 4, self.max_num_factories);
-        if num_tiles < 4 * num_factories {
-            num_factories = num_factories + 1;
-        }
-        let mut rng = rand::thread_rng();
-        self.tile_bag.shuffle(&mut rng);
-        for _ in 0..num_factories {
+        self.shuffle_bag();
+        'deal: for _ in 0..self.max_num_factories {
             let mut fact: HashMap<TileColor, i32> = HashMap::new();
-            for _ in 0..4 {
-                let drawn_tile = self
-                    .tile_bag
-                    .pop()
-                    .expect("Somehow we pulled a bad tile from the bag??");
+            for _ in 0..self.rules.tiles_per_factory {
+                if self.tile_bag.is_empty() {
+                    // bag ran dry mid-deal - dump the lid back in and keep going
+                    self.refill_bag_from_lid();
+                    self.shuffle_bag();
+                }
+                let drawn_tile = match self.tile_bag.pop() {
+                    Some(tile) => tile,
+                    None => {
+                        // bag and lid are both empty, so this round plays with what we have
+                        if !fact.is_empty() {
+                            self.tile_factories.push(fact);
+                        }
+                        break 'deal;
+                    }
+                };
                 fact.entry(drawn_tile)
                     .and_modify(|tile_count| *tile_count += 1)
                     .or_insert(1);
@@ -387,12 +571,7 @@ impl GameBoard {
     }
     fn end_of_round(&self) -> bool {
         // round ends when the pool and tile factories are empty
-        for ii in 0..NUM_COLORS {
-            if self.tile_pool[&TileColor::from_integer(ii)] > 0 {
-                return false;
-            }
-        }
-        self.tile_factories.is_empty()
+        self.tile_pool.values().all(|num| *num == 0) && self.tile_factories.is_empty()
     } // fn end_of_round
 } // impl GameBoard
 
@@ -401,392 +580,197 @@ struct Player {
     my_score: i32,
     my_num_penalties_for_round: i32,
     my_took_pool_penalty_this_round: bool,
+    my_floor_tiles: HashMap<TileColor, i32>, // goes to the box lid at the end of the round
     my_grid: Array2<bool>,
+    my_wall_colors: Option<Array2<TileColor>>, // gray wall only, see PlayerBoard
     my_rows: [(i32, TileColor); NUM_COLORS_AS_USIZE],
+    my_jokers: [i32; NUM_COLORS_AS_USIZE], // how many of each pattern line's tiles are jokers
     my_player_id: u8,
-    my_tx_to_gb: mpsc::Sender<json::JsonValue>,
-    my_rx_from_gb: mpsc::Receiver<json::JsonValue>,
+    my_tx_to_gb: mpsc::Sender<TableRequest>,
+    my_rx_from_gb: mpsc::Receiver<TableMessage>,
+    my_agent: Box<dyn PlayerAgent>,
+    my_events: Events, // what happened since we last reported in, for the game log
+    my_rules: RuleSet,
 }
 
-impl<'a> Player {
-    const PROMPT_FACTORY_DRAW: &'a str = "[f] take from factory ";
-    const PROMPT_POOL_DRAW: &'a str = "[p] take from pool ";
-    const PROMPT_DISCARD: &'a str = "[d] discard tile(s) ";
-    const PROMPT_PRINT_BOARD: &'a str = "[P] print game board";
+impl Player {
     pub fn new(
         my_player_id: u8,
-        my_tx_to_gb: mpsc::Sender<json::JsonValue>,
-        my_rx_from_gb: mpsc::Receiver<json::JsonValue>,
+        my_tx_to_gb: mpsc::Sender<TableRequest>,
+        my_rx_from_gb: mpsc::Receiver<TableMessage>,
+        my_agent: Box<dyn PlayerAgent>,
+        my_rules: RuleSet,
     ) -> Self {
         Player {
             my_score: 0,
             my_num_penalties_for_round: 0,
             my_took_pool_penalty_this_round: false,
+            my_floor_tiles: HashMap::new(),
             my_grid: arr2(&[[false; NUM_COLORS_AS_USIZE]; NUM_COLORS_AS_USIZE]),
+            my_wall_colors: None,
             my_rows: [(0, TileColor::NOCOLOR); NUM_COLORS_AS_USIZE],
+            my_jokers: [0; NUM_COLORS_AS_USIZE],
             my_player_id,
             my_tx_to_gb,
             my_rx_from_gb,
+            my_agent,
+            my_events: Events::new(),
+            my_rules,
         }
     }
-    fn check_valid_move(&self, color: TileColor, row_idx: usize) -> Result<bool, InvalidMoveError> {
-        // check if valid move
-        //   grid doesn't already have this color on this row
-        //   row is either empty or already has the same color
-        if color == TileColor::NOCOLOR {
-            return Err(InvalidMoveError::BadColorError);
-        }
-        let col_idx = get_col_idx(row_idx, color);
-        if self.my_grid[[row_idx, col_idx]] {
-            return Ok(false); // already have that color on this row
-        }
-        if !(self.my_rows[row_idx].1 == color || self.my_rows[row_idx].1 == TileColor::NOCOLOR) {
-            return Ok(false);
-        }
-        Ok(true)
-    }
+    // points for the tile that was just put on the wall at tile_row, tile_col, by the official
+    // rules. it's already on `grid`. a tile with no neighbours scores 1. otherwise it scores the
+    // length of the unbroken run it makes across its row if it touches anything there, plus the
+    // length of the one it makes down its column if it touches anything there. both runs count
+    // the new tile itself, so a tile joining a row and a column at once counts twice
     pub fn score_tile(grid: &ArrayView2<bool>, tile_row: &usize, tile_col: &usize) -> i32 {
-        let grid_row = grid.row(*tile_row);
-        let mut row_score: i32 = 0;
-        let (before, after) = grid_row.split_at(Axis(0), *tile_col);
-        for val in before.iter().rev() {
-            // i think we're double counting here?
-            if !val {
-                break;
-            }
-            row_score += 1;
-        }
-        for val in after.iter() {
-            if !val {
-                break;
-            }
-            row_score += 1;
-        }
-        let grid_col = grid.column(*tile_col);
-        let (before, after) = grid_col.split_at(Axis(0), *tile_row);
-        let mut col_score: i32 = 0;
-        for val in before.iter().rev() {
-            if !val {
-                break;
-            }
-            col_score += 1;
-        }
-        for val in after.iter() {
-            if !val {
-                break;
+        let (tile_row, tile_col) = (*tile_row as isize, *tile_col as isize);
+        debug_assert!(
+            grid[[tile_row as usize, tile_col as usize]],
+            "the tile isn't on the wall"
+        );
+        // how many tiles in a row there are next to ours, heading off in one direction
+        let neighbours = |row_step: isize, col_step: isize| -> i32 {
+            let mut count = 0;
+            let (mut row, mut col) = (tile_row + row_step, tile_col + col_step);
+            while grid.get([row as usize, col as usize]) == Some(&true) {
+                count += 1;
+                row += row_step;
+                col += col_step;
             }
-            col_score += 1;
+            count
+        };
+        let across = neighbours(0, -1) + neighbours(0, 1);
+        let down = neighbours(-1, 0) + neighbours(1, 0);
+        match (across, down) {
+            (0, 0) => 1,
+            (across, 0) => across + 1,
+            (0, down) => down + 1,
+            (across, down) => across + 1 + down + 1,
         }
-        if row_score == 1 || col_score == 1 {
-            return std::cmp::max(row_score, col_score);
+    } // fn score_tile
+    fn request_game_board(&self) -> TableView {
+        self.my_tx_to_gb
+            .send(TableRequest::GetBoard {
+                current_player: Some(self.my_player_id),
+            })
+            .unwrap();
+        match self.my_rx_from_gb.recv().unwrap() {
+            TableMessage::GetBoard(view) if view.current_player == Some(self.my_player_id) => view,
+            msg => panic!("expected our board, got {:?}", msg),
         }
-        let tile_score = row_score + col_score;
-        tile_score
-    }
-    fn prompt_for_factory_idx(num_factories: usize) -> Result<usize, InvalidMoveError> {
-        println!("Which factory? enter index [1-5]");
-        let mut read_buf: String = String::new();
-        std::io::stdin()
-            .read_line(&mut read_buf)
-            .map_err(InvalidMoveError::from_io)?;
-        let idx = read_buf
-            .trim()
-            .parse::<usize>()
-            .map_err(InvalidMoveError::from_parse)?;
-        if idx > num_factories || idx < 1 {
-            return Err(InvalidMoveError::BadFactoryRequestError);
-        }
-        Ok(idx - 1)
-    } // fn prompt_for_factory_idx
-    fn prompt_for_tile_color() -> Result<TileColor, InvalidMoveError> {
-        println!("Which color? [r|b|g|y|w]");
-        let mut read_buf = String::new();
-        std::io::stdin()
-            .read_line(&mut read_buf)
-            .map_err(InvalidMoveError::from_io)?;
-        match read_buf.chars().next() {
-            Some('r') => Ok(TileColor::RED),
-            Some('b') => Ok(TileColor::BLUE),
-            Some('g') => Ok(TileColor::GREEN),
-            Some('y') => Ok(TileColor::YELLOW),
-            Some('w') => Ok(TileColor::WHITE),
-            Some(_) => Err(InvalidMoveError::BadColorError),
-            None => Err(InvalidMoveError::UnknownError),
+    } // fn request_game_board
+
+    // sends a draw or discard and waits to hear how it went
+    fn request_move(&self, request: TableRequest) -> Result<MoveResult, RuleError> {
+        self.my_tx_to_gb.send(request).unwrap();
+        let result = match self.my_rx_from_gb.recv().unwrap() {
+            TableMessage::DrawFromFactory(result)
+            | TableMessage::DrawFromPool(result)
+            | TableMessage::DiscardFromFactory(result)
+            | TableMessage::DiscardFromPool(result) => result,
+            msg => panic!("expected an answer to our move, got {:?}", msg),
+        };
+        if !result.success {
+            return Err(result.error_type.unwrap_or(RuleError::NotAllowed));
         }
+        Ok(result)
+    } // fn request_move
+    fn seat(&self) -> usize {
+        (self.my_player_id - 1) as usize
     }
-    fn prompt_for_row_idx() -> Result<usize, InvalidMoveError> {
-        println!("Which row? enter number [1-5]");
-        let mut read_buf = String::new();
-        std::io::stdin()
-            .read_line(&mut read_buf)
-            .map_err(InvalidMoveError::from_io)?;
-        let row_idx = read_buf
-            .trim()
-            .parse::<usize>()
-            .map_err(InvalidMoveError::from_parse)?;
-        if row_idx < 1 || row_idx > 5 {
-            return Err(InvalidMoveError::BadInputRowIdxError);
-        }
-        Ok(row_idx - 1)
+    fn take_events(&mut self) -> Events {
+        std::mem::take(&mut self.my_events)
     }
-    fn print_board(&self, gb_msg: json::JsonValue) {
-        /*
-        object! {
-            "req_type" : AzoolRequestType::ReqTypeGetBoard.get_string(),
-            "num_factories" : fact_array.len(),
-            "factories" : fact_array,
-            "num_tiles_in_pool": num_tiles_in_pool,
-            "pool": pool_tree,
-            "end_of_round" : self.end_of_round(),
-            "white_tile_in_pool" : self.white_tile_in_pool,
-        }
-        */
-        // TODO - some sort of text stream?
-        println!("---------------------------");
-        println!("Factories:");
-        let mut counter = 1;
-        let mut lines = String::new();
-        for factory in gb_msg["factories"].members() {
-            lines.push_str(format! {"{}) ", counter}.as_str());
-            for (color_str, num) in factory.entries() {
-                for _ in 0..num.as_usize().unwrap() {
-                    lines.push_str(
-                        format! {"{} ", TileColor::from_string(color_str).color_string()}.as_str(),
-                    );
-                }
+    fn my_board(&self) -> PlayerBoard {
+        let mut floor_tiles = Vec::new();
+        for (color, count) in self.my_floor_tiles.iter() {
+            for _ in 0..*count {
+                floor_tiles.push(*color);
             }
-            lines.push_str("\n");
-            counter += 1;
-        } // iter over factories
-        lines.push_str("\nPOOL:\n");
-        if gb_msg["white_tile_in_pool"].as_bool().unwrap() {
-            lines.push_str("[-1]\n");
         }
-        for (color_str, num) in gb_msg["pool"].entries() {
-            lines.push_str(format!{"{} x {}\n", TileColor::from_string(color_str).color_string(), num.as_i32().unwrap()}.as_str());
+        PlayerBoard {
+            score: self.my_score,
+            has_first_player_marker: self.my_took_pool_penalty_this_round,
+            floor_tiles,
+            grid: self.my_grid.clone(),
+            rows: self.my_rows,
+            jokers: self.my_jokers,
+            wall_colors: self.my_wall_colors.clone(),
         }
-        for ii in 0..NUM_COLORS_AS_USIZE {
-            lines.push_str(format! {"{}) ", ii+1}.as_str());
-            for _ in ii + 1..NUM_COLORS_AS_USIZE {
-                lines.push_str(" ");
-            }
-            for jj in (0..ii + 1).rev() {
-                if self.my_rows[ii].1 == TileColor::NOCOLOR
-                    || jj >= self.my_rows[ii].0.try_into().unwrap()
-                {
-                    lines.push_str("_");
-                } else if jj < self.my_rows[ii].0.try_into().unwrap() {
-                    lines.push_str(self.my_rows[ii].1.color_string().as_str());
-                }
-            }
-            // print grid row
-            lines.push_str("  |");
-            for jj in 0..NUM_COLORS_AS_USIZE {
-                let color = TileColor::from_integer(((ii + jj) % 5) as i32);
-                if self.my_grid[[ii, jj]] {
-                    // print colored string
-                    lines.push_str(color.color_string().as_str());
-                } else {
-                    // print symbol only
-                    lines.push_str(color.to_char_symbol());
-                }
-                lines.push_str("|");
-            }
-            lines.push_str("\n");
-        } // iterate over rows
-        lines.push_str(format! {"CURRENT SCORE: {}", self.my_score}.as_str());
-        println!("{}", lines);
-        if self.my_took_pool_penalty_this_round {
-            println!("PLAYER {}*", self.my_player_id);
-        } else {
-            println!("PLAYER {}", self.my_player_id);
+    } // fn my_board
+
+    // picks up a saved game; the inverse of my_board
+    fn restore(&mut self, board: &PlayerBoard) {
+        self.my_score = board.score;
+        self.my_took_pool_penalty_this_round = board.has_first_player_marker;
+        self.my_num_penalties_for_round = board.num_penalties();
+        self.my_floor_tiles.clear();
+        for color in board.floor_tiles.iter() {
+            self.my_floor_tiles
+                .entry(*color)
+                .and_modify(|ct| *ct += 1)
+                .or_insert(1);
         }
-    } // fn print_board
-    fn request_game_board(&self) -> json::JsonValue {
-        self.my_tx_to_gb.send(object!{"req_type":AzoolRequestType::ReqTypeGetBoard.get_string(), "current_player" : self.my_player_id}).unwrap();
-        let msg = self.my_rx_from_gb.recv().unwrap();
-        // println!("{:?}", msg["req_type"]);
-        //TODO: good for debug (?)
-        if !msg.has_key("req_type") {
-            panic!("Missing key req_type!")
-        } else if !msg.has_key("current_player") {
-            panic!("Missing key current_player!")
-        } else if msg["current_player"].as_u8().unwrap() != self.my_player_id {
-            panic!(
-                "wrong current player! :{}",
-                msg["current_player"].as_u8().unwrap()
-            );
-        } else if AzoolRequestType::from_string(msg["req_type"].as_str().unwrap())
-            != AzoolRequestType::ReqTypeGetBoard
-        {
-            panic!("wrong request type! {}", msg["req_type"]);
-        } else {
-            msg
+        self.my_grid = board.grid.clone();
+        self.my_wall_colors = board.wall_colors.clone();
+        self.my_rows = board.rows;
+        self.my_jokers = board.jokers;
+    } // fn restore
+    fn observe(&self, view: &TableView) -> Observation {
+        // everyone else's board, starting with whoever plays after us
+        let opponents = match &view.players {
+            Some(boards) => (1..boards.len())
+                .map(|ii| boards[(self.seat() + ii) % boards.len()].clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        Observation {
+            rules: self.my_rules.clone(),
+            ..Observation::new(
+                self.seat(),
+                view.num_players,
+                view.factories.clone(),
+                view.pool.clone(),
+                view.white_tile_in_pool,
+                self.my_board(),
+                opponents,
+            )
         }
-    } // fn request_game_board
-    fn take_turn(&mut self) {
-        let mut full_input: bool = false;
-        let game_board_state = self.request_game_board();
-        let num_factories = game_board_state["num_factories"].as_usize().unwrap();
-        let num_in_pool = game_board_state["num_tiles_in_pool"].as_i32().unwrap();
-        self.print_board(game_board_state);
-        let mut write_buf = String::new();
-        let mut read_buf = String::new();
-        while !full_input {
-            if num_factories > 0 {
-                write_buf += Self::PROMPT_FACTORY_DRAW;
-            }
-            if num_in_pool > 0 {
-                write_buf += Self::PROMPT_POOL_DRAW;
-            }
-            write_buf += Self::PROMPT_DISCARD;
-            write_buf += Self::PROMPT_PRINT_BOARD;
-            println!("{write_buf}");
-            write_buf.clear();
-            read_buf.clear();
-            match std::io::stdin()
-                .read_line(&mut read_buf)
-                .map_err(InvalidMoveError::from_io)
-            {
-                Ok(_) => (),
-                Err(error) => {
-                    println!("ERROR: {:?}; try again", error);
-                    continue;
+    } // fn observe
+
+    // `time_left` is how long the table gives us, counting from when it asked
+    fn take_turn(&mut self, time_left: Option<Duration>) {
+        let deadline = time_left.and_then(|left| Instant::now().checked_add(left));
+        loop {
+            let game_board_state = self.request_game_board();
+            let obs = Observation {
+                time_left: deadline.map(|at| at.saturating_duration_since(Instant::now())),
+                ..self.observe(&game_board_state)
+            };
+            let action = self.my_agent.choose_action(&obs);
+            let result = match action {
+                Action::FactoryToRow {
+                    factory_idx,
+                    color,
+                    row_idx,
+                } => self.take_tiles_from_factory(factory_idx, color, row_idx),
+                Action::PoolToRow { color, row_idx } => self.take_tiles_from_pool(color, row_idx),
+                Action::FactoryToFloor { factory_idx, color } => {
+                    self.discard_from_factory(factory_idx, color)
                 }
+                Action::PoolToFloor { color } => self.discard_from_pool(color),
+            };
+            match result {
+                Ok(()) => break,
+                Err(error) => self.my_agent.on_invalid_action(action, error),
             }
-            match read_buf.chars().next().unwrap() {
-                'f' => {
-                    let factory_idx = match Self::prompt_for_factory_idx(num_factories) {
-                        Ok(idx) => idx,
-                        Err(error) => {
-                            println!("ERROR: {:?}; try again", error);
-                            continue;
-                        }
-                    };
-                    let tile_color: TileColor = match Self::prompt_for_tile_color() {
-                        Ok(color) => color,
-                        Err(error) => {
-                            println!("ERROR: {:?}; try again", error);
-                            continue;
-                        }
-                    };
-                    let row_idx = match Self::prompt_for_row_idx() {
-                        Ok(idx) => idx,
-                        Err(error) => {
-                            println!("ERROR: {:?}; try again", error);
-                            continue;
-                        }
-                    };
-                    if !self.take_tiles_from_factory(factory_idx, tile_color, row_idx) {
-                        println!(
-                            "ERROR: selected move: draw {:?} from factory {}; place on row {} FAILED",
-                            tile_color,
-                            factory_idx + 1,
-                            row_idx + 1);
-                        println!("row: {:?}", self.my_rows[row_idx]);
-                        continue;
-                    }
-                    full_input = true;
-                } // 'f'
-                'p' => {
-                    let tile_color: TileColor = match Self::prompt_for_tile_color() {
-                        Ok(color) => color,
-                        Err(error) => {
-                            println!("ERROR: {:?}; try again", error);
-                            continue;
-                        }
-                    };
-                    let row_idx = match Self::prompt_for_row_idx() {
-                        Ok(idx) => idx,
-                        Err(error) => {
-                            println!("ERROR: {:?}; try again", error);
-                            continue;
-                        }
-                    };
-                    if !self.take_tiles_from_pool(tile_color, row_idx) {
-                        println!(
-                            "ERROR: selected move: draw {:?} from pool; place on row {:?} FAILED",
-                            tile_color,
-                            row_idx + 1
-                        );
-                        println!("row: {:?}", self.my_rows[row_idx]);
-                        continue;
-                    }
-                    full_input = true;
-                } // 'p'
-                'd' => {
-                    let mut discard_input = String::new();
-                    println!("From factory or pool? [f|p]");
-                    match std::io::stdin()
-                        .read_line(&mut discard_input)
-                        .map_err(InvalidMoveError::from_io)
-                    {
-                        Ok(_) => (),
-                        Err(error) => {
-                            println!("INPUT ERROR: {:?} {}. Try again.", error, discard_input);
-                            continue;
-                        }
-                    }
-                    match discard_input.chars().next().unwrap() {
-                        'f' => {
-                            let factory_idx = match Self::prompt_for_factory_idx(num_factories) {
-                                Ok(idx) => idx,
-                                Err(error) => {
-                                    println!("ERROR: {:?}; try again", error);
-                                    continue;
-                                }
-                            };
-                            let tile_color = match Self::prompt_for_tile_color() {
-                                Ok(color) => color,
-                                Err(error) => {
-                                    println!("ERROR: {:?}; try again", error);
-                                    continue;
-                                }
-                            };
-                            if !self.discard_from_factory(factory_idx, tile_color) {
-                                println!(
-                                    "ERROR: selected move: discard {:?} from factory {} FAILED",
-                                    tile_color,
-                                    factory_idx + 1
-                                );
-                                continue;
-                            }
-                            full_input = true;
-                        }
-                        'p' => {
-                            let tile_color = match Self::prompt_for_tile_color() {
-                                Ok(color) => color,
-                                Err(error) => {
-                                    println!("ERROR: {:?}; try again", error);
-                                    continue;
-                                }
-                            };
-                            if !self.discard_from_pool(tile_color) {
-                                println!(
-                                    "ERROR: selected move: discard {} from pool FAILED",
-                                    tile_color,
-                                );
-                                continue;
-                            }
-                            full_input = true;
-                        }
-                        _ => {
-                            println!("Invalid input! Try again.");
-                            continue;
-                        }
-                    } // discard input
-                } // 'd'
-                'P' => {
-                    let ans = self.request_game_board();
-                    self.print_board(ans);
-                }
-                _ => {
-                    println!("Invalid input! Try again.");
-                    continue;
-                }
-            } // match read_buf.chars().next().unwrap()
-        } // while !full_input
-        let request = object! {"req_type": AzoolRequestType::ReqTypeTurnFinished.get_string(), "current_player" : self.my_player_id, "pool_penalty" : self.my_took_pool_penalty_this_round};
+        }
+        let request = TableRequest::TurnFinished {
+            current_player: self.my_player_id,
+            pool_penalty: self.my_took_pool_penalty_this_round,
+            events: self.take_events(),
+        };
         self.my_tx_to_gb.send(request).unwrap();
     } // fn take_turn
     fn take_tiles_from_factory(
@@ -794,171 +778,162 @@ impl<'a> Player {
         factory_idx: usize,
         color: TileColor,
         row_idx: usize,
-    ) -> bool {
-        if !self.check_valid_move(color, row_idx).unwrap() {
-            println!("not a valid move");
-            return false;
-        }
-        let request = object! {"req_type": AzoolRequestType::ReqTypeDrawFromFactory.get_string(), "tile_color": color.to_integer(),
-        "factory_idx": factory_idx, "current_player" : self.my_player_id};
-        self.my_tx_to_gb.send(request).unwrap();
-        let msg = self.my_rx_from_gb.recv().unwrap();
-        let success: bool = msg["success"].as_bool().unwrap();
-        if success {
-            let num_tiles: i32 = msg["num_tiles_returned"].as_i32().unwrap();
-            self.place_tiles(row_idx, color, num_tiles);
-        }
-        success
+    ) -> Result<(), RuleError> {
+        // the table checks the placement against its own copy of our board
+        let msg = self.request_move(TableRequest::DrawFromFactory(FactoryDraw {
+            current_player: Some(self.my_player_id),
+            factory_idx,
+            tile_color: color,
+            row_idx: Some(row_idx),
+        }))?;
+        let num_tiles = msg.num_tiles_returned;
+        self.my_events.push(Event::TilesTaken {
+            player: self.seat(),
+            factory_idx: Some(factory_idx),
+            color,
+            num_tiles,
+        });
+        self.place_tiles(row_idx, color, num_tiles);
+        Ok(())
     }
-    fn take_tiles_from_pool(&mut self, color: TileColor, row_idx: usize) -> bool {
-        if !self.check_valid_move(color, row_idx).unwrap() {
-            return false;
+    fn take_tiles_from_pool(&mut self, color: TileColor, row_idx: usize) -> Result<(), RuleError> {
+        let msg = self.request_move(TableRequest::DrawFromPool(PoolDraw {
+            current_player: Some(self.my_player_id),
+            tile_color: color,
+            row_idx: Some(row_idx),
+        }))?;
+        let num_tiles = msg.num_tiles_returned;
+        self.my_events.push(Event::TilesTaken {
+            player: self.seat(),
+            factory_idx: None,
+            color,
+            num_tiles,
+        });
+        if msg.pool_penalty == Some(true) {
+            self.take_first_player_marker();
         }
-        let request = object! {"req_type" : AzoolRequestType::ReqTypeDrawFromPool.get_string(), "tile_color": color.to_integer(), "current_player" : self.my_player_id};
-        self.my_tx_to_gb.send(request).unwrap();
-        let msg = self.my_rx_from_gb.recv().unwrap();
-        let success: bool = msg["success"].as_bool().unwrap();
-        if success {
-            let num_tiles: i32 = msg["num_tiles_returned"].as_i32().unwrap();
-            self.place_tiles(row_idx, color, num_tiles);
-            if msg["pool_penalty"].as_bool().unwrap() {
-                self.my_took_pool_penalty_this_round = true;
-                self.my_num_penalties_for_round += 1;
-            }
-        }
-        success
+        self.place_tiles(row_idx, color, num_tiles);
+        Ok(())
     }
-    fn discard_from_factory(&mut self, factory_idx: usize, color: TileColor) -> bool {
-        let request = object! {"req_type" : AzoolRequestType::ReqTypeDiscardFromFactory.get_string(), "tile_color": color.to_integer(), "factory_idx":factory_idx, "current_player" : self.my_player_id};
-        self.my_tx_to_gb.send(request).unwrap();
-        let msg = self.my_rx_from_gb.recv().unwrap();
-        let success: bool = msg["success"].as_bool().unwrap();
-        if success {
-            let num_tiles: i32 = msg["num_tiles_returned"].as_i32().unwrap();
-            self.my_num_penalties_for_round += num_tiles;
-            let request = object! {"req_type" : AzoolRequestType::ReqTypeReturnToBag.get_string(), "tile_color":color.to_integer(), "num_tiles_returned":num_tiles, "current_player" : self.my_player_id};
-            self.my_tx_to_gb.send(request).unwrap();
-        }
-        success
+    fn discard_from_factory(
+        &mut self,
+        factory_idx: usize,
+        color: TileColor,
+    ) -> Result<(), RuleError> {
+        let msg = self.request_move(TableRequest::DiscardFromFactory(FactoryDraw {
+            current_player: Some(self.my_player_id),
+            factory_idx,
+            tile_color: color,
+            row_idx: None,
+        }))?;
+        let num_tiles = msg.num_tiles_returned;
+        self.my_events.push(Event::TilesTaken {
+            player: self.seat(),
+            factory_idx: Some(factory_idx),
+            color,
+            num_tiles,
+        });
+        self.drop_tiles_on_floor(color, num_tiles);
+        Ok(())
     }
-    fn discard_from_pool(&mut self, color: TileColor) -> bool {
-        let request = object! {"req_type" : AzoolRequestType::ReqTypeDiscardFromPool.get_string(), "tile_color": color.to_integer(), "current_player" : self.my_player_id};
-        self.my_tx_to_gb.send(request).unwrap();
-        let msg = self.my_rx_from_gb.recv().unwrap();
-        let success: bool = msg["success"].as_bool().unwrap();
-        if success {
-            if msg["pool_penalty"].as_bool().unwrap() {
-                self.my_took_pool_penalty_this_round = true;
-                self.my_num_penalties_for_round += 1;
-            }
-            let num_tiles: i32 = msg["num_tiles_returned"].as_i32().unwrap();
-            self.my_num_penalties_for_round += num_tiles;
-            let request = object! {"req_type" : AzoolRequestType::ReqTypeReturnToBag.get_string(), "tile_color":color.to_integer(),
-            "num_tiles_returned":num_tiles, "current_player" : self.my_player_id};
-            self.my_tx_to_gb.send(request).unwrap();
+    fn discard_from_pool(&mut self, color: TileColor) -> Result<(), RuleError> {
+        let msg = self.request_move(TableRequest::DiscardFromPool(PoolDraw {
+            current_player: Some(self.my_player_id),
+            tile_color: color,
+            row_idx: None,
+        }))?;
+        let num_tiles = msg.num_tiles_returned;
+        self.my_events.push(Event::TilesTaken {
+            player: self.seat(),
+            factory_idx: None,
+            color,
+            num_tiles,
+        });
+        if msg.pool_penalty == Some(true) {
+            self.take_first_player_marker();
         }
-        success
+        self.drop_tiles_on_floor(color, num_tiles);
+        Ok(())
     }
+    fn take_first_player_marker(&mut self) {
+        self.my_took_pool_penalty_this_round = true;
+        self.my_num_penalties_for_round += 1;
+        self.my_events.push(Event::FirstPlayerMarkerTaken {
+            player: self.seat(),
+        });
+    } // fn take_first_player_marker
     fn place_tiles(&mut self, row_idx: usize, color: TileColor, num_tiles: i32) {
         self.my_rows[row_idx].0 += num_tiles;
-        self.my_rows[row_idx].1 = color;
         let max_num_in_row: i32 = row_idx as i32 + 1;
-        if self.my_rows[row_idx].0 > max_num_in_row {
-            self.my_num_penalties_for_round += self.my_rows[row_idx].0 - max_num_in_row;
-            self.my_rows[row_idx].0 = max_num_in_row;
+        let overflow = std::cmp::max(self.my_rows[row_idx].0 - max_num_in_row, 0);
+        self.my_rows[row_idx].0 -= overflow;
+        // jokers take on the line's color instead of setting it
+        match color {
+            TileColor::JOKER => self.my_jokers[row_idx] += num_tiles - overflow,
+            color => self.my_rows[row_idx].1 = color,
         }
+        self.my_events.push(Event::TilesPlaced {
+            player: self.seat(),
+            row_idx,
+            color,
+            num_tiles: num_tiles - overflow,
+        });
+        self.drop_tiles_on_floor(color, overflow);
     } // fn place_tiles
+    fn drop_tiles_on_floor(&mut self, color: TileColor, num_tiles: i32) {
+        if num_tiles == 0 {
+            return;
+        }
+        self.my_events.push(Event::TilesToFloor {
+            player: self.seat(),
+            color,
+            num_tiles,
+        });
+        self.my_num_penalties_for_round += num_tiles;
+        self.my_floor_tiles
+            .entry(color)
+            .and_modify(|ct| *ct += num_tiles)
+            .or_insert(num_tiles);
+    } // fn drop_tiles_on_floor
     fn end_round_and_return_full_row(&mut self) -> bool {
-        let mut returns_array = json::JsonValue::new_object();
-        for (row_idx, row) in self.my_rows.iter_mut().enumerate() {
-            if row.0 == TryInto::<i32>::try_into(row_idx + 1).unwrap() {
-                let col: usize = get_col_idx(row_idx, row.1);
-                self.my_grid[[row_idx, col]] = true;
-                self.my_score += Self::score_tile(&self.my_grid.view(), &row_idx, &col);
-                let _ = returns_array.insert(&row.1.to_string(), row_idx);
-                row.0 = 0;
-                row.1 = TileColor::NOCOLOR;
+        let seat = self.seat();
+        let mut board = self.my_board();
+        let agent = &mut self.my_agent;
+        // the agent picks gray wall columns, and gets asked again until it picks one that fits
+        let mut choose = |board: &PlayerBoard, row_idx: usize, columns: &[usize]| loop {
+            let col_idx = agent.choose_column(board, row_idx, columns);
+            if columns.contains(&col_idx) {
+                return col_idx;
             }
+            agent.on_invalid_column(col_idx, RuleError::BadColumn);
+        };
+        // everything but the tiles that went on the wall ends up in the box lid
+        let mut returns: HashMap<TileColor, i32> = HashMap::new();
+        let rules = &self.my_rules;
+        for color in board.tile_wall(seat, rules, &mut self.my_events, &mut choose) {
+            returns.entry(color).and_modify(|ct| *ct += 1).or_insert(1);
         }
-        let request = object! {"req_type" : AzoolRequestType::ReqTypeReturnToBag.get_string(), "returns":returns_array, "current_player" : self.my_player_id};
+        self.restore(&board);
+        let request = TableRequest::ReturnToLid {
+            current_player: Some(self.my_player_id),
+            returns,
+        };
         self.my_tx_to_gb.send(request).unwrap();
-        self.my_score -= Self::get_score_penalty(&self.my_num_penalties_for_round);
-        self.my_score = std::cmp::max(self.my_score, 0);
-        self.my_took_pool_penalty_this_round = false;
-        self.my_num_penalties_for_round = 0;
-        for row_idx in 0..NUM_COLORS_AS_USIZE {
-            let mut full_row: bool = true;
-            for col_idx in 0..NUM_COLORS_AS_USIZE {
-                if !self.my_grid[[row_idx, col_idx]] {
-                    full_row = false;
-                    break;
-                }
-            } // itr over elements in row
-            if full_row {
-                return true;
-            }
-        } // iter over rows in grid
-        false
+        board.has_full_row()
     } // fn end_round_and_return_full_row
-    fn get_score_penalty(num_penalties_for_round: &i32) -> i32 {
-        let num_penalties: usize = *num_penalties_for_round as usize;
-        if num_penalties >= PENALTY_POINTS.len() {
-            return PENALTY_POINTS[PENALTY_POINTS.len() - 1];
-        }
-        PENALTY_POINTS[num_penalties]
-    } // fn get_score_penalty
     fn end_game(&mut self) -> i32 {
-        self.my_score += finalize_score(&self.my_grid);
+        let points = finalize_score(&self.my_board(), &self.my_rules);
+        self.my_score += points;
+        self.my_events.push(Event::FinalBonus {
+            player: self.seat(),
+            points,
+        });
         self.my_score
     }
 } // impl PLayer
-fn finalize_score(grid: &Array2<bool>) -> i32 {
-    let mut score_bonus = 0;
-    let mut num_rows = 0;
-    for row in grid.rows() {
-        let mut full_row: bool = true;
-        for cell in row {
-            if !cell {
-                full_row = false;
-                break;
-            }
-        }
-        if full_row {
-            num_rows += 1;
-        }
-    }
-    score_bonus += num_rows * 2;
-    let mut num_cols = 0;
-    for col in grid.columns() {
-        let mut full_col: bool = true;
-        for cell in col {
-            if !cell {
-                full_col = false;
-                break;
-            }
-        }
-        if full_col {
-            num_cols += 1;
-        }
-    }
-    score_bonus += num_cols * 7;
-    let mut num_fives = 0;
-    for ii in 0..NUM_COLORS_AS_USIZE {
-        let mut all_five: bool = true;
-        for row_idx in 0..NUM_COLORS_AS_USIZE {
-            let col_idx: usize = get_col_idx(row_idx, TileColor::from_integer(ii as i32));
-            if !grid[[row_idx, col_idx]] {
-                all_five = false;
-                break;
-            }
-        } // check each row for color ii
-        if all_five {
-            num_fives += 1;
-        }
-    } // iterate over all colors
-    score_bonus += num_fives * 10;
-    score_bonus
-} // fn finalize_score
+fn finalize_score(board: &PlayerBoard, rules: &RuleSet) -> i32 {
+    Bonuses::count(board).points(rules)
+}
 fn get_col_idx(row_idx: usize, color: TileColor) -> usize {
     (5 + color as usize - row_idx) % 5
 }
@@ -966,9 +941,20 @@ fn get_col_idx(row_idx: usize, color: TileColor) -> usize {
 pub fn print_player() {
     let (tx, _) = mpsc::channel();
     let (_, rx) = mpsc::channel();
-    let p1 = Player::new(0, tx, rx);
+    let p1 = Player::new(
+        0,
+        tx,
+        rx,
+        Box::new(TerminalAgent::new()),
+        RuleSet::default(),
+    );
     println!("{:#?}", p1);
 }
+#[cfg(test)]
+use json::object;
+#[cfg(test)]
+use rand::Rng;
+
 #[test]
 fn test_tile_score() {
     let mut arr = [[false; NUM_COLORS_AS_USIZE]; NUM_COLORS_AS_USIZE];
@@ -982,7 +968,135 @@ fn test_tile_score() {
     let score = Player::score_tile(&grid.view(), &2, &1);
     assert_eq!(score, 5);
 }
+// the scoring rules taken as literally as possible, to check score_tile against. `wall` has a
+// bit for each spot, row by row, and the tile at tile_row, tile_col is already on it
+#[cfg(test)]
+fn reference_score(wall: u32, tile_row: usize, tile_col: usize) -> i32 {
+    let filled = |row: usize, col: usize| wall & (1 << (row * 5 + col)) != 0;
+    // the longest stretch of filled spots in `line` that takes in spot `at`
+    let longest_run = |line: &dyn Fn(usize) -> bool, at: usize| -> i32 {
+        let mut longest = 0;
+        for first in 0..=at {
+            for last in at..5 {
+                if (first..=last).all(line) {
+                    longest = std::cmp::max(longest, last - first + 1);
+                }
+            }
+        }
+        longest as i32
+    };
+    let in_row = |col: usize| filled(tile_row, col);
+    let in_col = |row: usize| filled(row, tile_col);
+    let touches_row =
+        (tile_col > 0 && in_row(tile_col - 1)) || (tile_col < 4 && in_row(tile_col + 1));
+    let touches_col =
+        (tile_row > 0 && in_col(tile_row - 1)) || (tile_row < 4 && in_col(tile_row + 1));
+    if !touches_row && !touches_col {
+        return 1;
+    }
+    let mut points = 0;
+    if touches_row {
+        points += longest_run(&in_row, tile_col);
+    }
+    if touches_col {
+        points += longest_run(&in_col, tile_row);
+    }
+    points
+} // fn reference_score
+#[cfg(test)]
+fn wall_from_bits(wall: u32) -> Array2<bool> {
+    Array2::from_shape_fn((5, 5), |(row, col)| wall & (1 << (row * 5 + col)) != 0)
+}
 #[test]
+fn test_tile_score_by_the_rules() {
+    // (wall, tile, points), drawn with the new tile as a T
+    let walls = [
+        ([".....", ".....", "..T..", ".....", "....."], 1), // on its own
+        ([".....", ".....", ".XTX.", ".....", "....."], 3), // middle of a row
+        ([".....", "..X..", "..T..", ".....", "....."], 2), // under one
+        ([".....", "..X..", ".XTX.", "..X..", "....."], 6), // a cross counts the tile twice
+        (["T....", "X....", ".....", ".....", "....."], 2), // in the corner
+        (["XX.XX", "..X..", "XXTXX", ".....", "..X.."], 7), // a full row, but the column's broken
+        ([".X...", ".X...", "XT...", ".....", ".X..."], 5), // diagonals don't count
+    ];
+    for (rows, points) in walls {
+        let mut wall = 0;
+        let mut tile = (0, 0);
+        for (row, line) in rows.iter().enumerate() {
+            for (col, spot) in line.chars().enumerate() {
+                if spot != '.' {
+                    wall |= 1 << (row * 5 + col);
+                }
+                if spot == 'T' {
+                    tile = (row, col);
+                }
+            }
+        }
+        let grid = wall_from_bits(wall);
+        assert_eq!(
+            Player::score_tile(&grid.view(), &tile.0, &tile.1),
+            points,
+            "{:?}",
+            rows
+        );
+        assert_eq!(reference_score(wall, tile.0, tile.1), points, "{:?}", rows);
+    }
+}
+#[test]
+fn test_tile_score_matches_the_reference_for_every_row_and_column() {
+    // a tile's score only depends on the rest of its row and column, so this covers every way
+    // those can be filled in, with the rest of the wall empty, full and somewhere in between
+    let mut rng = StdRng::seed_from_u64(24);
+    for tile_row in 0..5 {
+        for tile_col in 0..5 {
+            let tile = 1 << (tile_row * 5 + tile_col);
+            let lines: Vec<u32> = (0..25)
+                .filter(|spot| spot / 5 == tile_row || spot % 5 == tile_col)
+                .map(|spot| 1 << spot)
+                .filter(|bit| *bit != tile)
+                .collect();
+            let line_mask: u32 = lines.iter().sum::<u32>() | tile;
+            for pattern in 0..(1u32 << lines.len()) {
+                let in_lines: u32 = (0..lines.len())
+                    .filter(|ii| pattern & (1 << ii) != 0)
+                    .map(|ii| lines[ii])
+                    .sum();
+                let rest: u32 = rng.gen();
+                for elsewhere in [0, !line_mask, rest & !line_mask] {
+                    let wall = (tile | in_lines | elsewhere) & ((1 << 25) - 1);
+                    assert_eq!(
+                        Player::score_tile(&wall_from_bits(wall).view(), &tile_row, &tile_col),
+                        reference_score(wall, tile_row, tile_col),
+                        "wall {:025b} tile {:?}",
+                        wall,
+                        (tile_row, tile_col)
+                    );
+                }
+            }
+        }
+    }
+}
+// every one of the 2^25 walls, and every tile on each of them. too slow for every run, so:
+// cargo test --release -- --ignored test_tile_score_matches_the_reference_on_every_wall
+#[test]
+#[ignore]
+fn test_tile_score_matches_the_reference_on_every_wall() {
+    for wall in 0..(1u32 << 25) {
+        let grid = wall_from_bits(wall);
+        for spot in (0..25).filter(|spot| wall & (1 << spot) != 0) {
+            let (tile_row, tile_col) = (spot / 5, spot % 5);
+            assert_eq!(
+                Player::score_tile(&grid.view(), &tile_row, &tile_col),
+                reference_score(wall, tile_row, tile_col),
+                "wall {:025b} tile {:?}",
+                wall,
+                (tile_row, tile_col)
+            );
+        }
+    }
+}
+#[test]
+#[allow(clippy::needless_range_loop)]
 fn test_score_bonuses() {
     let mut arr = [[false; NUM_COLORS_AS_USIZE]; NUM_COLORS_AS_USIZE];
     for idx in 0..NUM_COLORS_AS_USIZE {
@@ -998,22 +1112,113 @@ fn test_score_bonuses() {
     arr[0][0] = true;
     arr[0][3] = true;
     arr[4][4] = true;
-    assert_eq!(finalize_score(&arr2(&arr)), 19);
+    let board_with = |arr: [[bool; NUM_COLORS_AS_USIZE]; NUM_COLORS_AS_USIZE]| PlayerBoard {
+        grid: arr2(&arr),
+        ..PlayerBoard::new()
+    };
+    let rules = RuleSet::default();
+    assert_eq!(finalize_score(&board_with(arr), &rules), 19);
+    // house rules pay out their own bonuses
+    let house_rules = RuleSet {
+        row_bonus: 1,
+        column_bonus: 4,
+        color_bonus: 6,
+        ..RuleSet::default()
+    };
+    assert_eq!(finalize_score(&board_with(arr), &house_rules), 11);
     // removes row and column bonuses
     arr[1][1] = false;
-    assert_eq!(finalize_score(&arr2(&arr)), 10);
+    assert_eq!(finalize_score(&board_with(arr), &rules), 10);
+}
+#[test]
+fn test_deal_tiles_num_players() {
+    for num_players in 2..=4 {
+        let mut game_board = GameBoard::with_rng(num_players, StdRng::seed_from_u64(0));
+        game_board.deal_tiles();
+        assert_eq!(game_board.tile_factories.len() as i32, 2 * num_players + 1);
+        for fact in &game_board.tile_factories {
+            assert_eq!(fact.values().sum::<i32>(), 4);
+        }
+    }
+}
+#[test]
+fn test_deal_tiles_house_rules() {
+    let rules = RuleSet {
+        tiles_per_color: 3,
+        tiles_per_factory: 3,
+        ..RuleSet::default()
+    };
+    let mut game_board = GameBoard::with_rules(2, StdRng::seed_from_u64(0), rules);
+    assert_eq!(game_board.tile_bag.len(), 15);
+    game_board.deal_tiles();
+    // five factories of three, which leaves nothing in the bag
+    assert_eq!(game_board.tile_factories.len(), 5);
+    for fact in &game_board.tile_factories {
+        assert_eq!(fact.values().sum::<i32>(), 3);
+    }
+    assert!(game_board.tile_bag.is_empty());
+}
+#[test]
+fn test_deal_tiles_same_seed() {
+    let mut board_a = GameBoard::with_rng(3, StdRng::seed_from_u64(1234));
+    let mut board_b = GameBoard::with_rng(3, StdRng::seed_from_u64(1234));
+    for _ in 0..3 {
+        board_a.deal_tiles();
+        board_b.deal_tiles();
+        assert_eq!(board_a.tile_factories, board_b.tile_factories);
+        assert_eq!(board_a.tile_bag, board_b.tile_bag);
+        board_a.tile_factories.clear();
+        board_b.tile_factories.clear();
+    }
+}
+#[test]
+fn test_deal_refills_bag_from_lid() {
+    let mut game_board = GameBoard::with_rng(2, StdRng::seed_from_u64(0));
+    // 6 tiles left in the bag, 10 in the lid
+    game_board.tile_bag.truncate(6);
+    game_board.return_tiles_to_lid(10, &TileColor::RED);
+    game_board.deal_tiles();
+    assert_eq!(game_board.tile_factories.len(), 4);
+    assert_eq!(
+        game_board
+            .tile_factories
+            .iter()
+            .map(|fact| fact.values().sum::<i32>())
+            .collect::<Vec<i32>>(),
+        vec![4, 4, 4, 4]
+    );
+    assert!(game_board.tile_bag.is_empty());
+    assert!(game_board.tile_lid.is_empty());
+}
+#[test]
+fn test_msg_processing_return_to_lid() {
+    let mut game_board = GameBoard::with_rng(2, StdRng::seed_from_u64(0));
+    let num_in_bag = game_board.tile_bag.len();
+    let request = TableRequest::ReturnToLid {
+        current_player: None,
+        returns: HashMap::from([(TileColor::RED, 2), (TileColor::BLUE, 3)]),
+    };
+    assert!(game_board.process_msg(request).is_none());
+    assert_eq!(game_board.tile_lid.len(), 5);
+    assert_eq!(game_board.tile_bag.len(), num_in_bag);
 }
 #[test]
-#[should_panic]
 fn test_msg_processing_invalid_type() {
-    let mut game_board = GameBoard::new();
+    let mut game_board = GameBoard::with_rng(2, StdRng::seed_from_u64(0));
     game_board.deal_tiles();
     let request = object! {"req_type": "brglker"};
-    let _ = game_board.process_msg(request).unwrap();
+    assert!(protocol::from_json::<TableRequest>(&request).is_err());
+    // reports are for host_game, not the board
+    let request = TableRequest::TurnFinished {
+        current_player: 1,
+        pool_penalty: false,
+        events: Events::new(),
+    };
+    assert!(game_board.process_msg(request).is_none());
 }
 #[test]
 fn test_msg_processing_factory_draw() {
-    let mut game_board = GameBoard::new();
+    let mut game_board = GameBoard::with_rng(2, StdRng::seed_from_u64(0));
     game_board.deal_tiles();
     let error_msg =
         format! {"Something wrong with our tile factories? {:#?}", game_board.tile_factories};
@@ -1023,30 +1228,105 @@ fn test_msg_processing_factory_draw() {
         .next()
         .expect(error_msg.as_str());
     let num_tiles = game_board.tile_factories[0][color];
-    let request = object! {"req_type" : AzoolRequestType::ReqTypeDrawFromFactory.get_string(), "tile_color" : color.to_integer(), "factory_idx" : 0};
-    let result = game_board.process_msg(request).unwrap();
-    assert_eq!(result["num_tiles_returned"].as_i32(), Some(num_tiles));
+    let color = *color;
+    let request = object! {"req_type" : AzoolRequestType::ReqTypeDrawFromFactory.get_string(), "tile_color" : color.to_integer(), "factory_idx" : 0, "current_player" : 1, "row_idx" : 4};
+    let result = game_board.process_msg(protocol::from_json(&request).unwrap());
+    assert_eq!(
+        protocol::to_json(&result)["num_tiles_returned"].as_i32(),
+        Some(num_tiles)
+    );
     assert_eq!(game_board.tile_factories.len(), num_factories - 1);
-    assert!(game_board.tile_pool.len() > 0 || Some(num_tiles) == Some(4));
+    assert!(!game_board.tile_pool.is_empty() || Some(num_tiles) == Some(4));
+    // every way to get it wrong says which way it was
+    for (factory_idx, tile_color, code) in [
+        (num_factories, color, "NO_SUCH_FACTORY"),
+        (0, TileColor::NOCOLOR, "BAD_COLOR"),
+    ] {
+        let draw = FactoryDraw {
+            current_player: Some(1),
+            factory_idx,
+            tile_color,
+            row_idx: Some(0),
+        };
+        let result = game_board.process_msg(TableRequest::DrawFromFactory(draw));
+        let result = protocol::to_json(&result);
+        assert_eq!(result["success"], false);
+        assert_eq!(result["error_type"], code);
+        assert!(result["error"].is_string());
+    }
+}
+// what the table says to a factory 0 draw, if it turns it down
+#[cfg(test)]
+fn draw_error(
+    game_board: &mut GameBoard,
+    current_player: Option<u8>,
+    tile_color: TileColor,
+    row_idx: Option<usize>,
+) -> Option<RuleError> {
+    let request = TableRequest::DrawFromFactory(FactoryDraw {
+        current_player,
+        factory_idx: 0,
+        tile_color,
+        row_idx,
+    });
+    match game_board.process_msg(request) {
+        Some(TableMessage::DrawFromFactory(result)) => result.error_type,
+        result => panic!("expected a factory draw, got {:?}", result),
+    }
+}
+#[test]
+fn test_msg_processing_checks_placement() {
+    let mut game_board = GameBoard::with_rng(2, StdRng::seed_from_u64(0));
+    game_board.deal_tiles();
+    let color = *game_board.tile_factories[0].keys().next().unwrap();
+    let other = TileColor::from_integer((color.to_integer() + 1) % NUM_COLORS);
+    let gb = &mut game_board;
+    assert_eq!(
+        draw_error(gb, None, color, Some(0)),
+        Some(RuleError::NoSuchPlayer)
+    );
+    assert_eq!(
+        draw_error(gb, Some(3), color, Some(0)),
+        Some(RuleError::NoSuchPlayer)
+    );
+    assert_eq!(
+        draw_error(gb, Some(1), color, None),
+        Some(RuleError::BadRowIdx)
+    );
+    assert_eq!(draw_error(gb, Some(1), color, Some(4)), None);
+    // the table put them on its own copy of the board, and holds the player to it
+    assert_eq!(game_board.player_boards[0].rows[4].1, color);
+    let gb = &mut game_board;
+    assert_eq!(
+        draw_error(gb, Some(1), other, Some(4)),
+        Some(RuleError::RowHasDifferentColor)
+    );
+    gb.player_boards[0].grid[[2, get_col_idx(2, other)]] = true;
+    assert_eq!(
+        draw_error(gb, Some(1), other, Some(2)),
+        Some(RuleError::ColorAlreadyOnWall)
+    );
+    // nothing moved for any of that
+    assert_eq!(game_board.player_boards[1], PlayerBoard::new());
 }
 #[test]
 fn test_msg_processing_pool_draw() {
-    let mut game_board = GameBoard::new();
+    // with this seed the first factory has more than one color, so drawing from it fills the pool
+    let mut game_board = GameBoard::with_rng(2, StdRng::seed_from_u64(4));
     game_board.deal_tiles();
     let error_msg =
         format! {"Something wrong with our tile factories? {:#?}", game_board.tile_factories};
-    while game_board.tile_factories[0].keys().len() == 1 {
-        // if the first factory is all of the same tile, drawing won't put anything in the pool
-        // so re-deal until it works
-        println!("got all of one kind - re-dealing...");
-        game_board.reset_board();
-        game_board.deal_tiles();
-    }
+    assert!(game_board.tile_factories[0].keys().len() > 1);
     let draw_color = game_board.tile_factories[0]
         .keys()
         .next()
         .expect(error_msg.as_str());
-    let _ = game_board.process_msg(object! {"req_type" : AzoolRequestType::ReqTypeDrawFromFactory.get_string(), "tile_color" : draw_color.to_integer(), "factory_idx" : 0});
+    let _ = game_board.process_msg(TableRequest::DrawFromFactory(FactoryDraw {
+        current_player: Some(1),
+        factory_idx: 0,
+        tile_color: *draw_color,
+        row_idx: Some(0),
+    }));
     let mut draw_color = TileColor::NOCOLOR;
     let mut num_tiles = 0;
     for (color, count) in game_board.tile_pool.iter() {
@@ -1056,26 +1336,257 @@ fn test_msg_processing_pool_draw() {
             break;
         }
     }
-    let result = game_board.process_msg(object!{"req_type" : AzoolRequestType::ReqTypeDrawFromPool.get_string(), "tile_color" : draw_color.to_integer()}).unwrap();
-    assert_eq!(result["num_tiles_returned"].as_i32(), Some(num_tiles));
+    let result = game_board.process_msg(TableRequest::DrawFromPool(PoolDraw {
+        current_player: Some(1),
+        tile_color: draw_color,
+        row_idx: Some(1),
+    }));
+    match result {
+        Some(TableMessage::DrawFromPool(result)) => {
+            assert_eq!(result.num_tiles_returned, num_tiles);
+            assert_eq!(result.pool_penalty, Some(true));
+        }
+        result => panic!("expected a pool draw, got {:?}", result),
+    }
+    assert!(game_board.player_boards[0].has_first_player_marker);
+}
+// plays the first legal move, but tries a column that isn't there before settling on the last one
+#[cfg(test)]
+struct StubbornAgent {
+    insist: bool,
+    refused: mpsc::Sender<(usize, RuleError)>,
+}
+#[cfg(test)]
+impl PlayerAgent for StubbornAgent {
+    fn name(&self) -> String {
+        String::from("stubborn")
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        obs.legal_actions[0]
+    }
+    fn choose_column(&mut self, _board: &PlayerBoard, _row_idx: usize, columns: &[usize]) -> usize {
+        self.insist = !self.insist;
+        match self.insist {
+            true => NUM_COLORS_AS_USIZE,
+            false => *columns.last().unwrap(),
+        }
+    }
+    fn on_invalid_column(&mut self, col_idx: usize, error: RuleError) {
+        self.refused.send((col_idx, error)).unwrap();
+    }
+}
+#[test]
+fn test_gray_game_checks_the_columns() {
+    let path = std::env::temp_dir().join(format!("azool_gray_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (refused, refused_rx) = mpsc::channel();
+    let stubborn = StubbornAgent {
+        insist: false,
+        refused,
+    };
+    let options = GameOptions {
+        log_path: Some(path.clone()),
+        variant: Variant::Gray,
+        ..Default::default()
+    };
+    run_game(
+        vec![Box::new(stubborn), Box::new(RandomAgent::new())],
+        3,
+        &options,
+    );
+    let refusals: Vec<(usize, RuleError)> = refused_rx.try_iter().collect();
+    assert!(!refusals.is_empty());
+    assert!(refusals
+        .iter()
+        .all(|refusal| *refusal == (5, RuleError::BadColumn)));
+    // the log keeps track of which column everything went in
+    let record = GameRecord::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let last = record.positions.last().unwrap();
+    assert!(last
+        .players
+        .iter()
+        .all(|board| board.variant() == Variant::Gray));
+    assert!(last.players.iter().any(|board| board.has_full_row()));
+}
+#[test]
+fn test_table_turns_down_a_wall_that_doesnt_fit() {
+    let mut game_board = GameBoard::with_rng(2, StdRng::seed_from_u64(0));
+    game_board.player_boards[0] = Variant::Gray.board();
+    game_board.player_boards[0].rows[0] = (1, TileColor::RED);
+    let before = game_board.player_boards[0].clone();
+    // a gray wall line can go anywhere, so the table needs telling where
+    assert_eq!(
+        game_board.tile_wall(0, &Events::new()),
+        Err(RuleError::BadColumn)
+    );
+    assert_eq!(game_board.player_boards[0], before);
+    let tiled = |col_idx| Event::WallTiled {
+        player: 0,
+        row_idx: 0,
+        col_idx,
+        color: TileColor::RED,
+        points: 1,
+    };
+    game_board.player_boards[0].grid[[1, 3]] = true;
+    game_board.player_boards[0].wall_colors.as_mut().unwrap()[[1, 3]] = TileColor::RED;
+    let before = game_board.player_boards[0].clone();
+    assert_eq!(
+        game_board.tile_wall(0, &vec![tiled(3)]),
+        Err(RuleError::BadColumn)
+    );
+    assert_eq!(game_board.player_boards[0], before);
+    assert_eq!(game_board.tile_wall(0, &vec![tiled(2)]), Ok(false));
+    assert!(game_board.player_boards[0].grid[[0, 2]]);
+    assert_eq!(game_board.player_boards[0].rows[0].0, 0);
+}
+#[test]
+fn test_house_rules_reach_every_seat() {
+    let path = std::env::temp_dir().join(format!("azool_house_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let options = GameOptions {
+        log_path: Some(path.clone()),
+        rules: RuleSet {
+            floor_penalties: vec![0, 1],
+            row_bonus: 0,
+            column_bonus: 0,
+            color_bonus: 0,
+            ..RuleSet::default()
+        },
+        ..Default::default()
+    };
+    run_game(
+        vec![Box::new(RandomAgent::new()), Box::new(GreedyAgent::new())],
+        6,
+        &options,
+    );
+    let record = GameRecord::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut num_penalties = 0;
+    for event in record
+        .positions
+        .iter()
+        .flat_map(|position| position.events.iter())
+    {
+        match event {
+            Event::FloorPenalty { points, .. } => {
+                assert_eq!(*points, 1);
+                num_penalties += 1;
+            }
+            Event::FinalBonus { points, .. } => assert_eq!(*points, 0),
+            _ => (),
+        }
+    }
+    // a random player can't stay off the floor for a whole game
+    assert!(num_penalties > 0);
+}
+#[test]
+fn test_joker_game_over_the_table() {
+    let path = std::env::temp_dir().join(format!("azool_jokers_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let options = GameOptions {
+        log_path: Some(path.clone()),
+        rules: RuleSet::preset("jokers").unwrap(),
+        ..Default::default()
+    };
+    let agents: Vec<Box<dyn PlayerAgent>> = vec![
+        Box::new(GreedyAgent::new()),
+        Box::new(RandomAgent::with_rng(StdRng::seed_from_u64(9))),
+        Box::new(GreedyAgent::new()),
+    ];
+    run_game(agents, 9, &options);
+    let record = GameRecord::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let events: Vec<&Event> = record
+        .positions
+        .iter()
+        .flat_map(|position| position.events.iter())
+        .collect();
+    // the table took every joker the players put down, and the game still got to the end
+    assert!(events.iter().any(|event| matches!(
+        event,
+        Event::TilesPlaced {
+            color: TileColor::JOKER,
+            ..
+        }
+    )));
+    assert!(matches!(events.last(), Some(Event::GameEnded { .. })));
+}
+// an MCTS seat that keeps a copy of everything it was shown
+#[cfg(test)]
+struct WatchedAgent {
+    agent: MctsAgent,
+    seen: std::sync::Arc<std::sync::Mutex<Vec<Observation>>>,
 }
+#[cfg(test)]
+impl PlayerAgent for WatchedAgent {
+    fn name(&self) -> String {
+        self.agent.name()
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        self.seen.lock().unwrap().push(obs.clone());
+        self.agent.choose_action(obs)
+    }
+}
+#[test]
+fn test_threaded_game_shows_the_opponents() {
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let agents: Vec<Box<dyn PlayerAgent>> = vec![
+        Box::new(GreedyAgent::new()),
+        Box::new(WatchedAgent {
+            agent: MctsAgent::with_rng(SearchBudget::Iterations(10), StdRng::seed_from_u64(3)),
+            seen: seen.clone(),
+        }),
+        Box::new(GreedyAgent::new()),
+    ];
+    run_game(agents, 12, &GameOptions::default());
+    let seen = seen.lock().unwrap();
+    assert!(!seen.is_empty());
+    for obs in seen.iter() {
+        assert_eq!(obs.seat, 1);
+        assert_eq!(obs.opponents.len(), 2);
+    }
+    // by the last turn the others have walls, and the search has to see them
+    let last = seen.last().unwrap();
+    for opponent in last.opponents.iter() {
+        assert!(opponent.grid.iter().any(|x| *x), "{:?}", opponent);
+    }
+    // and the search puts them back in the seats they came from
+    let sampled = GameState::determinize(last, &mut StdRng::seed_from_u64(0));
+    assert_eq!(sampled.player_board(2), &last.opponents[0]);
+    assert_eq!(sampled.player_board(0), &last.opponents[1]);
+}
+// players report what they did under "events" so the table can keep the game log. returns
+// whether they took the first player marker, and those events
 fn give_turn(
-    sender: &mpsc::Sender<json::JsonValue>,
-    gameboard_receiver: &mpsc::Receiver<json::JsonValue>,
+    sender: &mpsc::Sender<TableMessage>,
+    gameboard_receiver: &mpsc::Receiver<TableRequest>,
     game_board: &mut GameBoard,
     player_num: u8,
-) -> bool {
-    sender.send(
-                object!{"req_type" : AzoolRequestType::ReqTypeBeginTurn.get_string(), "current_player" : player_num}
-                ).unwrap();
+) -> (bool, Events) {
+    let time_left = game_board
+        .clocks
+        .as_mut()
+        .and_then(|clocks| clocks.start((player_num - 1) as usize));
+    sender
+        .send(TableMessage::TakeTurn {
+            current_player: player_num,
+            time_left_ms: time_left.map(|left| left.as_millis() as u64),
+        })
+        .unwrap();
     loop {
         match gameboard_receiver.recv() {
-            Ok(val) => {
-                if AzoolRequestType::from_string(val["req_type"].as_str().unwrap())
-                    == AzoolRequestType::ReqTypeTurnFinished
-                {
-                    return val["pool_penalty"].as_bool().unwrap();
+            Ok(TableRequest::TurnFinished {
+                pool_penalty,
+                events,
+                ..
+            }) => {
+                if let Some(clocks) = game_board.clocks.as_mut() {
+                    clocks.stop();
                 }
+                return (pool_penalty, events);
+            }
+            Ok(val) => {
                 if let Some(response) = game_board.process_msg(val) {
                     sender.send(response).unwrap();
                 }
@@ -1085,129 +1596,409 @@ fn give_turn(
     } // end loop
 } // fn give_turn
 fn spawn_player(mut player: Player) -> thread::JoinHandle<()> {
-    let player_handle = thread::spawn(move || {
+    thread::spawn(move || {
         let mut game_over = false;
         while !game_over {
             let msg = player.my_rx_from_gb.try_recv();
             match msg {
-                Ok(val) => {
-                    let req_type = AzoolRequestType::from_string(val["req_type"].as_str().unwrap());
-                    if val["current_player"] == player.my_player_id
-                        && req_type == AzoolRequestType::ReqTypeBeginTurn
-                    {
-                        player.take_turn();
-                    } else if req_type == AzoolRequestType::ReqTypeEndOfRound {
-                        let end_game = player.end_round_and_return_full_row();
-                        player.my_tx_to_gb.send(object!{"req_type":AzoolRequestType::ReqTypeFullRow.get_string(), "end_of_game":end_game, "current_player":player.my_player_id}).unwrap();
-                    } else if req_type == AzoolRequestType::ReqTypeEndOfGame {
-                        player.end_game();
-                        player.my_tx_to_gb.send(object!{"req_type":AzoolRequestType::ReqTypeEndOfGame.get_string(), "current_player": player.my_player_id, "final_score" : player.my_score}).unwrap();
-                        game_over = true;
-                    }
+                Ok(TableMessage::TakeTurn {
+                    current_player,
+                    time_left_ms,
+                }) if current_player == player.my_player_id => {
+                    player.take_turn(time_left_ms.map(Duration::from_millis));
+                }
+                Ok(TableMessage::EndOfRound { .. }) => {
+                    let end_game = player.end_round_and_return_full_row();
+                    player.my_agent.on_round_end(&player.my_board());
+                    let events = player.take_events();
+                    player
+                        .my_tx_to_gb
+                        .send(TableRequest::FullRow {
+                            current_player: player.my_player_id,
+                            end_of_game: end_game,
+                            events,
+                        })
+                        .unwrap();
+                }
+                Ok(TableMessage::EndOfGame { .. }) => {
+                    player.end_game();
+                    player.my_agent.on_game_end(player.my_score);
+                    let events = player.take_events();
+                    player
+                        .my_tx_to_gb
+                        .send(TableRequest::EndOfGame {
+                            current_player: player.my_player_id,
+                            final_score: player.my_score,
+                            events,
+                        })
+                        .unwrap();
+                    game_over = true;
+                }
+                Ok(TableMessage::GetPlayerState { .. }) => {
+                    player
+                        .my_tx_to_gb
+                        .send(TableRequest::GetPlayerState {
+                            current_player: player.my_player_id,
+                            board: player.my_board(),
+                        })
+                        .unwrap();
                 }
-                Err(_error) => continue,
+                Ok(_) | Err(_) => continue,
             }
         }
-    });
-    player_handle
+    })
 } // fn spawn_player
-pub fn run_game() {
-    let mut game_board = GameBoard::new();
-    let (gameboard_to_player1_sender, player1_receiver) = mpsc::channel();
-    let (gameboard_to_player2_sender, player2_receiver) = mpsc::channel();
-    let (player1_to_gameboard_sender, gameboard_receiver) = mpsc::channel();
-    let player2_to_gameboard_sender = player1_to_gameboard_sender.clone();
-    let player_1 = Player::new(1, player1_to_gameboard_sender, player1_receiver);
-    let player_2 = Player::new(2, player2_to_gameboard_sender, player2_receiver);
-    // TODO - implement for >2 players
-    let p1_handle = spawn_player(player_1);
-    let p2_handle = spawn_player(player_2);
+
+// asks every player for its board so the game can be written out between turns
+fn collect_player_boards(
+    players: &[(mpsc::Sender<TableMessage>, u8)],
+    gameboard_receiver: &mpsc::Receiver<TableRequest>,
+) -> Vec<PlayerBoard> {
+    let mut boards = vec![PlayerBoard::new(); players.len()];
+    for (sender, id) in players.iter() {
+        sender
+            .send(TableMessage::GetPlayerState {
+                current_player: *id,
+            })
+            .unwrap();
+        match gameboard_receiver.recv().unwrap() {
+            TableRequest::GetPlayerState { board, .. } => boards[(*id - 1) as usize] = board,
+            val => panic!("expected player state, got {:?}", val),
+        }
+    }
+    boards
+} // fn collect_player_boards
+
+// everything about a threaded game besides who's playing. anything left as None is skipped
+#[derive(Clone, Debug, Default)]
+pub struct GameOptions {
+    pub save_path: Option<PathBuf>, // overwritten after every turn, removed when the game ends
+    pub log_path: Option<PathBuf>,  // every move, appended as it happens - see GameRecord
+    pub spectators: Option<Spectators>, // shown the whole table after every turn
+    pub sessions: Option<Sessions>, // how dropped remote and browser players get back in
+    pub reconnect: ReconnectOptions, // and this is how long they've got
+    pub time_control: Option<TimeControl>, // clocks, and what gets played when one runs out
+    pub variant: Variant,           // which wall a new game is played on; a saved one keeps its own
+    pub rules: RuleSet,             // same for the house rules
+}
+pub fn run_game(agents: Vec<Box<dyn PlayerAgent>>, seed: u64, options: &GameOptions) {
+    host_game(
+        agents.into_iter().map(Seat::Local).collect(),
+        seed,
+        None,
+        options,
+    );
+}
+// agents[ii] takes over player ii + 1 from the save
+pub fn resume_game(agents: Vec<Box<dyn PlayerAgent>>, saved: SavedGame, options: &GameOptions) {
+    assert_eq!(
+        agents.len(),
+        saved.seats.len(),
+        "save file has {} players",
+        saved.seats.len()
+    );
+    let seed = saved.seed;
+    host_game(
+        agents.into_iter().map(Seat::Local).collect(),
+        seed,
+        Some(saved),
+        options,
+    );
+}
+// seats[ii] is player ii + 1. a saved game picks up where it left off
+pub fn host_game(seats: Vec<Seat>, seed: u64, saved: Option<SavedGame>, options: &GameOptions) {
+    let num_players = seats.len();
+    assert!(
+        (MIN_NUM_PLAYERS..=MAX_NUM_PLAYERS).contains(&num_players),
+        "azool needs {}-{} players, got {}",
+        MIN_NUM_PLAYERS,
+        MAX_NUM_PLAYERS,
+        num_players
+    );
+    // hang on to this - it's the only way to replay a game that went wrong
+    println!("game seed: {}", seed);
+    let seat_names: Vec<String> = seats.iter().map(|seat| seat.name()).collect();
+    let mut num_turns: u32 = saved.as_ref().map_or(0, |saved| saved.num_turns);
+    let rules = saved
+        .as_ref()
+        .map_or(&options.rules, |saved| &saved.rules)
+        .clone();
+    let mut game_board = GameBoard::with_rules(
+        num_players as i32,
+        StdRng::seed_from_u64(seed),
+        rules.clone(),
+    );
+    let (player_to_gameboard_sender, gameboard_receiver) = mpsc::channel();
+    let mut players: Vec<(mpsc::Sender<TableMessage>, u8)> = Vec::new();
+    let mut player_handles: Vec<thread::JoinHandle<()>> = Vec::new();
+    let sessions = options.sessions.clone().unwrap_or_default();
+    for (ii, seat) in (1..).zip(seats) {
+        let (gameboard_to_player_sender, player_receiver) = mpsc::channel();
+        let saved_board = saved
+            .as_ref()
+            .map(|saved| &saved.players[(ii - 1) as usize]);
+        let agent: Box<dyn PlayerAgent> = match seat {
+            Seat::Local(agent) => agent,
+            Seat::Remote(reader) => Box::new(RemoteAgent::new(
+                reader,
+                ii,
+                num_players,
+                saved_board,
+                &sessions,
+                &options.reconnect,
+            )),
+            Seat::Web(socket) => Box::new(RemoteAgent::new(
+                *socket,
+                ii,
+                num_players,
+                saved_board,
+                &sessions,
+                &options.reconnect,
+            )),
+        };
+        let agent: Box<dyn PlayerAgent> = match &options.time_control {
+            Some(control) => Box::new(ClockedAgent::new(agent, &control.on_timeout)),
+            None => agent,
+        };
+        let mut player = Player::new(
+            ii,
+            player_to_gameboard_sender.clone(),
+            player_receiver,
+            agent,
+            rules.clone(),
+        );
+        player.restore(saved_board.unwrap_or(&options.variant.board()));
+        player_handles.push(spawn_player(player));
+        players.push((gameboard_to_player_sender, ii));
+    }
     let mut end_game: bool = false;
-    let mut players = vec![
-        (&gameboard_to_player1_sender, 1),
-        (&gameboard_to_player2_sender, 2),
-    ];
     let mut first_player = 1;
-    let mut end_round: bool;
-    let mut reverse_players: bool;
-    let mut penalty_taken: bool;
+    let mut penalty_taken: bool = false;
+    let mut round_in_progress = false;
+    let mut turn_idx: usize = 0;
+    let mut round: u32 = 0;
+    game_board.player_boards = vec![options.variant.board(); num_players];
+    let mut start = Position {
+        players: game_board.player_boards.clone(),
+        ..Position::new(num_players)
+    };
+    // clocks aren't saved, so a resumed game starts them all over
+    game_board.clocks = options
+        .time_control
+        .as_ref()
+        .map(|control| Clocks::new(control, num_players));
+    if let Some(saved) = &saved {
+        game_board
+            .restore(&saved.board)
+            .expect("save file was checked when it was loaded");
+        game_board.player_boards = saved.players.clone();
+        players.sort_by_key(|(_, id)| saved.turn_order.iter().position(|x| x == id));
+        first_player = saved.first_player;
+        penalty_taken = saved.penalty_taken;
+        round_in_progress = saved.round_in_progress;
+        turn_idx = saved.next_turn_idx;
+        round = saved.round;
+        start = Position {
+            round,
+            factories: game_board.tile_factories.clone(),
+            pool: game_board.tile_pool.clone(),
+            first_player_marker_in_pool: game_board.white_tile_in_pool,
+            players: saved.players.clone(),
+            events: Events::new(),
+        };
+    }
+    // a game that can't keep its log still gets played
+    let mut game_log = options.log_path.as_ref().and_then(|path| {
+        GameLog::open(path, seed, &seat_names, &start)
+            .map_err(|error| println!("ERROR: {}", error))
+            .ok()
+    });
+    let mut log = |events: &Events| {
+        if let Some(game_log) = game_log.as_mut() {
+            if let Err(error) = game_log.append(events) {
+                println!("ERROR: {}", error);
+            }
+        }
+    };
+    let save = |game_board: &GameBoard,
+                players: &[(mpsc::Sender<TableMessage>, u8)],
+                num_turns: u32,
+                round: u32,
+                next_turn_idx: usize,
+                round_in_progress: bool,
+                first_player: u8,
+                penalty_taken: bool| {
+        if let Some(path) = &options.save_path {
+            let saved = SavedGame {
+                seed,
+                seats: seat_names.clone(),
+                num_turns,
+                round,
+                board: game_board.snapshot(),
+                players: collect_player_boards(players, &gameboard_receiver),
+                turn_order: players.iter().map(|(_, id)| *id).collect(),
+                next_turn_idx,
+                round_in_progress,
+                first_player,
+                penalty_taken,
+                rules: game_board.rules.clone(),
+            };
+            if let Err(error) = saved.save(path) {
+                println!("ERROR: {}", error);
+            }
+        }
+    };
+    // spectators get the table after every change, along with what changed
+    let show = |game_board: &GameBoard, boards: &[PlayerBoard], round: u32, events: &Events| {
+        if let Some(spectators) = &options.spectators {
+            spectators.broadcast(TableView {
+                players: Some(boards.to_vec()),
+                round: Some(round),
+                events: Some(events.clone()),
+                time_left_ms: game_board.time_left_ms(),
+                ..game_board.view()
+            });
+        }
+    };
+    let watched = options.spectators.is_some();
     while !end_game {
-        game_board.start_round();
-        end_round = false;
-        reverse_players = false;
-        penalty_taken = false;
-        while !end_round {
-            for (sender, player_id) in players.iter() {
-                if !game_board.end_of_round() {
-                    let player_took_penalty =
-                        give_turn(sender, &gameboard_receiver, &mut game_board, *player_id);
-                    if !penalty_taken && player_took_penalty {
-                        penalty_taken = true;
-                        if first_player != *player_id {
-                            reverse_players = true;
-                            first_player = *player_id;
-                        }
-                    }
-                } else {
-                    end_round = true;
-                    break;
-                }
-            } // iter over players
-        } // !end_round
-        if reverse_players {
-            // different person took penalty this time, so swap the order
-            players.reverse();
+        if !round_in_progress {
+            game_board.start_round();
+            penalty_taken = false;
+            turn_idx = 0;
+            round += 1;
+            log(&vec![Event::RoundStarted {
+                round,
+                first_player: (players[0].1 - 1) as usize,
+                factories: game_board.tile_factories.clone(),
+            }]);
         }
+        round_in_progress = false;
+        while !game_board.end_of_round() {
+            let (sender, player_id) = &players[turn_idx];
+            let (pool_penalty, events) =
+                give_turn(sender, &gameboard_receiver, &mut game_board, *player_id);
+            log(&events);
+            if watched {
+                let boards = collect_player_boards(&players, &gameboard_receiver);
+                show(&game_board, &boards, round, &events);
+            }
+            if !penalty_taken && pool_penalty {
+                penalty_taken = true;
+                first_player = *player_id;
+            }
+            turn_idx = (turn_idx + 1) % players.len();
+            num_turns += 1;
+            save(
+                &game_board,
+                &players,
+                num_turns,
+                round,
+                turn_idx,
+                true,
+                first_player,
+                penalty_taken,
+            );
+        } // !end_of_round
+
+        // whoever took the penalty tile goes first next round; everyone else keeps their seat order
+        let first_idx = players
+            .iter()
+            .position(|(_, id)| *id == first_player)
+            .unwrap();
+        players.rotate_left(first_idx);
+        let mut round_events = Events::new();
         for (sender, id) in players.iter() {
             sender
-                .send(object! {"req_type": AzoolRequestType::ReqTypeEndOfRound.get_string(), "current_player":*id})
+                .send(TableMessage::EndOfRound {
+                    current_player: *id,
+                })
                 .unwrap();
-            match gameboard_receiver.recv() {
-                Ok(val) => {
-                    // println!("Type: {}", val["req_type"]);
-                    // TODO is this redundant?
-                    if AzoolRequestType::from_string(val["req_type"].as_str().unwrap())
-                        == AzoolRequestType::ReqTypeFullRow
-                    {
-                        end_game |= val["end_of_game"].as_bool().unwrap();
-                    } else {
+            // the player hands back its leftover tiles before reporting FULL_ROW, and those
+            // need to land in the lid before the next deal
+            'rx_loop: loop {
+                match gameboard_receiver.recv() {
+                    Ok(TableRequest::FullRow { mut events, .. }) => {
+                        // the table's copy of the wall says whether the game's over, not the
+                        // player's
+                        let seat = (*id - 1) as usize;
+                        let full_row = match game_board.tile_wall(seat, &events) {
+                            Ok(full_row) => full_row,
+                            Err(error) => {
+                                println!("ERROR: player {}'s wall doesn't add up: {}", id, error);
+                                sender
+                                    .send(TableMessage::Invalid {
+                                        success: false,
+                                        error: error.to_string(),
+                                    })
+                                    .unwrap();
+                                // the two copies have to agree for the table to check anything
+                                // from here on, so this once it takes the Player's word for it
+                                let board =
+                                    &collect_player_boards(&players, &gameboard_receiver)[seat];
+                                game_board.player_boards[seat] = board.clone();
+                                board.has_full_row()
+                            }
+                        };
+                        end_game |= full_row;
+                        round_events.append(&mut events);
+                        break 'rx_loop;
+                    }
+                    Ok(val) => {
                         if let Some(response) = game_board.process_msg(val) {
                             sender.send(response).unwrap();
                         }
                     }
+                    Err(error) => println!("got error: {}", error),
                 }
-                Err(error) => println!("got error: {}", error),
-            }
+            } // 'rx_loop
+        }
+        round_events.push(Event::RoundEnded { round });
+        log(&round_events);
+        if watched {
+            let boards = collect_player_boards(&players, &gameboard_receiver);
+            show(&game_board, &boards, round, &round_events);
+        }
+        if !end_game {
+            save(
+                &game_board,
+                &players,
+                num_turns,
+                round,
+                0,
+                false,
+                first_player,
+                false,
+            );
         }
-        // NEED TO CHECK FOR END OF GAME
     } // !end_game
-    let mut max_score = 0;
-    let mut winner = 0;
+    let mut final_events = Events::new();
+    let mut final_scores = vec![0; num_players];
+    // the player threads are gone once they've sent their final score, so ask for boards first.
+    // the end of game bonuses don't touch the wall, so these are good for the standings too
+    let mut final_boards = collect_player_boards(&players, &gameboard_receiver);
     for (sender, id) in players.iter() {
-        sender.send(object!{"req_type" : AzoolRequestType::ReqTypeEndOfGame.get_string(), "current_player" : *id}).unwrap();
+        sender
+            .send(TableMessage::EndOfGame {
+                current_player: *id,
+            })
+            .unwrap();
         'rx_loop: loop {
             // need to catch any residual messages sent to game board
             match gameboard_receiver.recv() {
+                Ok(TableRequest::EndOfGame {
+                    current_player,
+                    final_score,
+                    mut events,
+                }) => {
+                    println!("PLAYER {} SCORE: {}", current_player, final_score);
+                    final_events.append(&mut events);
+                    final_scores[(current_player - 1) as usize] = final_score;
+                    break 'rx_loop;
+                }
                 Ok(val) => {
-                    // println!("Type: {}", val["req_type"]);
-                    // TODO is this redundant?
-                    if AzoolRequestType::from_string(val["req_type"].as_str().unwrap())
-                        == AzoolRequestType::ReqTypeEndOfGame
-                    {
-                        println!(
-                            "PLAYER {} SCORE: {}",
-                            val["current_player"], val["final_score"]
-                        );
-                        if val["final_score"].as_i32().unwrap() > max_score {
-                            max_score = val["final_score"].as_i32().unwrap();
-                            winner = val["current_player"].as_u8().unwrap();
-                            // TODO -handle tie
-                        }
-                        break 'rx_loop;
-                    } else {
-                        if let Some(response) = game_board.process_msg(val) {
-                            sender.send(response).unwrap();
-                        }
+                    if let Some(response) = game_board.process_msg(val) {
+                        sender.send(response).unwrap();
                     }
                 }
                 Err(error) => println!("got error: {}", error),
@@ -1216,8 +2007,24 @@ pub fn run_game() {
     } // iter over players
       // TODO _-- pretty sure gameboard isn't recieving all the messages it should
       // needs to listen and hear ALL of the messages
-    println!("winner is player {} with score: {}", winner, max_score);
-    p1_handle.join().unwrap();
-    p2_handle.join().unwrap();
+    for (board, score) in final_boards.iter_mut().zip(final_scores.iter()) {
+        board.score = *score;
+    }
+    final_events.push(Event::GameEnded {
+        scores: final_scores,
+    });
+    log(&final_events);
+    if let Some(spectators) = &options.spectators {
+        show(&game_board, &final_boards, round, &final_events);
+        spectators.finish();
+    }
+    println!("{}", Standings::new(&final_boards, &rules, &seat_names));
+    if let Some(path) = &options.save_path {
+        // nothing left to resume
+        let _ = std::fs::remove_file(path);
+    }
+    for handle in player_handles {
+        handle.join().unwrap();
+    }
     // finalize scores, print results
-} // fn run_game
+} // fn host_game
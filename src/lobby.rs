@@ -0,0 +1,599 @@
+use crate::net::{greet, say_hello, send_line, take_seat};
+use crate::protocol::{self, LobbyRequest, Reply, TableMessage};
+use crate::spectate::draw_table;
+use crate::{
+    host_game, AzoolRequestType, GameOptions, PlayerAgent, ReconnectOptions, Seat, Sessions,
+    Spectators, TimeControl, Variant,
+};
+use crate::{MAX_NUM_PLAYERS, MIN_NUM_PLAYERS};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// every kind of game a table can be set up for, by Variant::name
+pub const VARIANTS: &[&str] = &["standard", "gray"];
+
+// how often somebody waiting at a table checks whether it filled up
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// what LIST_TABLES says about a table
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableInfo {
+    pub table_id: u32,
+    pub num_players: usize,
+    pub variant: String,
+    pub num_seated: usize,
+    pub in_progress: bool,
+}
+impl TableInfo {
+    pub fn to_json(&self) -> json::JsonValue {
+        protocol::to_json(self)
+    }
+    pub fn from_json(val: &json::JsonValue) -> Result<Self, String> {
+        protocol::from_json(val)
+    }
+} // impl TableInfo
+impl fmt::Display for TableInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "table {}: {} {}/{}{}",
+            self.table_id,
+            self.variant,
+            self.num_seated,
+            self.num_players,
+            if self.in_progress { " (playing)" } else { "" }
+        )
+    }
+}
+
+// one seat's connection on its way from the lobby to the game
+type Handoff = (usize, BufReader<TcpStream>);
+
+#[derive(Debug)]
+struct Table {
+    num_players: usize,
+    variant: String,
+    seated: Vec<u32>, // client ids, in seat order
+    // set once the table fills. everybody sitting there passes their connection along it
+    handoff: Option<mpsc::Sender<Handoff>>,
+    spectators: Spectators,
+}
+
+#[derive(Debug, Default)]
+struct Lobby {
+    next_table_id: u32,
+    next_client_id: u32,
+    tables: BTreeMap<u32, Table>,
+    sessions: Sessions, // every table's, so dropped players can come back through the lobby
+    reconnect: ReconnectOptions,
+    time_control: Option<TimeControl>, // the same clocks at every table
+}
+
+impl Lobby {
+    fn info(&self, table_id: u32) -> Option<TableInfo> {
+        self.tables.get(&table_id).map(|table| TableInfo {
+            table_id,
+            num_players: table.num_players,
+            variant: table.variant.clone(),
+            num_seated: table.seated.len(),
+            in_progress: table.handoff.is_some(),
+        })
+    }
+    fn list(&self) -> Vec<TableInfo> {
+        self.tables
+            .keys()
+            .filter_map(|table_id| self.info(*table_id))
+            .collect()
+    }
+    fn create(&mut self, num_players: usize, variant: &str) -> Result<u32, String> {
+        if !(MIN_NUM_PLAYERS..=MAX_NUM_PLAYERS).contains(&num_players) {
+            return Err(format!(
+                "azool needs {}-{} players, not {}",
+                MIN_NUM_PLAYERS, MAX_NUM_PLAYERS, num_players
+            ));
+        }
+        if !VARIANTS.contains(&variant) {
+            return Err(format!(
+                "unknown variant {}, try one of {}",
+                variant,
+                VARIANTS.join(", ")
+            ));
+        }
+        self.next_table_id += 1;
+        self.tables.insert(
+            self.next_table_id,
+            Table {
+                num_players,
+                variant: String::from(variant),
+                seated: Vec::new(),
+                handoff: None,
+                spectators: Spectators::new(),
+            },
+        );
+        Ok(self.next_table_id)
+    }
+    // returns the other end of the handoff if that was the last seat
+    fn join(
+        &mut self,
+        table_id: u32,
+        client_id: u32,
+    ) -> Result<Option<mpsc::Receiver<Handoff>>, String> {
+        let table = self
+            .tables
+            .get_mut(&table_id)
+            .ok_or(format!("there's no table {}", table_id))?;
+        if table.handoff.is_some() || table.seated.len() == table.num_players {
+            return Err(format!("table {} is full", table_id));
+        }
+        table.seated.push(client_id);
+        if table.seated.len() < table.num_players {
+            return Ok(None);
+        }
+        let (tx, rx) = mpsc::channel();
+        table.handoff = Some(tx);
+        Ok(Some(rx))
+    } // fn join
+    fn leave(&mut self, table_id: u32, client_id: u32) -> Result<(), String> {
+        let table = self
+            .tables
+            .get_mut(&table_id)
+            .ok_or(format!("there's no table {}", table_id))?;
+        if table.handoff.is_some() {
+            return Err(String::from("the game has already started"));
+        }
+        table.seated.retain(|id| *id != client_id);
+        // nobody's coming back to an empty table
+        if table.seated.is_empty() {
+            self.tables.remove(&table_id);
+        }
+        Ok(())
+    }
+    fn spectators(&self, table_id: u32) -> Result<Spectators, String> {
+        self.tables
+            .get(&table_id)
+            .map(|table| table.spectators.clone())
+            .ok_or(format!("there's no table {}", table_id))
+    }
+    // where to send our connection, once the table we're sitting at has started
+    fn handoff(&self, table_id: u32, client_id: u32) -> Option<(usize, mpsc::Sender<Handoff>)> {
+        let table = self.tables.get(&table_id)?;
+        let seat_idx = table.seated.iter().position(|id| *id == client_id)?;
+        Some((seat_idx, table.handoff.clone()?))
+    }
+} // impl Lobby
+
+fn reply(req_type: AzoolRequestType, result: Result<Reply, String>) -> Reply {
+    match result {
+        Ok(response) => Reply {
+            req_type,
+            ..response
+        },
+        Err(error) => Reply::refuse(req_type, &error),
+    }
+}
+
+// collects everybody's connection once a table fills up, plays the game and clears the table away
+fn spawn_table(lobby: Arc<Mutex<Lobby>>, table_id: u32, handoffs: mpsc::Receiver<Handoff>) {
+    thread::spawn(move || {
+        let (num_players, options) = {
+            let lobby = lobby.lock().unwrap();
+            let table = &lobby.tables[&table_id];
+            let options = GameOptions {
+                spectators: Some(table.spectators.clone()),
+                sessions: Some(lobby.sessions.clone()),
+                reconnect: lobby.reconnect.clone(),
+                time_control: lobby.time_control.clone(),
+                variant: Variant::from_name(&table.variant)
+                    .expect("tables only get known variants"),
+                ..Default::default()
+            };
+            (table.num_players, options)
+        };
+        let mut readers: Vec<Option<BufReader<TcpStream>>> =
+            (0..num_players).map(|_| None).collect();
+        for _ in 0..num_players {
+            let (seat_idx, reader) = handoffs.recv().expect("lobby dropped a table mid-handoff");
+            readers[seat_idx] = Some(reader);
+        }
+        let seats: Vec<Seat> = readers
+            .into_iter()
+            .map(|reader| Seat::Remote(reader.unwrap()))
+            .collect();
+        println!("table {} is starting", table_id);
+        host_game(seats, rand::random(), None, &options);
+        println!("table {} is done", table_id);
+        lobby.lock().unwrap().tables.remove(&table_id);
+    });
+} // fn spawn_table
+
+// answers one lobby request from `client_id`, who is sitting at `table_id` if anywhere.
+// fills in `watching` if they'd rather just watch a table
+fn process_request(
+    lobby: &Arc<Mutex<Lobby>>,
+    client_id: u32,
+    table_id: &mut Option<u32>,
+    watching: &mut Option<Spectators>,
+    msg: &LobbyRequest,
+) -> Reply {
+    let mut lobby_guard = lobby.lock().unwrap();
+    let ok = Reply::ok(AzoolRequestType::ReqTypeInvalid);
+    let (req_type, result) = match msg {
+        LobbyRequest::ListTables => (
+            AzoolRequestType::ReqTypeListTables,
+            Ok(Reply {
+                tables: Some(lobby_guard.list()),
+                ..ok
+            }),
+        ),
+        LobbyRequest::CreateTable { .. } | LobbyRequest::JoinTable { .. } => {
+            let req_type = match msg {
+                LobbyRequest::CreateTable { .. } => AzoolRequestType::ReqTypeCreateTable,
+                _ => AzoolRequestType::ReqTypeJoinTable,
+            };
+            let result = if let Some(table_id) = table_id {
+                Err(format!("you're already at table {}", table_id))
+            } else {
+                // whoever sets up a table sits down at it
+                let new_table_id = match msg {
+                    LobbyRequest::CreateTable {
+                        num_players,
+                        variant,
+                    } => lobby_guard.create(*num_players, variant),
+                    LobbyRequest::JoinTable { table_id } => Ok(*table_id),
+                    _ => unreachable!(),
+                };
+                new_table_id.and_then(|new_table_id| {
+                    let handoffs = lobby_guard.join(new_table_id, client_id)?;
+                    *table_id = Some(new_table_id);
+                    if let Some(handoffs) = handoffs {
+                        spawn_table(lobby.clone(), new_table_id, handoffs);
+                    }
+                    Ok(Reply {
+                        table: lobby_guard.info(new_table_id),
+                        ..ok
+                    })
+                })
+            };
+            (req_type, result)
+        }
+        LobbyRequest::LeaveTable => (
+            AzoolRequestType::ReqTypeLeaveTable,
+            match table_id {
+                Some(old_table_id) => lobby_guard.leave(*old_table_id, client_id).map(|_| {
+                    *table_id = None;
+                    ok
+                }),
+                None => Err(String::from("you aren't at a table")),
+            },
+        ),
+        LobbyRequest::WatchTable { table_id: watch_id } => (
+            AzoolRequestType::ReqTypeWatchTable,
+            match table_id {
+                Some(table_id) => Err(format!("you're playing at table {}", table_id)),
+                None => lobby_guard.spectators(*watch_id).map(|spectators| {
+                    *watching = Some(spectators);
+                    ok
+                }),
+            },
+        ),
+        // only gets here if they're sitting at a table, and can't be in two games at once
+        LobbyRequest::RejoinGame { .. } => (
+            AzoolRequestType::ReqTypeRejoinGame,
+            Err(String::from("leave your table first")),
+        ),
+    };
+    reply(req_type, result)
+} // fn process_request
+
+// talks to one connection until it sits down at a table that starts, or goes away
+fn handle_client(lobby: Arc<Mutex<Lobby>>, stream: TcpStream, client_id: u32) {
+    let mut write_stream = match stream.try_clone() {
+        Ok(write_stream) => write_stream,
+        Err(error) => {
+            println!("couldn't clone client socket: {}", error);
+            return;
+        }
+    };
+    let mut reader = BufReader::new(stream);
+    if let Err(error) = greet(&mut reader) {
+        println!("turned away client {}: {}", client_id, error);
+        return;
+    }
+    // don't sit in a read forever, or we'd never notice the table filling up
+    let _ = reader.get_ref().set_read_timeout(Some(POLL_INTERVAL));
+    let mut table_id: Option<u32> = None;
+    let mut watching: Option<Spectators> = None;
+    let mut line: Vec<u8> = Vec::new();
+    loop {
+        let handoff =
+            table_id.and_then(|table_id| lobby.lock().unwrap().handoff(table_id, client_id));
+        if let Some((seat_idx, handoff)) = handoff {
+            let _ = reader.get_ref().set_read_timeout(None);
+            let _ = handoff.send((seat_idx, reader));
+            return;
+        }
+        // read_until keeps whatever it got before a timeout, so a half-read line just carries on
+        match reader.read_until(b'\n', &mut line) {
+            Ok(_) if line.ends_with(b"\n") => {
+                let response = match protocol::parse(&String::from_utf8_lossy(&line)) {
+                    // back to a game that's already going, whose seat takes it from here
+                    Ok(LobbyRequest::RejoinGame { token }) if table_id.is_none() => {
+                        let sessions = lobby.lock().unwrap().sessions.clone();
+                        let _ = sessions.rejoin(&token, reader);
+                        return;
+                    }
+                    Ok(msg) => {
+                        process_request(&lobby, client_id, &mut table_id, &mut watching, &msg)
+                    }
+                    Err(error) => Reply::refuse(AzoolRequestType::ReqTypeInvalid, &error),
+                };
+                line.clear();
+                if send_line(&mut write_stream, &response).is_err() {
+                    break;
+                }
+                // spectators don't come back to the lobby
+                if let Some(spectators) = watching {
+                    let _ = reader.get_ref().set_read_timeout(None);
+                    spectators.add(reader);
+                    return;
+                }
+            }
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                continue
+            }
+            _ => break, // hung up
+        }
+    }
+    if let Some(table_id) = table_id {
+        let mut lobby = lobby.lock().unwrap();
+        match lobby.handoff(table_id, client_id) {
+            // the game will find out they're gone soon enough
+            Some((seat_idx, handoff)) => {
+                let _ = handoff.send((seat_idx, reader));
+            }
+            None => {
+                let _ = lobby.leave(table_id, client_id);
+            }
+        }
+    }
+} // fn handle_client
+
+// runs the lobby on `listener` until the process ends. every table plays its own game in its own
+// thread, and connections move from the lobby to their table's game once it fills up
+pub fn run_lobby(
+    listener: TcpListener,
+    reconnect: &ReconnectOptions,
+    time_control: Option<&TimeControl>,
+) {
+    let lobby = Arc::new(Mutex::new(Lobby {
+        reconnect: reconnect.clone(),
+        time_control: time_control.cloned(),
+        ..Default::default()
+    }));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                println!("lobby connection failed: {}", error);
+                continue;
+            }
+        };
+        let client_id = {
+            let mut lobby = lobby.lock().unwrap();
+            lobby.next_client_id += 1;
+            lobby.next_client_id
+        };
+        let lobby = lobby.clone();
+        thread::spawn(move || handle_client(lobby, stream, client_id));
+    }
+} // fn run_lobby
+
+// sends one request to a lobby and waits for the answer
+fn lobby_request(reader: &mut BufReader<TcpStream>, msg: &LobbyRequest) -> Result<Reply, String> {
+    send_line(reader.get_mut(), msg)
+        .map_err(|error| format!("couldn't reach the lobby: {}", error))?;
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|error| format!("couldn't hear from the lobby: {}", error))?;
+    let response: Reply = protocol::parse(&line)?;
+    if !response.success {
+        return Err(response
+            .error
+            .unwrap_or_else(|| String::from("the lobby said no")));
+    }
+    Ok(response)
+} // fn lobby_request
+
+pub fn list_tables(stream: TcpStream) -> Result<Vec<TableInfo>, String> {
+    let mut reader = BufReader::new(stream);
+    say_hello(&mut reader)?;
+    let response = lobby_request(&mut reader, &LobbyRequest::ListTables)?;
+    response
+        .tables
+        .ok_or_else(|| String::from("the lobby didn't list any tables"))
+}
+
+#[derive(Clone, Debug)]
+pub enum TableChoice {
+    Create { num_players: usize, variant: String },
+    Join(u32),
+}
+
+// sits down at a lobby table and plays its game once it fills up. returns our final score
+pub fn join_table(
+    stream: TcpStream,
+    choice: &TableChoice,
+    agent: Box<dyn PlayerAgent>,
+) -> Result<i32, String> {
+    let msg = match choice {
+        TableChoice::Create {
+            num_players,
+            variant,
+        } => LobbyRequest::CreateTable {
+            num_players: *num_players,
+            variant: variant.clone(),
+        },
+        TableChoice::Join(table_id) => LobbyRequest::JoinTable {
+            table_id: *table_id,
+        },
+    };
+    let mut reader = BufReader::new(stream);
+    let lobby = say_hello(&mut reader)?;
+    if let TableChoice::Create { variant, .. } = choice {
+        if !lobby.variants.contains(variant) {
+            return Err(format!("{} doesn't set up {} games", lobby.name, variant));
+        }
+    }
+    let table = lobby_request(&mut reader, &msg)?
+        .table
+        .ok_or("the lobby didn't say which table")?;
+    println!("sitting at {}", table);
+    // the reader may already hold our seat if we were the last one in
+    take_seat(reader, agent)
+} // fn join_table
+
+// follows a table's game, drawing the whole table after every turn until it's over
+pub fn watch_table(stream: TcpStream, table_id: u32) -> Result<(), String> {
+    let mut reader = BufReader::new(stream);
+    let lobby = say_hello(&mut reader)?;
+    if !lobby.supports("watch") {
+        return Err(format!("{} doesn't let anybody watch", lobby.name));
+    }
+    lobby_request(&mut reader, &LobbyRequest::WatchTable { table_id })?;
+    println!("watching table {}", table_id);
+    // the table hangs up once the game is over
+    for line in reader.lines() {
+        let line = line.map_err(|error| format!("lost the table: {}", error))?;
+        match protocol::parse(&line)? {
+            TableMessage::GetBoard(view) => println!("{}\n", draw_table(&view)),
+            msg => println!("ignoring {:?}", msg),
+        }
+    }
+    Ok(())
+} // fn watch_table
+
+#[cfg(test)]
+use crate::{GreedyAgent, RandomAgent};
+#[cfg(test)]
+use std::io::Write;
+
+#[cfg(test)]
+fn test_lobby() -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || run_lobby(listener, &ReconnectOptions::default(), None));
+    addr
+}
+#[cfg(test)]
+fn connect(addr: std::net::SocketAddr) -> BufReader<TcpStream> {
+    let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+    say_hello(&mut reader).unwrap();
+    reader
+}
+// the lobby catches up with hang-ups and finished games on its own time
+#[cfg(test)]
+fn wait_for_tables(addr: std::net::SocketAddr, num_tables: usize) -> Vec<TableInfo> {
+    for _ in 0..100 {
+        let tables = list_tables(TcpStream::connect(addr).unwrap()).unwrap();
+        if tables.len() == num_tables {
+            return tables;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    panic!("lobby never got to {} tables", num_tables);
+}
+#[test]
+fn test_lobby_create_join_leave() {
+    let addr = test_lobby();
+    let mut first = connect(addr);
+    let mut second = connect(addr);
+    let create = |num_players: usize, variant: &str| LobbyRequest::CreateTable {
+        num_players,
+        variant: String::from(variant),
+    };
+    assert!(lobby_request(&mut first, &create(5, "standard")).is_err());
+    assert!(lobby_request(&mut first, &create(2, "hexagonal")).is_err());
+    let response = lobby_request(&mut first, &create(3, "standard")).unwrap();
+    let table = response.table.unwrap();
+    assert_eq!(table.num_seated, 1);
+    assert!(lobby_request(&mut first, &create(2, "standard")).is_err());
+    assert!(lobby_request(&mut second, &LobbyRequest::JoinTable { table_id: 99 }).is_err());
+    lobby_request(
+        &mut second,
+        &LobbyRequest::JoinTable {
+            table_id: table.table_id,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        list_tables(TcpStream::connect(addr).unwrap()).unwrap()[0].num_seated,
+        2
+    );
+    lobby_request(&mut first, &LobbyRequest::LeaveTable).unwrap();
+    assert!(lobby_request(&mut first, &LobbyRequest::LeaveTable).is_err());
+    // hanging up counts as leaving, and the last one out takes the table with them
+    drop(second);
+    wait_for_tables(addr, 0);
+    assert!(lobby_request(
+        &mut first,
+        &LobbyRequest::WatchTable {
+            table_id: table.table_id
+        }
+    )
+    .is_err());
+    // and anything it can't make sense of gets an answer saying so
+    for line in [
+        r#"{"req_type": "GET_BOARD"}"#,
+        "{",
+        r#"{"req_type": "JOIN_TABLE"}"#,
+    ] {
+        writeln!(first.get_mut(), "{}", line).unwrap();
+        let mut reply = String::new();
+        first.read_line(&mut reply).unwrap();
+        let reply: Reply = protocol::parse(&reply).unwrap();
+        assert_eq!(reply.req_type, AzoolRequestType::ReqTypeInvalid);
+        assert!(!reply.success && reply.error.is_some());
+    }
+}
+#[test]
+fn test_lobby_runs_tables_side_by_side() {
+    let addr = test_lobby();
+    let mut players = Vec::new();
+    for _ in 0..2 {
+        let stream = TcpStream::connect(addr).unwrap();
+        let choice = TableChoice::Create {
+            num_players: 2,
+            variant: String::from("standard"),
+        };
+        players.push(thread::spawn(move || {
+            join_table(stream, &choice, Box::new(GreedyAgent::new()))
+        }));
+    }
+    let tables = wait_for_tables(addr, 2);
+    let stream = TcpStream::connect(addr).unwrap();
+    let table_id = tables[0].table_id;
+    let watcher = thread::spawn(move || watch_table(stream, table_id));
+    for table in tables {
+        let stream = TcpStream::connect(addr).unwrap();
+        players.push(thread::spawn(move || {
+            join_table(
+                stream,
+                &TableChoice::Join(table.table_id),
+                Box::new(RandomAgent::new()),
+            )
+        }));
+    }
+    for player in players {
+        assert!(player.join().unwrap().unwrap() >= 0);
+    }
+    watcher.join().unwrap().unwrap();
+    // finished games clear their tables away
+    wait_for_tables(addr, 0);
+}
@@ -1,3 +1,487 @@
+use azool_rust::{
+    GameOptions, GameRecord, ReconnectOptions, RuleSet, SavedGame, Seat, Sessions, TableChoice,
+    TimeControl, TimeoutAction, Variant, MAX_NUM_PLAYERS, MIN_NUM_PLAYERS,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+// a bare number means that many seats of `default_kind`
+fn parse_players(val: Option<&String>, default_kind: &str) -> Result<Vec<String>, String> {
+    let val = val.ok_or("--players needs a number [2-4] or a list like human,greedy")?;
+    Ok(seats_from(val, default_kind))
+}
+fn seats_from(val: &str, default_kind: &str) -> Vec<String> {
+    match val.parse::<usize>() {
+        Ok(num_players) => vec![String::from(default_kind); num_players],
+        Err(_) => val.split(',').map(String::from).collect(),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(val: Option<&String>, flag: &str) -> Result<T, String> {
+    val.and_then(|val| val.parse().ok())
+        .ok_or_else(|| format!("{} needs a number", flag))
+}
+
+fn parse_path(val: Option<&String>, flag: &str) -> Result<PathBuf, String> {
+    val.map(PathBuf::from)
+        .ok_or_else(|| format!("{} needs a file name", flag))
+}
+
+fn parse_grace(val: Option<&String>) -> Result<Duration, String> {
+    val.and_then(|val| val.parse().ok())
+        .map(Duration::from_secs)
+        .ok_or_else(|| String::from("--grace needs a number of seconds"))
+}
+
+// a bot to finish the game for a player who dropped out, or "forfeit"
+fn parse_stand_in(val: Option<&String>) -> Result<Option<String>, String> {
+    let val = val.ok_or("--stand-in needs a bot or \"forfeit\"")?;
+    if val == "forfeit" {
+        return Ok(None);
+    }
+    if azool_rust::agent_from_name(val, 0).is_none() || val == "human" {
+        return Err(format!(
+            "--stand-in needs a bot or \"forfeit\", not {}",
+            val
+        ));
+    }
+    Ok(Some(val.clone()))
+}
+
+fn parse_move_time(val: Option<&String>) -> Result<Duration, String> {
+    let val = val.ok_or("--move-time needs a length of time like 30s")?;
+    azool_rust::parse_duration(val).map_err(|error| format!("--move-time: {}", error))
+}
+
+// a game clock and increment, like 5m+2s
+fn parse_clock(val: Option<&String>) -> Result<(Duration, Duration), String> {
+    let val = val.ok_or("--clock needs a length of time like 5m, plus an optional increment")?;
+    azool_rust::parse_clock(val).map_err(|error| format!("--clock: {}", error))
+}
+
+// what to play for somebody whose time runs out: a bot's move, or "discard"
+fn parse_on_timeout(val: Option<&String>) -> Result<TimeoutAction, String> {
+    let val = val.ok_or("--on-timeout needs a bot or \"discard\"")?;
+    TimeoutAction::from_name(val)
+        .ok_or_else(|| format!("--on-timeout needs a bot or \"discard\", not {}", val))
+}
+
+fn parse_variant(val: Option<&String>) -> Result<Variant, String> {
+    variant_named(val.ok_or("--variant needs a name like gray")?)
+}
+fn variant_named(name: &str) -> Result<Variant, String> {
+    Variant::from_name(name).ok_or_else(|| {
+        format!(
+            "unknown variant {}, try one of {}",
+            name,
+            azool_rust::VARIANTS.join(", ")
+        )
+    })
+}
+
+// one of the presets, or a .toml or .json file of house rules
+fn parse_rules(val: Option<&String>) -> Result<RuleSet, String> {
+    RuleSet::find(val.ok_or("--rules needs a preset like gentle, or a rules file")?)
+}
+
+// bad input on the command line: say what's wrong and stop
+fn exit_with(error: impl std::fmt::Display) -> ! {
+    eprintln!("ERROR: {}", error);
+    std::process::exit(1);
+}
+
+// what `play` or `serve` was asked to run. anything left as None gets worked out once we know
+// whether there's a game to resume
+#[derive(Default)]
+struct PlayArgs {
+    seats: Option<Vec<String>>,
+    port: u16,
+    web_port: u16,
+    seed: Option<u64>,
+    save_path: Option<PathBuf>,
+    log_path: Option<PathBuf>,
+    resume_path: Option<PathBuf>,
+    reconnect: ReconnectOptions,
+    time_control: Option<TimeControl>,
+    variant: Variant,
+    rules: Option<RuleSet>,
+}
+fn parse_play_args(args: &[String], serve: bool) -> Result<PlayArgs, String> {
+    // each seat is "human", "random", "greedy", "mcts[:budget]" or, when serving, "remote" or "web".
+    // a bare number means that many humans, or remote players when serving
+    let default_kind = if serve { "remote" } else { "human" };
+    let mut play = PlayArgs {
+        port: azool_rust::DEFAULT_PORT,
+        web_port: azool_rust::DEFAULT_WEB_PORT,
+        ..Default::default()
+    };
+    let mut arg_iter = args.iter();
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "-n" | "--players" => play.seats = Some(parse_players(arg_iter.next(), default_kind)?),
+            "-v" | "--variant" => play.variant = parse_variant(arg_iter.next())?,
+            "--rules" => play.rules = Some(parse_rules(arg_iter.next())?),
+            "-s" | "--seed" => play.seed = Some(parse_number(arg_iter.next(), "--seed")?),
+            "--save" => play.save_path = Some(parse_path(arg_iter.next(), "--save")?),
+            "-l" | "--log" => play.log_path = Some(parse_path(arg_iter.next(), "--log")?),
+            "-r" | "--resume" => play.resume_path = Some(parse_path(arg_iter.next(), "--resume")?),
+            "--move-time" => {
+                let control = play.time_control.get_or_insert_with(TimeControl::default);
+                control.per_move = Some(parse_move_time(arg_iter.next())?);
+            }
+            "--clock" => {
+                let control = play.time_control.get_or_insert_with(TimeControl::default);
+                let (game, increment) = parse_clock(arg_iter.next())?;
+                control.game = Some(game);
+                control.increment = increment;
+            }
+            "--on-timeout" => {
+                let control = play.time_control.get_or_insert_with(TimeControl::default);
+                control.on_timeout = parse_on_timeout(arg_iter.next())?;
+            }
+            "-p" | "--port" if serve => play.port = parse_number(arg_iter.next(), "--port")?,
+            "--grace" if serve => play.reconnect.grace_period = parse_grace(arg_iter.next())?,
+            "--stand-in" if serve => play.reconnect.stand_in = parse_stand_in(arg_iter.next())?,
+            "-w" | "--web-port" if serve => {
+                play.web_port = parse_number(arg_iter.next(), "--web-port")?
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    Ok(play)
+} // fn parse_play_args
+
+// `serve` is `play` with a port, where "remote" seats wait for somebody to `join` and "web"
+// seats for somebody to open the page on the web port
+fn play(args: &[String], serve: bool) {
+    let default_kind = if serve { "remote" } else { "human" };
+    let PlayArgs {
+        mut seats,
+        port,
+        web_port,
+        mut seed,
+        mut save_path,
+        log_path,
+        resume_path,
+        reconnect,
+        time_control,
+        variant,
+        rules,
+    } = parse_play_args(args, serve).unwrap_or_else(|error| exit_with(error));
+    let saved = resume_path
+        .as_ref()
+        .map(|path| SavedGame::load(path).unwrap_or_else(|error| exit_with(error)));
+    if let Some(saved) = &saved {
+        if seed.is_some() {
+            exit_with("a resumed game keeps the seed it was started with");
+        }
+        if rules.is_some() {
+            exit_with("a resumed game keeps the rules it was started with");
+        }
+        seed = Some(saved.seed);
+        // the same seats by default, but someone can hand their seat to a bot if they had to leave
+        let seats = seats.get_or_insert_with(|| saved.seats.clone());
+        if seats.len() != saved.seats.len() {
+            exit_with(format!("save file has {} players", saved.seats.len()));
+        }
+        // keep saving over the file we resumed from unless told otherwise
+        save_path = save_path.or(resume_path);
+    }
+    let seats = seats.unwrap_or_else(|| vec![String::from(default_kind); 2]);
+    // before anything gets bound or waited on
+    if !(MIN_NUM_PLAYERS..=MAX_NUM_PLAYERS).contains(&seats.len()) {
+        exit_with(format!(
+            "azool needs {}-{} players, got {}",
+            MIN_NUM_PLAYERS,
+            MAX_NUM_PLAYERS,
+            seats.len()
+        ));
+    }
+    let seed = seed.unwrap_or_else(rand::random);
+    // the table and each bot get their own stream off the one seed
+    let mut rng = StdRng::seed_from_u64(seed);
+    let seeds: Vec<u64> = seats.iter().map(|_| rng.gen()).collect();
+    let sessions = Sessions::new();
+    let table = if serve {
+        let listener = TcpListener::bind(("0.0.0.0", port)).unwrap_or_else(|error| {
+            exit_with(format!("couldn't listen on port {}: {}", port, error))
+        });
+        // only put the page up if somebody is going to play in a browser
+        let web_sockets = seats.iter().any(|seat| seat == "web").then(|| {
+            let web_listener = TcpListener::bind(("0.0.0.0", web_port)).unwrap_or_else(|error| {
+                exit_with(format!("couldn't listen on port {}: {}", web_port, error))
+            });
+            println!("browser players can open http://localhost:{}/", web_port);
+            azool_rust::spawn_web_gateway(web_listener, sessions.clone())
+        });
+        let table = azool_rust::accept_seats(&listener, web_sockets.as_ref(), &seats, &seeds);
+        // anybody who drops out can come back on the same port
+        azool_rust::spawn_rejoin_listener(listener, sessions.clone());
+        table
+    } else {
+        seats
+            .iter()
+            .zip(seeds.iter())
+            .map(
+                |(seat, seed)| match azool_rust::agent_from_name(seat, *seed) {
+                    Some(agent) => Ok(Seat::Local(agent)),
+                    None if seat == "remote" || seat == "web" => {
+                        Err(format!("{} seats need `serve`", seat))
+                    }
+                    None => Err(format!("unknown player type: {}", seat)),
+                },
+            )
+            .collect()
+    };
+    let table = table.unwrap_or_else(|error| exit_with(error));
+    let options = GameOptions {
+        save_path,
+        log_path,
+        sessions: Some(sessions),
+        reconnect,
+        time_control,
+        variant,
+        rules: rules.unwrap_or_default(),
+        ..Default::default()
+    };
+    azool_rust::host_game(table, seed, saved, &options);
+}
+
+// takes a seat at somebody else's `serve`, or at a table in their `lobby`, or just watches one
+fn join(args: &[String]) {
+    let addr = args
+        .first()
+        .expect("join needs an address like localhost:7878");
+    let mut seat = String::from("human");
+    let mut list = false;
+    let mut table_id: Option<u32> = None;
+    let mut create: Option<usize> = None;
+    let mut watch: Option<u32> = None;
+    let mut rejoin: Option<String> = None;
+    let mut variant = String::from("standard");
+    let mut arg_iter = args[1..].iter();
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "-n" | "--player" => seat = arg_iter.next().expect("--player needs a type").clone(),
+            "--list" => list = true,
+            "-t" | "--table" => {
+                table_id = Some(
+                    arg_iter
+                        .next()
+                        .and_then(|val| val.parse().ok())
+                        .expect("--table needs a table number"),
+                );
+            }
+            "-c" | "--create" => {
+                create = Some(
+                    arg_iter
+                        .next()
+                        .and_then(|val| val.parse().ok())
+                        .expect("--create needs a number of players"),
+                );
+            }
+            "-w" | "--watch" => {
+                watch = Some(
+                    arg_iter
+                        .next()
+                        .and_then(|val| val.parse().ok())
+                        .expect("--watch needs a table number"),
+                );
+            }
+            "-r" | "--rejoin" => {
+                rejoin = Some(arg_iter.next().expect("--rejoin needs a token").clone())
+            }
+            "-v" | "--variant" => {
+                variant = arg_iter.next().expect("--variant needs a name").clone()
+            }
+            _ => panic!("unknown argument: {}", arg),
+        }
+    }
+    let table = match (table_id, create) {
+        (Some(_), Some(_)) => panic!("--table and --create don't go together"),
+        (Some(table_id), None) => Some(TableChoice::Join(table_id)),
+        (None, Some(num_players)) => Some(TableChoice::Create {
+            num_players,
+            variant,
+        }),
+        (None, None) => None,
+    };
+    let agent = azool_rust::agent_from_name(&seat, rand::random())
+        .unwrap_or_else(|| panic!("unknown player type: {}", seat));
+    // a bare hostname gets the default port
+    let addr = if addr.contains(':') {
+        addr.clone()
+    } else {
+        format!("{}:{}", addr, azool_rust::DEFAULT_PORT)
+    };
+    let stream = TcpStream::connect(&addr).unwrap_or_else(|error| {
+        eprintln!("ERROR: couldn't connect to {}: {}", addr, error);
+        std::process::exit(1);
+    });
+    if list {
+        match azool_rust::list_tables(stream) {
+            Ok(tables) if tables.is_empty() => println!("no tables yet, --create one"),
+            Ok(tables) => tables.iter().for_each(|table| println!("{}", table)),
+            Err(error) => {
+                eprintln!("ERROR: {}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(table_id) = watch {
+        if let Err(error) = azool_rust::watch_table(stream, table_id) {
+            eprintln!("ERROR: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let result = match (&table, &rejoin) {
+        (Some(table), _) => azool_rust::join_table(stream, table, agent),
+        (None, Some(token)) => azool_rust::rejoin_game(stream, token, agent),
+        (None, None) => azool_rust::join_game(stream, agent),
+    };
+    match result {
+        Ok(score) => println!("final score: {}", score),
+        Err(error) => {
+            eprintln!("ERROR: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+// hosts as many tables as people care to set up, until somebody kills it
+fn lobby(args: &[String]) {
+    let (port, reconnect, time_control) =
+        parse_lobby_args(args).unwrap_or_else(|error| exit_with(error));
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .unwrap_or_else(|error| exit_with(format!("couldn't listen on port {}: {}", port, error)));
+    println!("lobby open on port {}", port);
+    azool_rust::run_lobby(listener, &reconnect, time_control.as_ref());
+}
+// the port, and what every table gets for dropouts and clocks
+fn parse_lobby_args(
+    args: &[String],
+) -> Result<(u16, ReconnectOptions, Option<TimeControl>), String> {
+    let mut port: u16 = azool_rust::DEFAULT_PORT;
+    let mut reconnect = ReconnectOptions::default();
+    let mut time_control: Option<TimeControl> = None;
+    let mut arg_iter = args.iter();
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "--grace" => reconnect.grace_period = parse_grace(arg_iter.next())?,
+            "--stand-in" => reconnect.stand_in = parse_stand_in(arg_iter.next())?,
+            "--move-time" => {
+                let control = time_control.get_or_insert_with(TimeControl::default);
+                control.per_move = Some(parse_move_time(arg_iter.next())?);
+            }
+            "--clock" => {
+                let control = time_control.get_or_insert_with(TimeControl::default);
+                let (game, increment) = parse_clock(arg_iter.next())?;
+                control.game = Some(game);
+                control.increment = increment;
+            }
+            "--on-timeout" => {
+                let control = time_control.get_or_insert_with(TimeControl::default);
+                control.on_timeout = parse_on_timeout(arg_iter.next())?;
+            }
+            "-p" | "--port" => port = parse_number(arg_iter.next(), "--port")?,
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    Ok((port, reconnect, time_control))
+} // fn parse_lobby_args
+
+// what `simulate` was asked to run
+struct SimulationArgs {
+    seats: Vec<String>,
+    num_games: u32,
+    seed: u64,
+    variant: Variant,
+    rules: RuleSet,
+}
+// simulate runs unattended, so a bad argument is an error to report rather than a panic
+fn parse_simulation_args(args: &[String]) -> Result<SimulationArgs, String> {
+    let mut sim = SimulationArgs {
+        seats: vec![String::from("greedy"), String::from("random")],
+        num_games: 1000,
+        seed: rand::random(),
+        variant: Variant::default(),
+        rules: RuleSet::default(),
+    };
+    let mut arg_iter = args.iter();
+    while let Some(arg) = arg_iter.next() {
+        let mut value = |what: &str| {
+            arg_iter
+                .next()
+                .ok_or_else(|| format!("{} needs {}", arg, what))
+        };
+        let number = |val: &String| format!("{} needs a number, not {}", arg, val);
+        match arg.as_str() {
+            "-n" | "--players" => {
+                sim.seats = seats_from(value("a number [2-4] or a list like greedy,mcts")?, "human")
+            }
+            "-g" | "--games" => {
+                let val = value("a number")?;
+                sim.num_games = val.parse().map_err(|_| number(val))?;
+            }
+            "-s" | "--seed" => {
+                let val = value("a number")?;
+                sim.seed = val.parse().map_err(|_| number(val))?;
+            }
+            "-v" | "--variant" => sim.variant = variant_named(value("a name like gray")?)?,
+            "--rules" => {
+                sim.rules = RuleSet::find(value("a preset like gentle, or a rules file")?)?
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    Ok(sim)
+} // fn parse_simulation_args
+
+fn simulate(args: &[String]) {
+    let run = parse_simulation_args(args).and_then(|sim| {
+        let report = azool_rust::run_simulation(
+            &sim.seats,
+            sim.num_games,
+            sim.seed,
+            sim.variant,
+            &sim.rules,
+        )?;
+        Ok((sim.seed, report))
+    });
+    match run {
+        Ok((seed, report)) => {
+            println!("seed: {}", seed);
+            print!("{}", report);
+        }
+        Err(error) => {
+            eprintln!("ERROR: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn replay(args: &[String]) {
+    let path = parse_path(args.first(), "replay").unwrap_or_else(|error| exit_with(error));
+    match GameRecord::load(&path) {
+        Ok(record) => azool_rust::run_replay(&record),
+        Err(error) => exit_with(error),
+    }
+}
+
 fn main() {
-    azool_rust::run_game();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("simulate") => simulate(&args[1..]),
+        Some("play") => play(&args[1..], false),
+        Some("serve") => play(&args[1..], true),
+        Some("join") => join(&args[1..]),
+        Some("lobby") => lobby(&args[1..]),
+        Some("replay") => replay(&args[1..]),
+        _ => play(&args, false),
+    }
 }
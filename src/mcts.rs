@@ -0,0 +1,276 @@
+use crate::bots::evaluate_move;
+use crate::{finalize_score, Action, GameState, Observation, PlayerAgent};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+// how long the search gets to think about each move
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SearchBudget {
+    Iterations(u32),
+    Millis(u64),
+}
+
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+// rollouts stop this many rounds past the root and score the position as it stands
+const ROLLOUT_ROUNDS: u32 = 3;
+// chance a rollout move is picked at random instead of greedily
+const ROLLOUT_EPSILON: f64 = 0.1;
+// how big a lead in points gained has to be before the reward starts to flatten out
+const REWARD_SCALE: f64 = 20.0;
+
+#[derive(Debug)]
+struct Node {
+    action: Option<Action>,
+    player: usize, // who made `action`
+    children: Vec<usize>,
+    untried: Vec<Action>,
+    visits: u32,
+    total_reward: f64,
+}
+
+// UCT over the legal moves. the tree only covers the current round - everything inside a round is
+// public, so the tree is exact there. past the end of the round the next deal depends on the bag,
+// so each iteration samples a fresh bag and plays it out with a mostly-greedy rollout
+#[derive(Debug)]
+pub struct MctsAgent {
+    budget: SearchBudget,
+    rng: StdRng,
+}
+
+impl MctsAgent {
+    pub fn new(budget: SearchBudget) -> Self {
+        Self::with_rng(budget, StdRng::from_entropy())
+    }
+    pub fn with_rng(budget: SearchBudget, rng: StdRng) -> Self {
+        MctsAgent { budget, rng }
+    }
+    pub fn search(&mut self, obs: &Observation) -> Action {
+        if obs.legal_actions.len() == 1 {
+            return obs.legal_actions[0];
+        }
+        let mut tree: Vec<Node> = vec![Node {
+            action: None,
+            player: obs.seat,
+            children: Vec::new(),
+            untried: obs.legal_actions.clone(),
+            visits: 0,
+            total_reward: 0.0,
+        }];
+        let start = Instant::now();
+        let mut num_iterations: u32 = 0;
+        loop {
+            match self.budget {
+                SearchBudget::Iterations(max) => {
+                    if num_iterations >= max {
+                        break;
+                    }
+                }
+                SearchBudget::Millis(ms) => {
+                    if start.elapsed() >= Duration::from_millis(ms) {
+                        break;
+                    }
+                }
+            }
+            self.iterate(&mut tree, obs);
+            num_iterations += 1;
+        }
+        let best_child = tree[0]
+            .children
+            .iter()
+            .max_by_key(|child| tree[**child].visits)
+            .copied();
+        match best_child {
+            Some(child) => tree[child].action.unwrap(),
+            None => obs.legal_actions[0], // didn't get a single iteration in
+        }
+    } // fn search
+    fn iterate(&mut self, tree: &mut Vec<Node>, obs: &Observation) {
+        let mut state = GameState::determinize(obs, &mut self.rng);
+        let root_round = state.round();
+        let root_scores = state.scores();
+        let mut path = vec![0];
+        let mut node_idx = 0;
+        // selection
+        while tree[node_idx].untried.is_empty() && !tree[node_idx].children.is_empty() {
+            node_idx = self.select_child(tree, node_idx);
+            state.apply(tree[node_idx].action.unwrap()).unwrap();
+            path.push(node_idx);
+        }
+        // expansion
+        if !tree[node_idx].untried.is_empty() {
+            let untried = &mut tree[node_idx].untried;
+            let action = untried.swap_remove(self.rng.gen_range(0..untried.len()));
+            let player = state.current_player();
+            state.apply(action).unwrap();
+            // once the round is over the next deal is different every iteration, so stop growing
+            let untried = if state.is_terminal() || state.round() != root_round {
+                Vec::new()
+            } else {
+                state.legal_actions()
+            };
+            tree.push(Node {
+                action: Some(action),
+                player,
+                children: Vec::new(),
+                untried,
+                visits: 0,
+                total_reward: 0.0,
+            });
+            let child_idx = tree.len() - 1;
+            tree[node_idx].children.push(child_idx);
+            path.push(child_idx);
+        }
+        // simulation
+        let rewards = self.rollout(&mut state, root_round + ROLLOUT_ROUNDS, &root_scores);
+        // backpropagation
+        for idx in path {
+            tree[idx].visits += 1;
+            tree[idx].total_reward += rewards[tree[idx].player];
+        }
+    } // fn iterate
+    fn select_child(&self, tree: &[Node], node_idx: usize) -> usize {
+        let parent_visits = tree[node_idx].visits as f64;
+        *tree[node_idx]
+            .children
+            .iter()
+            .max_by(|a, b| {
+                let ucb = |idx: usize| {
+                    let child = &tree[idx];
+                    let visits = child.visits as f64;
+                    child.total_reward / visits + EXPLORATION * (parent_visits.ln() / visits).sqrt()
+                };
+                ucb(**a).total_cmp(&ucb(**b))
+            })
+            .unwrap()
+    } // fn select_child
+
+    // plays on from `state` and returns a reward in [0, 1] for every seat. rewards are based on
+    // points gained since the root rather than totals, so seats we know nothing about don't skew it
+    fn rollout(&mut self, state: &mut GameState, last_round: u32, root_scores: &[i32]) -> Vec<f64> {
+        while !state.is_terminal() && state.round() <= last_round {
+            let actions = state.legal_actions();
+            let action = if self.rng.gen_bool(ROLLOUT_EPSILON) {
+                *actions.choose(&mut self.rng).unwrap()
+            } else {
+                let board = state.player_board(state.current_player());
+                *actions
+                    .iter()
+                    .max_by_key(|action| {
+                        evaluate_move(
+                            state.factories(),
+                            state.pool(),
+                            state.first_player_marker_in_pool(),
+                            board,
+                            state.rules(),
+                            action,
+                        )
+                    })
+                    .unwrap()
+            };
+            state.apply(action).unwrap();
+        }
+        // games cut short still get credit for the end of game bonuses they've locked in
+        let gains: Vec<i32> = (0..state.num_players())
+            .map(|player| {
+                let board = state.player_board(player);
+                let score = if state.is_terminal() {
+                    board.score
+                } else {
+                    board.score + finalize_score(board, state.rules())
+                };
+                score - root_scores[player]
+            })
+            .collect();
+        gains
+            .iter()
+            .enumerate()
+            .map(|(player, gain)| {
+                let best_other = gains
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != player)
+                    .map(|(_, other_gain)| *other_gain)
+                    .max()
+                    .unwrap_or(0);
+                0.5 + 0.5 * ((gain - best_other) as f64 / REWARD_SCALE).tanh()
+            })
+            .collect()
+    } // fn rollout
+} // impl MctsAgent
+
+impl PlayerAgent for MctsAgent {
+    // spelled the way agent_from_name takes it, so a saved game gets the same budget back
+    fn name(&self) -> String {
+        match self.budget {
+            SearchBudget::Iterations(num) => format!("mcts:{}", num),
+            SearchBudget::Millis(ms) => format!("mcts:{}ms", ms),
+        }
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        self.search(obs)
+    }
+} // impl PlayerAgent for MctsAgent
+
+#[cfg(test)]
+use crate::{RuleSet, Variant};
+
+#[test]
+fn test_mcts_plays_legal_moves() {
+    let mut state = GameState::new(2, 17);
+    let mut agent = MctsAgent::new(SearchBudget::Iterations(50));
+    for _ in 0..6 {
+        let obs = Observation::from_state(&state);
+        let action = agent.choose_action(&obs);
+        assert!(obs.legal_actions.contains(&action));
+        state.apply(action).unwrap();
+    }
+}
+#[test]
+fn test_mcts_time_budget() {
+    let state = GameState::new(3, 2);
+    let mut agent = MctsAgent::new(SearchBudget::Millis(50));
+    let start = Instant::now();
+    let obs = Observation::from_state(&state);
+    let action = agent.choose_action(&obs);
+    assert!(obs.legal_actions.contains(&action));
+    assert!(start.elapsed() < Duration::from_millis(1000));
+}
+#[test]
+fn test_determinize_accounts_for_every_tile() {
+    for rules in [RuleSet::default(), RuleSet::preset("jokers").unwrap()] {
+        let num_tiles = 5 * rules.tiles_per_color + rules.jokers;
+        let mut state = GameState::with_rules(4, 23, Variant::Standard, rules);
+        for _ in 0..7 {
+            let action = state.legal_actions()[0];
+            state.apply(action).unwrap();
+        }
+        let obs = Observation::from_state(&state);
+        let sampled = GameState::determinize(&obs, &mut StdRng::seed_from_u64(0));
+        assert_eq!(sampled.factories(), state.factories());
+        assert_eq!(sampled.pool(), state.pool());
+        assert_eq!(sampled.current_player(), state.current_player());
+        for player in 0..4 {
+            assert_eq!(sampled.player_board(player), state.player_board(player));
+        }
+        let num_on_boards: usize = (0..4)
+            .map(|player| {
+                let board = sampled.player_board(player);
+                board.grid.iter().filter(|x| **x).count()
+                    + board.rows.iter().map(|row| row.0 as usize).sum::<usize>()
+                    + board.floor_tiles.len()
+            })
+            .sum();
+        let num_shared: i32 = sampled
+            .factories()
+            .iter()
+            .map(|fact| fact.values().sum::<i32>())
+            .sum::<i32>()
+            + sampled.pool().values().sum::<i32>();
+        assert_eq!(
+            sampled.num_tiles_in_bag() + num_on_boards + num_shared as usize,
+            num_tiles as usize
+        );
+    }
+}
@@ -0,0 +1,798 @@
+use crate::bots::ForfeitAgent;
+use crate::protocol::{self, ColumnChoice, Hello, LobbyRequest, Reply, SeatMessage};
+use crate::{
+    agent_from_name, Action, AzoolRequestType, Observation, PlayerAgent, PlayerBoard, RuleError,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::WebSocket;
+
+pub const DEFAULT_PORT: u16 = 7878;
+// who we say we are in our HELLO, either end
+pub(crate) const PEER_NAME: &str = concat!("azool-rust ", env!("CARGO_PKG_VERSION"));
+
+// how long a new connection gets to say HELLO, or to ask for its seat back, before we give up on it
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+// how often a remote seat that's waiting on its player checks whether they came back on a new connection
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// how long `join` keeps trying to get back to a game it lost the connection to
+const REJOIN_ATTEMPTS: u32 = 30;
+const REJOIN_DELAY: Duration = Duration::from_secs(1);
+
+// somebody at the table: an agent in this process, a `join` client on the other end of a
+// socket, or a browser
+#[derive(Debug)]
+pub enum Seat {
+    Local(Box<dyn PlayerAgent>),
+    Remote(BufReader<TcpStream>), // buffered, in case the lobby already read ahead
+    Web(Box<WebSocket<TcpStream>>), // boxed, since a websocket is a lot bigger than the rest
+}
+impl Seat {
+    pub fn name(&self) -> String {
+        match self {
+            Seat::Local(agent) => agent.name(),
+            Seat::Remote(_) => String::from(BufReader::<TcpStream>::SEAT_NAME),
+            Seat::Web(_) => String::from(WebSocket::<TcpStream>::SEAT_NAME),
+        }
+    }
+} // impl Seat
+
+// "remote" seats are filled in order by whoever connects to `listener`, "web" seats by the next
+// browser off `web_sockets`; everything else goes through agent_from_name. `seeds` gives each
+// local bot its own rng
+pub fn accept_seats(
+    listener: &TcpListener,
+    web_sockets: Option<&mpsc::Receiver<WebSocket<TcpStream>>>,
+    names: &[String],
+    seeds: &[u64],
+) -> Result<Vec<Seat>, String> {
+    let mut seats = Vec::new();
+    for (ii, (name, seed)) in names.iter().zip(seeds.iter()).enumerate() {
+        if name == "remote" {
+            println!(
+                "waiting for player {} on {}",
+                ii + 1,
+                listener.local_addr().map_err(|error| error.to_string())?
+            );
+            // whoever can't say HELLO doesn't get the seat, so keep going until somebody can
+            loop {
+                let (stream, addr) = listener
+                    .accept()
+                    .map_err(|error| format!("couldn't accept player {}: {}", ii + 1, error))?;
+                let mut reader = BufReader::new(stream);
+                match greet(&mut reader) {
+                    Ok(hello) => {
+                        println!("player {} joined from {} ({})", ii + 1, addr, hello.name);
+                        seats.push(Seat::Remote(reader));
+                        break;
+                    }
+                    Err(error) => println!("turned away {}: {}", addr, error),
+                }
+            }
+        } else if name == "web" {
+            let web_sockets = web_sockets.ok_or("web seats need a web port")?;
+            println!("waiting for player {} in a browser", ii + 1);
+            let socket = web_sockets
+                .recv()
+                .map_err(|_| format!("web gateway closed before player {} joined", ii + 1))?;
+            println!("player {} joined from a browser", ii + 1);
+            seats.push(Seat::Web(Box::new(socket)));
+        } else {
+            seats.push(Seat::Local(
+                crate::agent_from_name(name, *seed)
+                    .ok_or(format!("unknown player type: {}", name))?,
+            ));
+        }
+    }
+    Ok(seats)
+} // fn accept_seats
+
+// one message per line, as json - see protocol.rs
+pub(crate) fn send_line<T: Serialize>(stream: &mut TcpStream, msg: &T) -> std::io::Result<()> {
+    writeln!(stream, "{}", serde_json::to_string(msg)?)?;
+    stream.flush()
+}
+
+// the server's side of the HELLO every connection opens with: hears the client's, then answers
+// with ours, or with a refusal saying why we won't play with them
+pub(crate) fn greet(reader: &mut BufReader<TcpStream>) -> Result<Hello, String> {
+    let _ = reader.get_ref().set_read_timeout(Some(HELLO_TIMEOUT));
+    let mut line = String::new();
+    let hello = match reader.read_line(&mut line) {
+        Ok(0) => Err(String::from("hung up before saying HELLO")),
+        Ok(_) => protocol::parse::<Hello>(&line)
+            .map_err(|error| format!("say HELLO first: {}", error))
+            .and_then(|hello| hello.check().map(|_| hello)),
+        Err(error) => Err(format!("never said HELLO: {}", error)),
+    };
+    let _ = reader.get_ref().set_read_timeout(None);
+    let sent = match &hello {
+        Ok(_) => send_line(reader.get_mut(), &Hello::new(PEER_NAME)),
+        Err(error) => send_line(
+            reader.get_mut(),
+            &Reply::refuse(AzoolRequestType::ReqTypeHello, error),
+        ),
+    };
+    sent.map_err(|error| error.to_string())?;
+    hello
+} // fn greet
+
+// what happens to a remote seat whose connection drops
+#[derive(Clone, Debug)]
+pub struct ReconnectOptions {
+    pub grace_period: Duration, // how long the seat waits for its player to come back
+    pub stand_in: Option<String>, // bot that plays the seat out after that; None forfeits it
+}
+impl Default for ReconnectOptions {
+    fn default() -> Self {
+        ReconnectOptions {
+            grace_period: Duration::from_secs(60),
+            stand_in: Some(String::from("greedy")),
+        }
+    }
+}
+
+// how a player reaches their seat: a `join` client's socket, or a browser's. RemoteAgent plays
+// the seat the same way over either
+pub(crate) trait Connection: Sized + Send + fmt::Debug + 'static {
+    const SEAT_NAME: &'static str;
+    // the seats waiting on somebody to come back over this kind of connection
+    fn waiting(sessions: &Sessions) -> &Waiting<Self>;
+    // gets it ready for a seat to poll, every POLL_INTERVAL
+    fn prepare(&mut self);
+    // false if the other end is gone
+    fn send<T: Serialize>(&mut self, msg: &T) -> bool;
+    // the next whole message, or None if there wasn't one in time. `partial` keeps whatever's
+    // arrived of a message that isn't all here yet. Err once the other end is gone
+    fn poll(&mut self, partial: &mut Vec<u8>) -> Result<Option<String>, ()>;
+    fn hang_up(self);
+}
+impl Connection for BufReader<TcpStream> {
+    const SEAT_NAME: &'static str = "remote";
+    fn waiting(sessions: &Sessions) -> &Waiting<Self> {
+        &sessions.seats
+    }
+    fn prepare(&mut self) {
+        let _ = self.get_ref().set_nodelay(true);
+        let _ = self.get_ref().set_read_timeout(Some(POLL_INTERVAL));
+    }
+    fn send<T: Serialize>(&mut self, msg: &T) -> bool {
+        send_line(self.get_mut(), msg).is_ok()
+    }
+    fn poll(&mut self, partial: &mut Vec<u8>) -> Result<Option<String>, ()> {
+        // read_until keeps whatever it got before a timeout, so a half-read line just carries on
+        match self.read_until(b'\n', partial) {
+            Ok(_) if partial.ends_with(b"\n") => {
+                let line = String::from_utf8_lossy(partial).into_owned();
+                partial.clear();
+                Ok(Some(line))
+            }
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Ok(None)
+            }
+            _ => Err(()),
+        }
+    }
+    fn hang_up(self) {
+        let _ = self.get_ref().shutdown(Shutdown::Both);
+    }
+} // impl Connection for BufReader<TcpStream>
+
+pub(crate) type Waiting<C> = Arc<Mutex<HashMap<String, mpsc::Sender<C>>>>;
+// the remote and browser seats still waiting on somebody, by the token each player got when they
+// sat down. a player that lost their connection hands the token back on a new one to pick their
+// seat back up
+#[derive(Clone, Debug, Default)]
+pub struct Sessions {
+    seats: Waiting<BufReader<TcpStream>>,
+    pub(crate) browsers: Waiting<WebSocket<TcpStream>>,
+}
+impl Sessions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn open<C: Connection>(&self) -> (String, mpsc::Receiver<C>) {
+        let mut seats = C::waiting(self).lock().unwrap();
+        let mut token = format!("{:016x}", rand::random::<u64>());
+        while seats.contains_key(&token) {
+            token = format!("{:016x}", rand::random::<u64>());
+        }
+        let (tx, rx) = mpsc::channel();
+        seats.insert(token.clone(), tx);
+        (token, rx)
+    }
+    fn close<C: Connection>(&self, token: &str) {
+        C::waiting(self).lock().unwrap().remove(token);
+    }
+    // hands `conn` to the seat `token` belongs to, or tells them why not and hangs up
+    pub(crate) fn rejoin<C: Connection>(&self, token: &str, mut conn: C) -> Result<(), String> {
+        let error = match C::waiting(self).lock().unwrap().get(token) {
+            Some(seat) => match seat.send(conn) {
+                Ok(_) => return Ok(()),
+                Err(mpsc::SendError(returned)) => {
+                    conn = returned;
+                    String::from("that game is over")
+                }
+            },
+            None => String::from("nobody is waiting on that token"),
+        };
+        conn.send(&Reply::refuse(AzoolRequestType::ReqTypeRejoinGame, &error));
+        conn.hang_up();
+        Err(error)
+    } // fn rejoin
+} // impl Sessions
+
+// keeps taking connections on `listener` after the seats have filled, for anybody coming back
+// with REJOIN_GAME and their token
+pub fn spawn_rejoin_listener(listener: TcpListener, sessions: Sessions) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            let sessions = sessions.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream);
+                if greet(&mut reader).is_err() {
+                    return;
+                }
+                // and the same again to say which seat they're after, or we hang up on them
+                let _ = reader.get_ref().set_read_timeout(Some(HELLO_TIMEOUT));
+                let mut line = String::new();
+                if matches!(reader.read_line(&mut line), Ok(0) | Err(_)) {
+                    return;
+                }
+                let refusal = match protocol::parse::<LobbyRequest>(&line) {
+                    Ok(LobbyRequest::RejoinGame { token }) => {
+                        let _ = sessions.rejoin(&token, reader);
+                        return;
+                    }
+                    Ok(_) => Reply::refuse(
+                        AzoolRequestType::ReqTypeRejoinGame,
+                        "the table is full, only REJOIN_GAME from here on",
+                    ),
+                    Err(error) => Reply::refuse(AzoolRequestType::ReqTypeInvalid, &error),
+                };
+                let _ = send_line(reader.get_mut(), &refusal);
+            });
+        }
+    });
+} // fn spawn_rejoin_listener
+
+// what a remote seat's connection just did. a move is an Action, or a ColumnChoice when we're
+// tiling the gray wall
+enum Incoming<T> {
+    Move(T),
+    Rejoined,  // they're back on a new connection and need the turn again
+    Abandoned, // the grace period ran out and somebody else has the seat now
+}
+
+// the server's half of a remote or browser seat. the Player thread and its board stay here with
+// the table; whoever's on the other end of the connection is only asked for moves
+#[derive(Debug)]
+pub(crate) struct RemoteAgent<C: Connection> {
+    player_id: u8,
+    num_players: usize,
+    token: String,
+    sessions: Sessions,
+    rejoins: mpsc::Receiver<C>,
+    reconnect: ReconnectOptions,
+    conn: Option<C>,
+    dropped_at: Instant, // when we lost `conn`, if it's gone
+    line: Vec<u8>,       // whatever's arrived of the next message
+    board: Option<PlayerBoard>,
+    stand_in: Option<Box<dyn PlayerAgent>>, // plays the seat for good once it's been abandoned
+}
+
+impl<C: Connection> RemoteAgent<C> {
+    pub(crate) fn new(
+        conn: C,
+        player_id: u8,
+        num_players: usize,
+        board: Option<&PlayerBoard>,
+        sessions: &Sessions,
+        reconnect: &ReconnectOptions,
+    ) -> Self {
+        let (token, rejoins) = sessions.open();
+        let mut agent = RemoteAgent {
+            player_id,
+            num_players,
+            token,
+            sessions: sessions.clone(),
+            rejoins,
+            reconnect: reconnect.clone(),
+            conn: None,
+            dropped_at: Instant::now(),
+            line: Vec::new(),
+            board: board.cloned(),
+            stand_in: None,
+        };
+        agent.connect(conn);
+        agent
+    } // fn new
+
+    // takes over a new connection and tells whoever's on it where they're sitting
+    fn connect(&mut self, mut conn: C) {
+        conn.prepare();
+        if let Some(old) = self.conn.replace(conn) {
+            old.hang_up();
+        }
+        self.line.clear();
+        let welcome = SeatMessage::JoinGame {
+            current_player: self.player_id,
+            num_players: self.num_players,
+            token: self.token.clone(),
+            board: self.board.clone(),
+        };
+        self.send(&welcome);
+    } // fn connect
+    fn disconnect(&mut self) {
+        if self.conn.take().is_some() {
+            println!(
+                "lost player {}, holding their seat for {:?}",
+                self.player_id, self.reconnect.grace_period
+            );
+            self.dropped_at = Instant::now();
+        }
+    }
+    fn send(&mut self, msg: &SeatMessage) {
+        let sent = match self.conn.as_mut() {
+            Some(conn) => conn.send(msg),
+            None => return,
+        };
+        if !sent {
+            self.disconnect();
+        }
+    }
+    fn abandon(&mut self) {
+        println!("player {} didn't come back", self.player_id);
+        self.sessions.close::<C>(&self.token);
+        self.stand_in = Some(
+            self.reconnect
+                .stand_in
+                .as_ref()
+                .and_then(|name| agent_from_name(name, rand::random()))
+                .unwrap_or_else(|| Box::new(ForfeitAgent {})),
+        );
+    }
+    // waits for the next message, or for the player to come back if the connection's gone
+    fn receive<T: DeserializeOwned>(&mut self) -> Incoming<T> {
+        loop {
+            if let Ok(conn) = self.rejoins.try_recv() {
+                println!("player {} is back", self.player_id);
+                self.connect(conn);
+                return Incoming::Rejoined;
+            }
+            let conn = match self.conn.as_mut() {
+                Some(conn) => conn,
+                None => {
+                    let waited = self.dropped_at.elapsed();
+                    if waited >= self.reconnect.grace_period {
+                        self.abandon();
+                        return Incoming::Abandoned;
+                    }
+                    let wait = (self.reconnect.grace_period - waited).min(POLL_INTERVAL);
+                    if let Ok(conn) = self.rejoins.recv_timeout(wait) {
+                        println!("player {} is back", self.player_id);
+                        self.connect(conn);
+                        return Incoming::Rejoined;
+                    }
+                    continue;
+                }
+            };
+            match conn.poll(&mut self.line) {
+                Ok(Some(msg)) => match protocol::parse(&msg) {
+                    Ok(action) => return Incoming::Move(action),
+                    Err(error) => self.send(&SeatMessage::bad_message(&error)),
+                },
+                Ok(None) => {}
+                Err(()) => self.disconnect(),
+            }
+        }
+    } // fn receive
+} // impl RemoteAgent
+
+impl<C: Connection> PlayerAgent for RemoteAgent<C> {
+    fn name(&self) -> String {
+        String::from(C::SEAT_NAME)
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        self.board = Some(obs.my_board.clone());
+        if let Some(stand_in) = self.stand_in.as_mut() {
+            return stand_in.choose_action(obs);
+        }
+        let turn = SeatMessage::TakeTurn(obs.clone());
+        self.send(&turn);
+        loop {
+            match self.receive() {
+                Incoming::Move(action) => match obs.check(&action) {
+                    Ok(()) => return action,
+                    Err(error) => self.on_invalid_action(action, error),
+                },
+                // they missed the turn, so ask again
+                Incoming::Rejoined => self.send(&turn),
+                Incoming::Abandoned => return self.stand_in.as_mut().unwrap().choose_action(obs),
+            }
+        }
+    } // fn choose_action
+    fn on_invalid_action(&mut self, action: Action, error: RuleError) {
+        self.send(&SeatMessage::refuse(error, action));
+    }
+    // the Player checks the answer, and asks again if it doesn't fit
+    fn choose_column(&mut self, board: &PlayerBoard, row_idx: usize, columns: &[usize]) -> usize {
+        if let Some(stand_in) = self.stand_in.as_mut() {
+            return stand_in.choose_column(board, row_idx, columns);
+        }
+        let ask = SeatMessage::ChooseColumn {
+            row_idx,
+            columns: columns.to_vec(),
+            my_board: board.clone(),
+        };
+        self.send(&ask);
+        loop {
+            match self.receive() {
+                Incoming::Move(ColumnChoice::ChooseColumn { col_idx }) => return col_idx,
+                Incoming::Rejoined => self.send(&ask),
+                Incoming::Abandoned => {
+                    return self
+                        .stand_in
+                        .as_mut()
+                        .unwrap()
+                        .choose_column(board, row_idx, columns)
+                }
+            }
+        }
+    } // fn choose_column
+    fn on_invalid_column(&mut self, col_idx: usize, error: RuleError) {
+        self.send(&SeatMessage::refuse_column(error, col_idx));
+    }
+    fn on_round_end(&mut self, board: &PlayerBoard) {
+        self.board = Some(board.clone());
+        match self.stand_in.as_mut() {
+            Some(stand_in) => stand_in.on_round_end(board),
+            None => self.send(&SeatMessage::EndOfRound {
+                my_board: board.clone(),
+            }),
+        }
+    }
+    fn on_game_end(&mut self, final_score: i32) {
+        self.sessions.close::<C>(&self.token);
+        if let Some(stand_in) = self.stand_in.as_mut() {
+            stand_in.on_game_end(final_score);
+        }
+        self.send(&SeatMessage::EndOfGame { final_score });
+        if let Some(conn) = self.conn.take() {
+            conn.hang_up();
+        }
+    }
+} // impl PlayerAgent for RemoteAgent<C>
+
+// reads the next message off a server connection
+fn read_line(reader: &mut BufReader<TcpStream>) -> Result<String, String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Err(String::from("the server hung up")),
+        Ok(_) => Ok(line),
+        Err(error) => Err(format!("lost the server: {}", error)),
+    }
+}
+// the client's side of the HELLO: says ours and hears theirs, or why they won't have us
+pub(crate) fn say_hello(reader: &mut BufReader<TcpStream>) -> Result<Hello, String> {
+    send_line(reader.get_mut(), &Hello::new(PEER_NAME))
+        .map_err(|error| format!("couldn't reach the server: {}", error))?;
+    let line = read_line(reader)?;
+    match protocol::parse::<Hello>(&line) {
+        Ok(hello) => hello.check().map(|_| hello),
+        Err(_) => Err(match protocol::parse::<Reply>(&line) {
+            Ok(Reply {
+                error: Some(error), ..
+            }) => error,
+            _ => format!("expected HELLO from the server, got {}", line.trim()),
+        }),
+    }
+} // fn say_hello
+fn rejoin_request(token: &str) -> LobbyRequest {
+    LobbyRequest::RejoinGame {
+        token: String::from(token),
+    }
+}
+
+// gets back into a game we lost the connection to, trying for a while before giving up
+fn reconnect(addr: std::net::SocketAddr, token: &str) -> Result<BufReader<TcpStream>, String> {
+    let mut last_error = String::new();
+    for _ in 0..REJOIN_ATTEMPTS {
+        match TcpStream::connect(addr) {
+            Ok(stream) => {
+                let mut reader = BufReader::new(stream);
+                let rejoined = say_hello(&mut reader).and_then(|_| {
+                    send_line(reader.get_mut(), &rejoin_request(token))
+                        .map_err(|error| error.to_string())
+                });
+                match rejoined {
+                    Ok(_) => return Ok(reader),
+                    Err(error) => last_error = error,
+                }
+            }
+            Err(error) => last_error = error.to_string(),
+        }
+        thread::sleep(REJOIN_DELAY);
+    }
+    Err(format!("couldn't get back to the game: {}", last_error))
+} // fn reconnect
+
+// plays one seat of a game hosted somewhere else. returns our final score
+pub fn join_game(stream: TcpStream, agent: Box<dyn PlayerAgent>) -> Result<i32, String> {
+    let mut reader = BufReader::new(stream);
+    say_hello(&mut reader)?;
+    take_seat(reader, agent)
+}
+
+// picks a seat back up with the token the server gave us when we first sat down
+pub fn rejoin_game(
+    stream: TcpStream,
+    token: &str,
+    agent: Box<dyn PlayerAgent>,
+) -> Result<i32, String> {
+    let mut reader = BufReader::new(stream);
+    let server = say_hello(&mut reader)?;
+    if !server.supports("rejoin") {
+        return Err(format!("{} doesn't let players rejoin", server.name));
+    }
+    send_line(reader.get_mut(), &rejoin_request(token))
+        .map_err(|error| format!("couldn't reach the server: {}", error))?;
+    take_seat(reader, agent)
+}
+
+// join_game for a connection that's already been talking, e.g. to a lobby
+pub(crate) fn take_seat(
+    mut reader: BufReader<TcpStream>,
+    mut agent: Box<dyn PlayerAgent>,
+) -> Result<i32, String> {
+    let _ = reader.get_ref().set_nodelay(true);
+    let addr = reader
+        .get_ref()
+        .peer_addr()
+        .map_err(|error| format!("not connected: {}", error))?;
+    let mut token: Option<String> = None;
+    loop {
+        let line = match read_line(&mut reader) {
+            Ok(line) => line,
+            // we can only get back in with the token from our seat
+            Err(error) => match &token {
+                Some(token) => {
+                    println!("{}, trying to get back in", error);
+                    reader = reconnect(addr, token)?;
+                    continue;
+                }
+                None => return Err(error),
+            },
+        };
+        let msg = match protocol::parse::<SeatMessage>(&line) {
+            Ok(msg) => msg,
+            // whoever turns us away before we've sat down says why in a Reply
+            Err(_) if token.is_none() => {
+                return Err(match protocol::parse::<Reply>(&line) {
+                    Ok(Reply {
+                        error: Some(error), ..
+                    }) => error,
+                    _ => format!("expected a seat from the server, got {}", line.trim()),
+                })
+            }
+            Err(error) => {
+                println!("ignoring {}", error);
+                continue;
+            }
+        };
+        match msg {
+            SeatMessage::JoinGame {
+                current_player,
+                num_players,
+                token: seat_token,
+                ..
+            } => {
+                if token.is_none() {
+                    println!(
+                        "joined as player {} of {} - if you lose the connection, `join` again with --rejoin {}",
+                        current_player, num_players, seat_token
+                    );
+                }
+                token = Some(seat_token);
+            }
+            _ if token.is_none() => {
+                return Err(format!(
+                    "expected a seat from the server, got {}",
+                    line.trim()
+                ))
+            }
+            SeatMessage::TakeTurn(obs) => {
+                let action = agent.choose_action(&obs);
+                // if this doesn't get there, the next read will notice
+                let _ = send_line(reader.get_mut(), &action);
+            }
+            SeatMessage::ChooseColumn {
+                row_idx,
+                columns,
+                my_board,
+            } => {
+                let col_idx = agent.choose_column(&my_board, row_idx, &columns);
+                let _ = send_line(reader.get_mut(), &ColumnChoice::ChooseColumn { col_idx });
+            }
+            SeatMessage::Invalid {
+                action,
+                col_idx,
+                error_type,
+                error,
+                ..
+            } => match (action, col_idx) {
+                (Some(action), _) => agent.on_invalid_action(action, error_type),
+                (None, Some(col_idx)) => agent.on_invalid_column(col_idx, error_type),
+                (None, None) => println!("server says: {}", error),
+            },
+            SeatMessage::EndOfRound { my_board } => agent.on_round_end(&my_board),
+            SeatMessage::EndOfGame { final_score } => {
+                agent.on_game_end(final_score);
+                return Ok(final_score);
+            }
+        }
+    }
+} // fn take_seat
+
+#[cfg(test)]
+use crate::{host_game, GameOptions, GreedyAgent, RandomAgent, Variant};
+
+#[test]
+fn test_game_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        join_game(stream, Box::new(GreedyAgent::new()))
+    });
+    let mut reader = BufReader::new(listener.accept().unwrap().0);
+    assert!(greet(&mut reader).unwrap().supports("rejoin"));
+    let seats = vec![
+        Seat::Remote(reader),
+        Seat::Local(Box::new(RandomAgent::new())),
+    ];
+    assert_eq!(seats[0].name(), "remote");
+    host_game(seats, 5, None, &GameOptions::default());
+    let score = client.join().unwrap().unwrap();
+    assert!(score >= 0);
+}
+#[test]
+fn test_gray_game_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        join_game(stream, Box::new(RandomAgent::new()))
+    });
+    let mut reader = BufReader::new(listener.accept().unwrap().0);
+    greet(&mut reader).unwrap();
+    let seats = vec![
+        Seat::Remote(reader),
+        Seat::Local(Box::new(GreedyAgent::new())),
+    ];
+    let options = GameOptions {
+        variant: Variant::Gray,
+        ..Default::default()
+    };
+    // the client picks its own columns, and the table would panic on one that doesn't fit
+    host_game(seats, 8, None, &options);
+    assert!(client.join().unwrap().unwrap() >= 0);
+}
+#[test]
+fn test_join_needs_a_seat() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let mut reader = BufReader::new(listener.accept().unwrap().0);
+        greet(&mut reader).unwrap();
+        writeln!(reader.get_mut(), r#"{{"req_type": "GET_BOARD"}}"#).unwrap();
+    });
+    let stream = TcpStream::connect(addr).unwrap();
+    assert!(join_game(stream, Box::new(GreedyAgent::new())).is_err());
+    server.join().unwrap();
+}
+#[test]
+fn test_hello_turns_away_strangers() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        (0..2)
+            .map(|_| greet(&mut BufReader::new(listener.accept().unwrap().0)))
+            .collect::<Vec<_>>()
+    });
+    let future = r#"{"req_type": "HELLO", "version": 99, "name": "azool 2099"}"#;
+    for line in [future, r#"{"req_type": "LIST_TABLES"}"#] {
+        let mut reader = BufReader::new(TcpStream::connect(addr).unwrap());
+        writeln!(reader.get_mut(), "{}", line).unwrap();
+        let reply: Reply = protocol::parse(&read_line(&mut reader).unwrap()).unwrap();
+        assert_eq!(reply.req_type, AzoolRequestType::ReqTypeHello);
+        assert!(!reply.success);
+    }
+    let refusals = server.join().unwrap();
+    assert!(refusals[0].as_ref().unwrap_err().contains("version 99"));
+    assert!(refusals[1]
+        .as_ref()
+        .unwrap_err()
+        .starts_with("say HELLO first"));
+}
+// sits down, waits for the first turn and hangs up without moving. returns the seat's token
+#[cfg(test)]
+fn drop_on_first_turn(stream: TcpStream) -> String {
+    let mut reader = BufReader::new(stream);
+    say_hello(&mut reader).unwrap();
+    let token = match protocol::parse(&read_line(&mut reader).unwrap()).unwrap() {
+        SeatMessage::JoinGame { token, .. } => token,
+        msg => panic!("expected a seat, got {:?}", msg),
+    };
+    loop {
+        let msg = protocol::parse(&read_line(&mut reader).unwrap()).unwrap();
+        if let SeatMessage::TakeTurn(_) = msg {
+            reader.get_ref().shutdown(Shutdown::Both).unwrap();
+            return token;
+        }
+    }
+}
+#[cfg(test)]
+fn host_with_a_dropout(reconnect: ReconnectOptions) -> (std::net::SocketAddr, Sessions, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = thread::spawn(move || drop_on_first_turn(TcpStream::connect(addr).unwrap()));
+    let mut reader = BufReader::new(listener.accept().unwrap().0);
+    greet(&mut reader).unwrap();
+    let sessions = Sessions::new();
+    spawn_rejoin_listener(listener, sessions.clone());
+    let options = GameOptions {
+        sessions: Some(sessions.clone()),
+        reconnect,
+        ..Default::default()
+    };
+    thread::spawn(move || {
+        let seats = vec![
+            Seat::Remote(reader),
+            Seat::Local(Box::new(RandomAgent::new())),
+        ];
+        host_game(seats, 9, None, &options);
+    });
+    (addr, sessions, client.join().unwrap())
+}
+#[test]
+fn test_rejoin_picks_up_the_pending_turn() {
+    let (addr, _, token) = host_with_a_dropout(ReconnectOptions {
+        grace_period: Duration::from_secs(30),
+        stand_in: None,
+    });
+    assert!(rejoin_game(
+        TcpStream::connect(addr).unwrap(),
+        "not a token",
+        Box::new(GreedyAgent::new())
+    )
+    .is_err());
+    let stream = TcpStream::connect(addr).unwrap();
+    let score = rejoin_game(stream, &token, Box::new(GreedyAgent::new())).unwrap();
+    assert!(score >= 0);
+}
+#[test]
+fn test_seat_is_forfeited_after_the_grace_period() {
+    let (addr, sessions, token) = host_with_a_dropout(ReconnectOptions {
+        grace_period: Duration::from_millis(200),
+        stand_in: None,
+    });
+    // the game plays out without them and closes the seat
+    while !sessions.seats.lock().unwrap().is_empty() {
+        thread::sleep(POLL_INTERVAL);
+    }
+    let stream = TcpStream::connect(addr).unwrap();
+    assert!(rejoin_game(stream, &token, Box::new(GreedyAgent::new())).is_err());
+}
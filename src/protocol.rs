@@ -0,0 +1,666 @@
+// every message azool sends, typed. Players and the table talk in TableRequest/TableMessage over
+// their channels; remote and browser seats get SeatMessages and answer with an Action; the lobby
+// takes LobbyRequests and answers with a Reply. every connection opens with a HELLO each way.
+// each one is a json object tagged with its AzoolRequestType under "req_type" - docs/protocol.md
+// has the whole schema with examples
+use crate::{
+    Action, AzoolRequestType, Event, Events, Observation, PlayerBoard, RuleError, TableInfo,
+    TileColor, VARIANTS,
+};
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+// bumped whenever a message changes shape. peers have to match exactly
+pub const PROTOCOL_VERSION: u32 = 1;
+// what this build can do beyond playing a game
+pub const FEATURES: &[&str] = &["rejoin", "watch"];
+
+/// The first thing both ends of a connection say: who they are and what they can do.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "req_type", rename = "HELLO", try_from = "Greeting")]
+pub struct Hello {
+    pub version: u32,
+    pub name: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub variants: Vec<String>, // the kinds of game a lobby will set up
+}
+impl Hello {
+    pub fn new(name: &str) -> Self {
+        Hello {
+            version: PROTOCOL_VERSION,
+            name: String::from(name),
+            features: FEATURES.iter().map(|feature| feature.to_string()).collect(),
+            variants: VARIANTS.iter().map(|variant| variant.to_string()).collect(),
+        }
+    }
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|ours| ours == feature)
+    }
+    // why we can't play with whoever sent this, if we can't
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.version != PROTOCOL_VERSION {
+            return Err(format!(
+                "{} speaks azool protocol version {}, but this is version {}",
+                self.name, self.version, PROTOCOL_VERSION
+            ));
+        }
+        Ok(())
+    }
+} // impl Hello
+
+// serde doesn't check a struct's own tag on the way in, so Hello reads through this first
+#[derive(Deserialize)]
+struct Greeting {
+    req_type: AzoolRequestType,
+    version: u32,
+    name: String,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    variants: Vec<String>,
+}
+impl TryFrom<Greeting> for Hello {
+    type Error = String;
+    fn try_from(greeting: Greeting) -> Result<Self, String> {
+        if greeting.req_type != AzoolRequestType::ReqTypeHello {
+            return Err(format!(
+                "expected HELLO, got {}",
+                greeting.req_type.get_string()
+            ));
+        }
+        let Greeting {
+            version,
+            name,
+            features,
+            variants,
+            ..
+        } = greeting;
+        Ok(Hello {
+            version,
+            name,
+            features,
+            variants,
+        })
+    }
+}
+
+/// A Player asking the table for something, or reporting back what it did.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "req_type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TableRequest {
+    GetBoard {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_player: Option<u8>,
+    },
+    DrawFromFactory(FactoryDraw),
+    DrawFromPool(PoolDraw),
+    DiscardFromFactory(FactoryDraw),
+    DiscardFromPool(PoolDraw),
+    // leftover tiles at the end of the round, by color name
+    ReturnToLid {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_player: Option<u8>,
+        returns: HashMap<TileColor, i32>,
+    },
+    TurnFinished {
+        current_player: u8,
+        pool_penalty: bool,
+        events: Events,
+    },
+    FullRow {
+        current_player: u8,
+        end_of_game: bool,
+        events: Events,
+    },
+    EndOfGame {
+        current_player: u8,
+        final_score: i32,
+        events: Events,
+    },
+    GetPlayerState {
+        current_player: u8,
+        board: PlayerBoard,
+    },
+}
+
+impl TableRequest {
+    pub fn req_type(&self) -> AzoolRequestType {
+        match self {
+            TableRequest::GetBoard { .. } => AzoolRequestType::ReqTypeGetBoard,
+            TableRequest::DrawFromFactory(_) => AzoolRequestType::ReqTypeDrawFromFactory,
+            TableRequest::DrawFromPool(_) => AzoolRequestType::ReqTypeDrawFromPool,
+            TableRequest::DiscardFromFactory(_) => AzoolRequestType::ReqTypeDiscardFromFactory,
+            TableRequest::DiscardFromPool(_) => AzoolRequestType::ReqTypeDiscardFromPool,
+            TableRequest::ReturnToLid { .. } => AzoolRequestType::ReqTypeReturnToLid,
+            TableRequest::TurnFinished { .. } => AzoolRequestType::ReqTypeTurnFinished,
+            TableRequest::FullRow { .. } => AzoolRequestType::ReqTypeFullRow,
+            TableRequest::EndOfGame { .. } => AzoolRequestType::ReqTypeEndOfGame,
+            TableRequest::GetPlayerState { .. } => AzoolRequestType::ReqTypeGetPlayerState,
+        }
+    }
+} // impl TableRequest
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FactoryDraw {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_player: Option<u8>,
+    pub factory_idx: usize,
+    #[serde(with = "color_index")]
+    pub tile_color: TileColor,
+    // the pattern line the tiles go on. draws need one; discards go to the floor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_idx: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PoolDraw {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_player: Option<u8>,
+    #[serde(with = "color_index")]
+    pub tile_color: TileColor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_idx: Option<usize>,
+}
+
+/// The table telling a Player to do something, or answering one of its requests.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "req_type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TableMessage {
+    TakeTurn {
+        current_player: u8,
+        // how long this move can take, if the game is on the clock
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        time_left_ms: Option<u64>,
+    },
+    EndOfRound {
+        current_player: u8,
+    },
+    EndOfGame {
+        current_player: u8,
+    },
+    GetPlayerState {
+        current_player: u8,
+    },
+    GetBoard(TableView),
+    DrawFromFactory(MoveResult),
+    DrawFromPool(MoveResult),
+    DiscardFromFactory(MoveResult),
+    DiscardFromPool(MoveResult),
+    Invalid {
+        #[serde(default)]
+        success: bool,
+        error: String,
+    },
+}
+
+/// The shared tiles, as a Player sees them. Spectators get everyone's board and what just
+/// happened along with it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableView {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_player: Option<u8>,
+    pub num_players: usize,
+    pub num_factories: usize,
+    pub factories: Vec<HashMap<TileColor, i32>>,
+    pub num_tiles_in_pool: i32,
+    pub pool: HashMap<TileColor, i32>,
+    pub end_of_round: bool,
+    pub white_tile_in_pool: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub players: Option<Vec<PlayerBoard>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Events>,
+    // what's left on each seat's clock, if the game is on one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_left_ms: Option<Vec<u64>>,
+}
+
+/// How a draw or discard went. Echoes the request it answers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MoveResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_player: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factory_idx: Option<usize>,
+    #[serde(with = "color_index")]
+    pub tile_color: TileColor,
+    pub success: bool,
+    pub num_tiles_returned: i32,
+    // only for draws from the pool: whether this one took the first player marker
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_penalty: Option<bool>,
+    // why it failed, if it did: a code for programs and a message for people
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_type: Option<RuleError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+impl MoveResult {
+    pub(crate) fn new(
+        current_player: Option<u8>,
+        factory_idx: Option<usize>,
+        tile_color: TileColor,
+    ) -> Self {
+        MoveResult {
+            current_player,
+            factory_idx,
+            tile_color,
+            success: false,
+            num_tiles_returned: 0,
+            pool_penalty: None,
+            error_type: None,
+            error: None,
+        }
+    }
+    pub(crate) fn refuse(&mut self, error: RuleError) {
+        self.success = false;
+        self.error_type = Some(error);
+        self.error = Some(error.to_string());
+    }
+} // impl MoveResult
+
+/// What a remote or browser seat gets sent. It answers TAKE_TURN with one of the
+/// observation's legal actions, and CHOOSE_COLUMN with a `ColumnChoice`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "req_type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SeatMessage {
+    // first thing on every connection to the seat, including rejoins
+    JoinGame {
+        current_player: u8,
+        num_players: usize,
+        token: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        board: Option<PlayerBoard>,
+    },
+    TakeTurn(Observation),
+    // gray wall only: pattern line `row_idx` is full and could go in any of `columns`
+    ChooseColumn {
+        row_idx: usize,
+        columns: Vec<usize>,
+        my_board: PlayerBoard,
+    },
+    Invalid {
+        #[serde(default)]
+        success: bool,
+        error_type: RuleError,
+        error: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        action: Option<Action>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        col_idx: Option<usize>,
+    },
+    EndOfRound {
+        my_board: PlayerBoard,
+    },
+    EndOfGame {
+        final_score: i32,
+    },
+}
+impl SeatMessage {
+    // a move we turned down
+    pub(crate) fn refuse(error_type: RuleError, action: Action) -> Self {
+        SeatMessage::Invalid {
+            success: false,
+            error_type,
+            error: error_type.to_string(),
+            action: Some(action),
+            col_idx: None,
+        }
+    }
+    // a wall column we turned down
+    pub(crate) fn refuse_column(error_type: RuleError, col_idx: usize) -> Self {
+        SeatMessage::Invalid {
+            success: false,
+            error_type,
+            error: error_type.to_string(),
+            action: None,
+            col_idx: Some(col_idx),
+        }
+    }
+    // a message that isn't a move at all
+    pub(crate) fn bad_message(error: &str) -> Self {
+        SeatMessage::Invalid {
+            success: false,
+            error_type: RuleError::BadMessage,
+            error: String::from(error),
+            action: None,
+            col_idx: None,
+        }
+    }
+}
+
+/// A seat's answer to CHOOSE_COLUMN.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "req_type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ColumnChoice {
+    ChooseColumn { col_idx: usize },
+}
+
+// an Action on the wire. the same shape as the draws and discards a Player sends the table, plus
+// the row the tiles go on
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "req_type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub(crate) enum Move {
+    DrawFromFactory {
+        factory_idx: usize,
+        #[serde(with = "color_index")]
+        tile_color: TileColor,
+        row_idx: usize,
+    },
+    DrawFromPool {
+        #[serde(with = "color_index")]
+        tile_color: TileColor,
+        row_idx: usize,
+    },
+    DiscardFromFactory {
+        factory_idx: usize,
+        #[serde(with = "color_index")]
+        tile_color: TileColor,
+    },
+    DiscardFromPool {
+        #[serde(with = "color_index")]
+        tile_color: TileColor,
+    },
+}
+impl From<Action> for Move {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::FactoryToRow {
+                factory_idx,
+                color,
+                row_idx,
+            } => Move::DrawFromFactory {
+                factory_idx,
+                tile_color: color,
+                row_idx,
+            },
+            Action::PoolToRow { color, row_idx } => Move::DrawFromPool {
+                tile_color: color,
+                row_idx,
+            },
+            Action::FactoryToFloor { factory_idx, color } => Move::DiscardFromFactory {
+                factory_idx,
+                tile_color: color,
+            },
+            Action::PoolToFloor { color } => Move::DiscardFromPool { tile_color: color },
+        }
+    }
+}
+impl From<Move> for Action {
+    fn from(action: Move) -> Self {
+        match action {
+            Move::DrawFromFactory {
+                factory_idx,
+                tile_color,
+                row_idx,
+            } => Action::FactoryToRow {
+                factory_idx,
+                color: tile_color,
+                row_idx,
+            },
+            Move::DrawFromPool {
+                tile_color,
+                row_idx,
+            } => Action::PoolToRow {
+                color: tile_color,
+                row_idx,
+            },
+            Move::DiscardFromFactory {
+                factory_idx,
+                tile_color,
+            } => Action::FactoryToFloor {
+                factory_idx,
+                color: tile_color,
+            },
+            Move::DiscardFromPool { tile_color } => Action::PoolToFloor { color: tile_color },
+        }
+    }
+}
+
+/// Everything the lobby takes, plus REJOIN_GAME, which also works on a `serve` game's port.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "req_type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LobbyRequest {
+    ListTables,
+    CreateTable {
+        num_players: usize,
+        #[serde(default = "standard_variant")]
+        variant: String,
+    },
+    JoinTable {
+        table_id: u32,
+    },
+    LeaveTable,
+    WatchTable {
+        table_id: u32,
+    },
+    RejoinGame {
+        token: String,
+    },
+}
+fn standard_variant() -> String {
+    String::from("standard")
+}
+
+/// The lobby's answer to a LobbyRequest, and how anybody turns down a request they won't do.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reply {
+    pub req_type: AzoolRequestType,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<Vec<TableInfo>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<TableInfo>,
+}
+impl Reply {
+    pub(crate) fn ok(req_type: AzoolRequestType) -> Self {
+        Reply {
+            req_type,
+            success: true,
+            error: None,
+            tables: None,
+            table: None,
+        }
+    }
+    pub(crate) fn refuse(req_type: AzoolRequestType, error: &str) -> Self {
+        Reply {
+            success: false,
+            error: Some(String::from(error)),
+            ..Reply::ok(req_type)
+        }
+    }
+} // impl Reply
+
+// reads one message. anything that doesn't fit its type comes back as the reason why, for the
+// caller to send back instead of the answer
+pub(crate) fn parse<T: DeserializeOwned>(line: &str) -> Result<T, String> {
+    serde_json::from_str(line).map_err(|error| format!("bad message: {}", error))
+}
+
+// and the same for the json crate's values, which boards, events and save files are still built from
+pub(crate) fn from_json<T: DeserializeOwned>(val: &json::JsonValue) -> Result<T, String> {
+    serde_json::from_value(to_serde_value(val)).map_err(|error| format!("bad message: {}", error))
+}
+pub(crate) fn to_json<T: Serialize>(msg: &T) -> json::JsonValue {
+    from_serde_value(&serde_json::to_value(msg).expect("every message serializes"))
+}
+
+fn to_serde_value(val: &json::JsonValue) -> serde_json::Value {
+    match val {
+        json::JsonValue::Null => serde_json::Value::Null,
+        json::JsonValue::Boolean(b) => serde_json::Value::Bool(*b),
+        json::JsonValue::Number(_) => match val.as_i64() {
+            Some(num) => num.into(),
+            None => val.as_f64().into(),
+        },
+        json::JsonValue::Short(_) | json::JsonValue::String(_) => {
+            serde_json::Value::String(val.as_str().unwrap_or("").to_string())
+        }
+        json::JsonValue::Array(items) => items.iter().map(to_serde_value).collect(),
+        json::JsonValue::Object(obj) => serde_json::Value::Object(
+            obj.iter()
+                .map(|(key, val)| (key.to_string(), to_serde_value(val)))
+                .collect(),
+        ),
+    }
+}
+fn from_serde_value(val: &serde_json::Value) -> json::JsonValue {
+    match val {
+        serde_json::Value::Null => json::JsonValue::Null,
+        serde_json::Value::Bool(b) => (*b).into(),
+        serde_json::Value::Number(num) => match num.as_i64() {
+            Some(num) => num.into(),
+            None => num.as_f64().unwrap_or(0.0).into(),
+        },
+        serde_json::Value::String(s) => s.as_str().into(),
+        serde_json::Value::Array(items) => {
+            json::JsonValue::Array(items.iter().map(from_serde_value).collect())
+        }
+        serde_json::Value::Object(obj) => {
+            let mut out = json::JsonValue::new_object();
+            for (key, val) in obj.iter() {
+                out[key.as_str()] = from_serde_value(val);
+            }
+            out
+        }
+    }
+}
+
+// boards, events and observations had their json written by hand long before there was serde
+// here, and the save files and game logs depend on it, so serde goes through that
+macro_rules! serde_via_json {
+    ($($ty:ty),*) => {$(
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                to_serde_value(&self.to_json()).serialize(serializer)
+            }
+        }
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let val = serde_json::Value::deserialize(deserializer)?;
+                <$ty>::from_json(&from_serde_value(&val)).map_err(D::Error::custom)
+            }
+        }
+    )*};
+}
+serde_via_json!(PlayerBoard, Event, Observation);
+
+// tile colors in a request are TileColor::to_integer: the five real ones, or 5 for a joker
+mod color_index {
+    use super::*;
+    pub fn serialize<S: Serializer>(color: &TileColor, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(color.to_integer())
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TileColor, D::Error> {
+        let val = i32::deserialize(deserializer)?;
+        match TileColor::from_integer(val) {
+            TileColor::NOCOLOR => Err(D::Error::custom(format!("not a tile color: {}", val))),
+            color => Ok(color),
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::{GameState, RuleSet, Variant};
+
+#[test]
+fn test_messages_keep_their_wire_format() {
+    let draw = TableRequest::DrawFromFactory(FactoryDraw {
+        current_player: Some(2),
+        factory_idx: 3,
+        tile_color: TileColor::GREEN,
+        row_idx: Some(1),
+    });
+    let val = to_json(&draw);
+    assert_eq!(val["req_type"], "DRAW_FROM_FACTORY");
+    assert_eq!(val["tile_color"], 2);
+    assert_eq!(val["row_idx"], 1);
+    assert_eq!(from_json::<TableRequest>(&val), Ok(draw));
+    let returns =
+        parse::<TableRequest>(r#"{"req_type": "RETURN_TO_LID", "returns": {"RED": 2, "BLUE": 3}}"#)
+            .unwrap();
+    match returns {
+        TableRequest::ReturnToLid { returns, .. } => assert_eq!(returns[&TileColor::BLUE], 3),
+        request => panic!("wrong request: {:?}", request),
+    }
+    let action = Action::PoolToRow {
+        color: TileColor::WHITE,
+        row_idx: 4,
+    };
+    let val = action.to_json();
+    assert_eq!(val["req_type"], "DRAW_FROM_POOL");
+    assert_eq!(Action::from_json(&val), Ok(action));
+    let reply = to_json(&Reply::refuse(AzoolRequestType::ReqTypeJoinTable, "nope"));
+    assert_eq!(reply["req_type"], "JOIN_TABLE");
+    assert_eq!(reply["success"], false);
+    assert!(!reply.has_key("tables"));
+}
+#[test]
+fn test_observations_survive_the_trip() {
+    let rules = RuleSet::preset("gentle").unwrap();
+    let mut state = GameState::with_rules(3, 7, Variant::Standard, rules.clone());
+    for _ in 0..5 {
+        state.apply(state.legal_actions()[0]).unwrap();
+    }
+    let turn = SeatMessage::TakeTurn(Observation::from_state(&state));
+    let line = serde_json::to_string(&turn).unwrap();
+    match parse::<SeatMessage>(&line).unwrap() {
+        SeatMessage::TakeTurn(obs) => {
+            assert_eq!(
+                obs.my_board,
+                state.player_board(state.current_player()).clone()
+            );
+            assert_eq!(obs.legal_actions, state.legal_actions());
+            assert_eq!(obs.rules, rules);
+        }
+        msg => panic!("wrong message: {:?}", msg),
+    }
+}
+#[test]
+fn test_hello_needs_the_same_version() {
+    let hello = Hello::new("tester");
+    assert!(hello.check().is_ok());
+    assert!(hello.supports("rejoin") && !hello.supports("chat"));
+    let val = to_json(&hello);
+    assert_eq!(val["req_type"], "HELLO");
+    assert_eq!(from_json::<Hello>(&val), Ok(hello));
+    // anything that's missing a feature list just doesn't have any
+    let old = parse::<Hello>(r#"{"req_type": "HELLO", "version": 0, "name": "old"}"#).unwrap();
+    assert!(old.features.is_empty());
+    assert!(old.check().unwrap_err().contains("version 0"));
+    assert!(parse::<Hello>(r#"{"req_type": "LIST_TABLES", "version": 1, "name": "x"}"#).is_err());
+    // a req_type we've never heard of isn't one of ours, even on a message that only carries it
+    assert!(parse::<Reply>(r#"{"req_type": "CREATE_TABEL", "success": true}"#).is_err());
+    assert_eq!(
+        parse::<Reply>(r#"{"req_type": "CREATE_TABLE", "success": true}"#),
+        Ok(Reply::ok(AzoolRequestType::ReqTypeCreateTable))
+    );
+}
+#[test]
+fn test_bad_messages_are_errors() {
+    for line in [
+        "not json",
+        r#"{"no": "req_type"}"#,
+        r#"{"req_type": "brglker"}"#,
+        r#"{"req_type": "DRAW_FROM_FACTORY", "tile_color": 0}"#,
+        r#"{"req_type": "DRAW_FROM_POOL", "tile_color": 9}"#,
+        r#"{"req_type": "GET_PLAYER_STATE", "current_player": 1, "board": {}}"#,
+    ] {
+        assert!(parse::<TableRequest>(line).is_err(), "{} parsed", line);
+    }
+    assert!(parse::<LobbyRequest>(r#"{"req_type": "JOIN_TABLE", "table_id": -1}"#).is_err());
+    assert_eq!(
+        parse::<LobbyRequest>(r#"{"req_type": "CREATE_TABLE", "num_players": 2}"#),
+        Ok(LobbyRequest::CreateTable {
+            num_players: 2,
+            variant: standard_variant()
+        })
+    );
+}
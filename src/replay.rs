@@ -0,0 +1,458 @@
+use crate::agent::{draw_player_board, draw_shared_tiles};
+use crate::game_state::{factories_from_json, factories_to_json, parse_color};
+use crate::{Event, Events, PlayerBoard, TileColor, NUM_COLORS, NUM_COLORS_AS_USIZE};
+use json::object;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const LOG_FORMAT_VERSION: u32 = 1;
+
+// everything on the table at one point in a recorded game
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub round: u32,
+    pub factories: Vec<HashMap<TileColor, i32>>,
+    pub pool: HashMap<TileColor, i32>,
+    pub first_player_marker_in_pool: bool,
+    pub players: Vec<PlayerBoard>,
+    pub events: Events, // what got us here from the position before
+}
+
+impl Position {
+    // before the first deal
+    pub fn new(num_players: usize) -> Self {
+        Position {
+            round: 0,
+            factories: Vec::new(),
+            pool: empty_pool(),
+            first_player_marker_in_pool: true,
+            players: vec![PlayerBoard::new(); num_players],
+            events: Events::new(),
+        }
+    }
+    // moves the pieces the way the event says. the log is trusted to follow the rules - nothing
+    // gets re-checked here - but not to only point at factories, seats and lines that are there
+    fn apply(&mut self, event: &Event) -> Result<(), String> {
+        match event {
+            Event::TilesTaken {
+                factory_idx, color, ..
+            } => match factory_idx {
+                Some(idx) => {
+                    if *idx >= self.factories.len() {
+                        return Err(format!("there's no factory {}", idx));
+                    }
+                    let mut fact = self.factories.remove(*idx);
+                    fact.remove(color);
+                    for (other, count) in fact {
+                        *self.pool.entry(other).or_insert(0) += count;
+                    }
+                }
+                None => {
+                    self.pool.insert(*color, 0);
+                }
+            },
+            Event::FirstPlayerMarkerTaken { player } => {
+                self.first_player_marker_in_pool = false;
+                self.board(*player)?.has_first_player_marker = true;
+            }
+            Event::TilesPlaced {
+                player,
+                row_idx,
+                color,
+                num_tiles,
+            } => {
+                check_idx(*row_idx, "row")?;
+                self.board(*player)?
+                    .place_tiles(*row_idx, *color, *num_tiles);
+            }
+            Event::TilesToFloor {
+                player,
+                color,
+                num_tiles,
+            } => {
+                let board = self.board(*player)?;
+                for _ in 0..*num_tiles {
+                    board.floor_tiles.push(*color);
+                }
+            }
+            Event::WallTiled {
+                player,
+                row_idx,
+                col_idx,
+                color,
+                points,
+            } => {
+                check_idx(*row_idx, "row")?;
+                check_idx(*col_idx, "column")?;
+                let board = self.board(*player)?;
+                board.grid[[*row_idx, *col_idx]] = true;
+                if let Some(wall_colors) = board.wall_colors.as_mut() {
+                    wall_colors[[*row_idx, *col_idx]] = *color;
+                }
+                board.clear_row(*row_idx);
+                board.score += points;
+            }
+            Event::LineDiscarded {
+                player, row_idx, ..
+            } => {
+                check_idx(*row_idx, "row")?;
+                // the tiles go to the lid, and FLOOR_PENALTY already counts them
+                self.board(*player)?.clear_row(*row_idx);
+            }
+            Event::FloorPenalty { player, points } => {
+                let board = self.board(*player)?;
+                board.score = std::cmp::max(board.score - points, 0);
+            }
+            Event::RoundEnded { .. } => {
+                for board in self.players.iter_mut() {
+                    board.floor_tiles.clear();
+                    board.has_first_player_marker = false;
+                }
+            }
+            Event::RoundStarted {
+                round, factories, ..
+            } => {
+                self.round = *round;
+                self.factories = factories.clone();
+                self.pool = empty_pool();
+                self.first_player_marker_in_pool = true;
+            }
+            Event::FinalBonus { player, points } => self.board(*player)?.score += points,
+            Event::GameEnded { .. } => {}
+        }
+        Ok(())
+    } // fn apply
+    fn board(&mut self, player: usize) -> Result<&mut PlayerBoard, String> {
+        let num_players = self.players.len();
+        self.players.get_mut(player).ok_or(format!(
+            "there's no player {} in a {} player game",
+            player, num_players
+        ))
+    }
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut pool = json::JsonValue::new_object();
+        for (color, count) in self.pool.iter() {
+            let _ = pool.insert(&color.to_string(), *count);
+        }
+        let players: Vec<json::JsonValue> = self.players.iter().map(|b| b.to_json()).collect();
+        object! {
+            "round": self.round,
+            "factories": factories_to_json(&self.factories),
+            "pool": pool,
+            "first_player_marker_in_pool": self.first_player_marker_in_pool,
+            "players": players,
+        }
+    } // fn to_json
+    pub fn from_json(val: &json::JsonValue) -> Result<Self, String> {
+        let mut pool = empty_pool();
+        for (color_str, num) in val["pool"].entries() {
+            pool.insert(
+                parse_color(color_str)?,
+                num.as_i32().ok_or("pool counts are numbers")?,
+            );
+        }
+        Ok(Position {
+            round: val["round"]
+                .as_u32()
+                .ok_or("position is missing its round")?,
+            factories: factories_from_json(&val["factories"])?,
+            pool,
+            first_player_marker_in_pool: val["first_player_marker_in_pool"]
+                .as_bool()
+                .ok_or("position is missing first_player_marker_in_pool")?,
+            players: val["players"]
+                .members()
+                .map(PlayerBoard::from_json)
+                .collect::<Result<Vec<PlayerBoard>, String>>()?,
+            events: Events::new(),
+        })
+    } // fn from_json
+} // impl Position
+
+fn empty_pool() -> HashMap<TileColor, i32> {
+    (0..NUM_COLORS)
+        .map(|ii| (TileColor::from_integer(ii), 0))
+        .collect()
+}
+
+// for a row or column of a wall, or a pattern line
+fn check_idx(idx: usize, what: &str) -> Result<(), String> {
+    if idx < NUM_COLORS_AS_USIZE {
+        Ok(())
+    } else {
+        Err(format!("there's no {} {}", what, idx))
+    }
+}
+
+// appends to a game log as the game goes: a header line with the seats and where we started,
+// then one line of events per turn, round end and game end
+#[derive(Debug)]
+pub(crate) struct GameLog {
+    file: File,
+}
+
+impl GameLog {
+    // a resumed game keeps adding to the log it already had
+    pub(crate) fn open(
+        path: &Path,
+        seed: u64,
+        seats: &[String],
+        start: &Position,
+    ) -> Result<Self, String> {
+        let write_error =
+            |error: std::io::Error| format!("couldn't write {}: {}", path.display(), error);
+        let is_new = std::fs::metadata(path).map_or(true, |meta| meta.len() == 0);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(write_error)?;
+        if is_new {
+            let header = object! {
+                "version": LOG_FORMAT_VERSION,
+                "seed": seed,
+                "seats": seats.to_vec(),
+                "start": start.to_json(),
+            };
+            writeln!(file, "{}", header.dump()).map_err(write_error)?;
+        }
+        Ok(GameLog { file })
+    } // fn open
+    pub(crate) fn append(&mut self, events: &Events) -> Result<(), String> {
+        let events: Vec<json::JsonValue> = events.iter().map(|event| event.to_json()).collect();
+        writeln!(self.file, "{}", object! {"events": events}.dump())
+            .map_err(|error| format!("couldn't write game log: {}", error))
+    } // fn append
+} // impl GameLog
+
+// a game log read back in, with the position after every step worked out up front
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub seed: u64,
+    pub seats: Vec<String>,
+    pub positions: Vec<Position>, // positions[0] is where the log starts
+}
+
+impl GameRecord {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = File::open(path)
+            .map_err(|error| format!("couldn't read {}: {}", path.display(), error))?;
+        let mut lines = Vec::new();
+        for line in std::io::BufReader::new(file).lines() {
+            lines.push(
+                line.map_err(|error| format!("couldn't read {}: {}", path.display(), error))?,
+            );
+        }
+        Self::from_lines(&lines)
+    } // fn load
+    pub fn from_lines(lines: &[String]) -> Result<Self, String> {
+        let mut parsed = Vec::new();
+        for (line_num, line) in (1..).zip(lines.iter()) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            parsed
+                .push(json::parse(line).map_err(|error| format!("line {}: {}", line_num, error))?);
+        }
+        let header = parsed.first().ok_or("game log is empty")?;
+        if header["version"].as_u32() != Some(LOG_FORMAT_VERSION) {
+            return Err(format!(
+                "game log version {} isn't supported, expected {}",
+                header["version"], LOG_FORMAT_VERSION
+            ));
+        }
+        let seats: Vec<String> = header["seats"]
+            .members()
+            .map(|seat| seat.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .ok_or("seats are player type names")?;
+        let start = Position::from_json(&header["start"])?;
+        if start.players.len() != seats.len() {
+            return Err(String::from("game log has a board for every seat"));
+        }
+        let mut positions = vec![start];
+        for (step, val) in (1..).zip(parsed[1..].iter()) {
+            let mut position = positions.last().unwrap().clone();
+            position.events.clear();
+            for event in val["events"].members() {
+                let event =
+                    Event::from_json(event).map_err(|error| format!("step {}: {}", step, error))?;
+                position
+                    .apply(&event)
+                    .map_err(|error| format!("step {}: {}", step, error))?;
+                position.events.push(event);
+            }
+            positions.push(position);
+        }
+        Ok(GameRecord {
+            seed: header["seed"]
+                .as_u64()
+                .ok_or("game log is missing its seed")?,
+            seats,
+            positions,
+        })
+    } // fn from_lines
+    pub fn draw(&self, step: usize) -> String {
+        let position = &self.positions[step];
+        let mut lines = format!(
+            "---------------------------\nstep {}/{}, round {}\n",
+            step,
+            self.positions.len() - 1,
+            position.round
+        );
+        for event in position.events.iter() {
+            lines.push_str(format!("  {}\n", event).as_str());
+        }
+        lines.push_str(
+            draw_shared_tiles(
+                &position.factories,
+                &position.pool,
+                position.first_player_marker_in_pool,
+            )
+            .as_str(),
+        );
+        for (seat, board) in position.players.iter().enumerate() {
+            let marker = if board.has_first_player_marker {
+                "*"
+            } else {
+                ""
+            };
+            lines.push_str(
+                format!("\nPLAYER {}{} ({})\n", seat + 1, marker, self.seats[seat]).as_str(),
+            );
+            lines.push_str(draw_player_board(board).as_str());
+            lines.push('\n');
+        }
+        lines
+    } // fn draw
+} // impl GameRecord
+
+// steps through a recorded game at the terminal
+pub fn run_replay(record: &GameRecord) {
+    let last_step = record.positions.len() - 1;
+    let mut step = 0;
+    loop {
+        print!("{}", record.draw(step));
+        println!("[n] next [p] previous [s] start [e] end [<number>] go to step [q] quit");
+        let mut read_buf = String::new();
+        match std::io::stdin().read_line(&mut read_buf) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        match read_buf.trim() {
+            "" | "n" => step = std::cmp::min(step + 1, last_step),
+            "p" | "b" => step = step.saturating_sub(1),
+            "s" => step = 0,
+            "e" => step = last_step,
+            "q" => return,
+            other => match other.parse::<usize>() {
+                Ok(num) if num <= last_step => step = num,
+                _ => println!("ERROR: no step {}; the game has {}", other, last_step),
+            },
+        }
+    }
+} // fn run_replay
+
+#[cfg(test)]
+use crate::{run_game, GameOptions, GameState, GreedyAgent, PlayerAgent, RandomAgent};
+
+#[test]
+fn test_replay_matches_game_state() {
+    let mut state = GameState::new(3, 31);
+    let start = Position {
+        round: state.round(),
+        factories: state.factories().to_vec(),
+        pool: state.pool().clone(),
+        first_player_marker_in_pool: state.first_player_marker_in_pool(),
+        players: vec![PlayerBoard::new(); 3],
+        events: Events::new(),
+    };
+    let header = object! {"version": LOG_FORMAT_VERSION, "seed": 31, "seats": vec!["greedy"; 3], "start": start.to_json()};
+    let mut lines = vec![header.dump()];
+    let mut num_turns = 0;
+    while !state.is_terminal() {
+        let action = state.legal_actions()[0];
+        let events: Vec<json::JsonValue> = state
+            .apply(action)
+            .unwrap()
+            .iter()
+            .map(|event| event.to_json())
+            .collect();
+        lines.push(object! {"events": events}.dump());
+        num_turns += 1;
+    }
+    let record = GameRecord::from_lines(&lines).unwrap();
+    assert_eq!(record.positions.len(), num_turns + 1);
+    let last = record.positions.last().unwrap();
+    for player in 0..3 {
+        assert_eq!(&last.players[player], state.player_board(player));
+    }
+    assert!(matches!(last.events.last(), Some(Event::GameEnded { .. })));
+    // every step draws, start to finish
+    for step in 0..record.positions.len() {
+        assert!(record.draw(step).contains("PLAYER 3"));
+    }
+}
+#[test]
+fn test_threaded_game_log_replays() {
+    let path = std::env::temp_dir().join(format!("azool_log_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let agents: Vec<Box<dyn PlayerAgent>> =
+        vec![Box::new(GreedyAgent::new()), Box::new(RandomAgent::new())];
+    let options = GameOptions {
+        log_path: Some(path.clone()),
+        ..Default::default()
+    };
+    run_game(agents, 12, &options);
+    let record = GameRecord::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(record.seed, 12);
+    assert_eq!(record.seats, vec!["greedy", "random"]);
+    let last = record.positions.last().unwrap();
+    let scores = match last.events.last() {
+        Some(Event::GameEnded { scores }) => scores.clone(),
+        other => panic!("log should end with the final scores, got {:?}", other),
+    };
+    let replayed: Vec<i32> = last.players.iter().map(|board| board.score).collect();
+    assert_eq!(replayed, scores);
+    assert!(last.players.iter().any(|board| board.has_full_row()));
+}
+#[test]
+fn test_replay_turns_away_events_that_point_nowhere() {
+    let header = object! {"version": LOG_FORMAT_VERSION, "seed": 3, "seats": vec!["greedy"; 2], "start": Position::new(2).to_json()};
+    let bad_events = [
+        Event::TilesTaken {
+            player: 0,
+            factory_idx: Some(7),
+            color: TileColor::RED,
+            num_tiles: 2,
+        },
+        Event::TilesPlaced {
+            player: 1,
+            row_idx: 5,
+            color: TileColor::BLUE,
+            num_tiles: 1,
+        },
+        Event::WallTiled {
+            player: 0,
+            row_idx: 0,
+            col_idx: 9,
+            color: TileColor::BLUE,
+            points: 1,
+        },
+        Event::FloorPenalty {
+            player: 2,
+            points: 1,
+        },
+    ];
+    for event in bad_events {
+        let lines = vec![
+            header.dump(),
+            object! {"events": vec![event.to_json()]}.dump(),
+        ];
+        let error = GameRecord::from_lines(&lines).unwrap_err();
+        assert!(error.starts_with("step 1: there's no"), "{}", error);
+    }
+}
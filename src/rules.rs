@@ -0,0 +1,177 @@
+// house rules: how many tiles there are, how they get dealt and what they score. the wall is
+// always 5x5, but everything else can come from a preset or a file instead of a recompile
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// every rule set you can ask for by name, standard first
+pub const RULE_PRESETS: [&str; 4] = ["standard", "gentle", "crowded", "jokers"];
+
+// anything a file leaves out keeps its standard value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleSet {
+    pub tiles_per_color: i32,   // in the bag at the start of the game
+    pub tiles_per_factory: i32, // dealt onto each factory at the start of a round
+    pub jokers: i32,            // wild tiles in the bag as well, none by default
+    // what 0, 1, 2... tiles on the floor cost in all. any more than that costs the last one
+    pub floor_penalties: Vec<i32>,
+    pub row_bonus: i32,    // at the end of the game, for every full row
+    pub column_bonus: i32, // for every full column
+    pub color_bonus: i32,  // for every color with all five tiles on the wall
+}
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            tiles_per_color: 20,
+            tiles_per_factory: 4,
+            jokers: 0,
+            floor_penalties: vec![0, 1, 2, 3, 5, 7, 10, 13, 15],
+            row_bonus: 2,
+            column_bonus: 7,
+            color_bonus: 10,
+        }
+    }
+}
+
+impl RuleSet {
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(RuleSet::default()),
+            // the floor stings a lot less, so it's worth taking a big draw for one tile
+            "gentle" => Some(RuleSet {
+                floor_penalties: vec![0, 1, 1, 2, 2, 3, 3, 4, 4],
+                ..Default::default()
+            }),
+            // five to a factory, with enough tiles in the bag to keep the deals full
+            "crowded" => Some(RuleSet {
+                tiles_per_color: 25,
+                tiles_per_factory: 5,
+                ..Default::default()
+            }),
+            // a sixth color that can finish any pattern line that's already been started
+            "jokers" => Some(RuleSet {
+                jokers: 10,
+                ..Default::default()
+            }),
+            _ => None,
+        }
+    } // fn preset
+
+    // a preset, or failing that a .toml or .json file
+    pub fn find(name_or_path: &str) -> Result<Self, String> {
+        match Self::preset(name_or_path) {
+            Some(rules) => Ok(rules),
+            None if Path::new(name_or_path).is_file() => Self::load(Path::new(name_or_path)),
+            None => Err(format!(
+                "{} isn't a rules file or one of {}",
+                name_or_path,
+                RULE_PRESETS.join(", ")
+            )),
+        }
+    } // fn find
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("couldn't read {}: {}", path.display(), error))?;
+        let rules = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&contents),
+            Some("json") => Self::from_json(&contents),
+            _ => Err(String::from("rules files end in .toml or .json")),
+        };
+        rules.map_err(|error| format!("{}: {}", path.display(), error))
+    } // fn load
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        let rules: RuleSet = toml::from_str(contents).map_err(|error| error.to_string())?;
+        rules.check()?;
+        Ok(rules)
+    }
+    pub fn from_json(contents: &str) -> Result<Self, String> {
+        let rules: RuleSet = serde_json::from_str(contents).map_err(|error| error.to_string())?;
+        rules.check()?;
+        Ok(rules)
+    }
+    // turns away rules a game can't be played under
+    pub fn check(&self) -> Result<(), String> {
+        if self.tiles_per_color < 1 {
+            return Err(String::from("tiles_per_color has to be at least 1"));
+        }
+        if self.jokers < 0 {
+            return Err(String::from("jokers can't be negative"));
+        }
+        if self.tiles_per_factory < 1 {
+            return Err(String::from("tiles_per_factory has to be at least 1"));
+        }
+        if self.floor_penalties.is_empty() {
+            return Err(String::from("floor_penalties needs at least one entry"));
+        }
+        if self.floor_penalties.iter().any(|points| *points < 0) {
+            return Err(String::from(
+                "floor_penalties are points lost, so none can be negative",
+            ));
+        }
+        Ok(())
+    } // fn check
+
+    // what `num_penalties` tiles on the floor cost
+    pub fn penalty(&self, num_penalties: i32) -> i32 {
+        let last = self.floor_penalties.len() - 1;
+        self.floor_penalties[std::cmp::min(num_penalties.max(0) as usize, last)]
+    }
+} // impl RuleSet
+
+// for save files and observations, which the json crate builds. ones written before there were
+// house rules don't have any, and were played by the standard ones
+pub(crate) fn rules_from_json(val: &json::JsonValue) -> Result<RuleSet, String> {
+    match val {
+        json::JsonValue::Null => Ok(RuleSet::default()),
+        val => RuleSet::from_json(&val.dump()).map_err(|error| format!("rules: {}", error)),
+    }
+}
+
+#[test]
+fn test_presets_are_all_playable() {
+    for name in RULE_PRESETS {
+        let rules = RuleSet::preset(name).unwrap();
+        assert_eq!(rules.check(), Ok(()));
+        assert_eq!(RuleSet::find(name), Ok(rules));
+    }
+    assert_eq!(RuleSet::preset("standard"), Some(RuleSet::default()));
+    assert!(RuleSet::find("no-such-rules").is_err());
+}
+#[test]
+fn test_penalty_caps_at_the_last_entry() {
+    let rules = RuleSet::default();
+    assert_eq!(rules.penalty(0), 0);
+    assert_eq!(rules.penalty(4), 5);
+    assert_eq!(rules.penalty(8), 15);
+    assert_eq!(rules.penalty(20), 15);
+}
+#[test]
+fn test_rules_files_fill_in_the_rest() {
+    let rules = RuleSet::from_toml("tiles_per_factory = 3\nfloor_penalties = [0, 2, 4]\n").unwrap();
+    assert_eq!(rules.tiles_per_factory, 3);
+    assert_eq!(rules.penalty(7), 4);
+    assert_eq!(rules.tiles_per_color, RuleSet::default().tiles_per_color);
+    let rules = RuleSet::from_json(r#"{"row_bonus": 3, "color_bonus": 12}"#).unwrap();
+    assert_eq!(rules.row_bonus, 3);
+    assert_eq!(rules.column_bonus, 7);
+    // typos and unplayable values get turned away instead of silently ignored
+    assert!(RuleSet::from_toml("row_bonsu = 3").is_err());
+    assert!(RuleSet::from_json(r#"{"tiles_per_factory": 0}"#).is_err());
+    assert!(RuleSet::from_json(r#"{"floor_penalties": []}"#).is_err());
+}
+#[test]
+fn test_load_picks_the_format_by_extension() {
+    let toml_path = std::env::temp_dir().join(format!("azool_rules_{}.toml", std::process::id()));
+    std::fs::write(&toml_path, "tiles_per_color = 15\n").unwrap();
+    assert_eq!(
+        RuleSet::find(toml_path.to_str().unwrap())
+            .unwrap()
+            .tiles_per_color,
+        15
+    );
+    let txt_path = toml_path.with_extension("txt");
+    std::fs::write(&txt_path, "tiles_per_color = 15\n").unwrap();
+    assert!(RuleSet::load(&txt_path).is_err());
+    std::fs::remove_file(toml_path).unwrap();
+    std::fs::remove_file(txt_path).unwrap();
+}
@@ -0,0 +1,301 @@
+use crate::rules::rules_from_json;
+use crate::{protocol, GameBoard, PlayerBoard, RuleSet, MAX_NUM_PLAYERS, MIN_NUM_PLAYERS};
+use json::object;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::path::Path;
+
+// bump this if the layout changes so old files get turned away instead of misread
+const SAVE_FORMAT_VERSION: u32 = 2;
+
+// a threaded game frozen between turns. run_game writes one of these after every turn when it's
+// given somewhere to save, and resume_game picks it back up
+#[derive(Clone, Debug)]
+pub struct SavedGame {
+    pub seed: u64,
+    pub seats: Vec<String>, // agent names, player 1 first
+    pub(crate) num_turns: u32,
+    pub(crate) round: u32,
+    pub(crate) board: json::JsonValue,    // GameBoard::snapshot
+    pub(crate) players: Vec<PlayerBoard>, // player 1 first
+    pub(crate) turn_order: Vec<u8>,       // player ids, starting with whoever led the round
+    pub(crate) next_turn_idx: usize,      // index into turn_order
+    pub(crate) round_in_progress: bool,   // false means the next round hasn't been dealt yet
+    pub(crate) first_player: u8,          // leads the next round
+    pub(crate) penalty_taken: bool,       // somebody already has the first player marker
+    pub(crate) rules: RuleSet,            // the game keeps these even if it's resumed with others
+}
+
+impl SavedGame {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("couldn't read {}: {}", path.display(), error))?;
+        let val = json::parse(&contents)
+            .map_err(|error| format!("{} isn't valid json: {}", path.display(), error))?;
+        Self::from_json(&val)
+    } // fn load
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        // write next to the old save and swap it in, so getting killed mid-write doesn't lose both
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, self.to_json().pretty(2))
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|error| format!("couldn't write {}: {}", path.display(), error))
+    } // fn save
+    pub fn to_json(&self) -> json::JsonValue {
+        let players: Vec<json::JsonValue> =
+            self.players.iter().map(|board| board.to_json()).collect();
+        object! {
+            "version": SAVE_FORMAT_VERSION,
+            "seed": self.seed,
+            "seats": self.seats.clone(),
+            "num_turns": self.num_turns,
+            "round": self.round,
+            "board": self.board.clone(),
+            "players": players,
+            "turn_order": self.turn_order.clone(),
+            "next_turn_idx": self.next_turn_idx,
+            "round_in_progress": self.round_in_progress,
+            "first_player": self.first_player,
+            "penalty_taken": self.penalty_taken,
+            "rules": protocol::to_json(&self.rules),
+        }
+    } // fn to_json
+    pub fn from_json(val: &json::JsonValue) -> Result<Self, String> {
+        if val["version"].as_u32() != Some(SAVE_FORMAT_VERSION) {
+            return Err(format!(
+                "save file version {} isn't supported, expected {}",
+                val["version"], SAVE_FORMAT_VERSION
+            ));
+        }
+        let seats: Vec<String> = val["seats"]
+            .members()
+            .map(|seat| seat.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .ok_or("seats are player type names")?;
+        let num_players = seats.len();
+        if !(MIN_NUM_PLAYERS..=MAX_NUM_PLAYERS).contains(&num_players) {
+            return Err(format!(
+                "azool needs {}-{} players, save file has {}",
+                MIN_NUM_PLAYERS, MAX_NUM_PLAYERS, num_players
+            ));
+        }
+        let players = val["players"]
+            .members()
+            .map(PlayerBoard::from_json)
+            .collect::<Result<Vec<PlayerBoard>, String>>()?;
+        if players.len() != num_players {
+            return Err(format!(
+                "save file has {} seats but {} boards",
+                num_players,
+                players.len()
+            ));
+        }
+        let turn_order: Vec<u8> = val["turn_order"]
+            .members()
+            .map(|id| id.as_u8())
+            .collect::<Option<Vec<u8>>>()
+            .ok_or("turn_order is a list of player ids")?;
+        let mut sorted_ids = turn_order.clone();
+        sorted_ids.sort();
+        if sorted_ids != (1..=num_players as u8).collect::<Vec<u8>>() {
+            return Err(String::from("turn_order needs every player exactly once"));
+        }
+        let next_turn_idx = val["next_turn_idx"]
+            .as_usize()
+            .filter(|idx| *idx < num_players)
+            .ok_or("next_turn_idx is missing or out of range")?;
+        let first_player = val["first_player"]
+            .as_u8()
+            .filter(|id| turn_order.contains(id))
+            .ok_or("first_player is missing or isn't at the table")?;
+        let rules = rules_from_json(&val["rules"])?;
+        // make sure the board loads now rather than halfway through starting the game
+        GameBoard::with_rules(num_players as i32, StdRng::seed_from_u64(0), rules.clone())
+            .restore(&val["board"])?;
+        Ok(SavedGame {
+            seed: val["seed"]
+                .as_u64()
+                .ok_or("save file is missing its seed")?,
+            seats,
+            num_turns: val["num_turns"].as_u32().ok_or("num_turns is missing")?,
+            round: val["round"].as_u32().ok_or("round is missing")?,
+            board: val["board"].clone(),
+            players,
+            turn_order,
+            next_turn_idx,
+            round_in_progress: val["round_in_progress"]
+                .as_bool()
+                .ok_or("round_in_progress is missing")?,
+            first_player,
+            penalty_taken: val["penalty_taken"]
+                .as_bool()
+                .ok_or("penalty_taken is missing")?,
+            rules,
+        })
+    } // fn from_json
+} // impl SavedGame
+
+#[cfg(test)]
+use crate::{
+    resume_game, run_game, Action, GameOptions, GameRecord, GreedyAgent, Observation, PlayerAgent,
+    TileColor,
+};
+#[cfg(test)]
+use std::path::PathBuf;
+
+#[cfg(test)]
+fn test_saved_game() -> SavedGame {
+    let mut game_board = GameBoard::with_rng(3, StdRng::seed_from_u64(8));
+    game_board.start_round();
+    let color = *game_board.tile_factories[0].keys().next().unwrap();
+    let num_tiles = game_board.take_tiles_from_factory(0, &color).unwrap();
+    let mut first = PlayerBoard::new();
+    first.rows[4] = (num_tiles, color);
+    first.grid[[0, 2]] = true;
+    first.score = 3;
+    let mut second = PlayerBoard::new();
+    second.floor_tiles = vec![TileColor::RED, TileColor::BLUE];
+    SavedGame {
+        seed: 8,
+        seats: vec![String::from("greedy"); 3],
+        num_turns: 1,
+        round: 1,
+        board: game_board.snapshot(),
+        players: vec![first, second, PlayerBoard::new()],
+        turn_order: vec![2, 3, 1],
+        next_turn_idx: 2,
+        round_in_progress: true,
+        first_player: 2,
+        penalty_taken: false,
+        rules: RuleSet::preset("gentle").unwrap(),
+    }
+}
+#[test]
+fn test_saved_game_round_trip() {
+    let saved = test_saved_game();
+    let loaded = SavedGame::from_json(&json::parse(&saved.to_json().dump()).unwrap()).unwrap();
+    assert_eq!(loaded.seed, saved.seed);
+    assert_eq!(loaded.seats, saved.seats);
+    assert_eq!(loaded.players, saved.players);
+    assert_eq!(loaded.turn_order, saved.turn_order);
+    assert_eq!(loaded.next_turn_idx, saved.next_turn_idx);
+    assert_eq!(loaded.first_player, saved.first_player);
+    assert_eq!(loaded.rules, saved.rules);
+    let mut board = GameBoard::with_rng(3, StdRng::seed_from_u64(0));
+    board.restore(&loaded.board).unwrap();
+    assert_eq!(board.snapshot(), saved.board);
+    // 100 tiles less the ones sitting on player 1's pattern line
+    let num_on_table: i32 = board
+        .tile_factories
+        .iter()
+        .map(|fact| fact.values().sum::<i32>())
+        .sum::<i32>()
+        + board.tile_pool.values().sum::<i32>()
+        + board.tile_bag.len() as i32;
+    assert_eq!(num_on_table + saved.players[0].rows[4].0, 100);
+}
+#[test]
+fn test_saved_game_rejects_bad_files() {
+    let mut val = test_saved_game().to_json();
+    val["turn_order"] = json::array![1, 1, 3];
+    assert!(SavedGame::from_json(&val).is_err());
+    let mut val = test_saved_game().to_json();
+    val["board"]["bag"][0] = "PURPLE".into();
+    assert!(SavedGame::from_json(&val).is_err());
+    let mut val = test_saved_game().to_json();
+    val["version"] = 0.into();
+    assert!(SavedGame::from_json(&val).is_err());
+    let mut val = test_saved_game().to_json();
+    val["rules"]["tiles_per_factory"] = 0.into();
+    assert!(SavedGame::from_json(&val).is_err());
+    // older saves don't have any rules, and get the standard ones
+    let mut val = test_saved_game().to_json();
+    val.remove("rules");
+    assert_eq!(
+        SavedGame::from_json(&val).unwrap().rules,
+        RuleSet::default()
+    );
+}
+#[test]
+fn test_resume_game_plays_to_the_end() {
+    let path = std::env::temp_dir().join(format!("azool_resume_{}.json", std::process::id()));
+    test_saved_game().save(&path).unwrap();
+    let saved = SavedGame::load(&path).unwrap();
+    let agents: Vec<Box<dyn PlayerAgent>> = (0..3)
+        .map(|_| Box::new(GreedyAgent::new()) as Box<dyn PlayerAgent>)
+        .collect();
+    let options = GameOptions {
+        save_path: Some(path.clone()),
+        ..Default::default()
+    };
+    resume_game(agents, saved, &options);
+    // a finished game cleans up after itself
+    assert!(!path.exists());
+}
+// plays greedy, and takes a copy of the save file the table wrote just before its `at`th move
+#[cfg(test)]
+struct SnapshotAgent {
+    agent: GreedyAgent,
+    num_moves: u32,
+    at: u32,
+    from: PathBuf,
+    to: PathBuf,
+}
+#[cfg(test)]
+impl PlayerAgent for SnapshotAgent {
+    fn name(&self) -> String {
+        self.agent.name()
+    }
+    fn choose_action(&mut self, obs: &Observation) -> Action {
+        self.num_moves += 1;
+        if self.num_moves == self.at {
+            std::fs::copy(&self.from, &self.to).unwrap();
+        }
+        self.agent.choose_action(obs)
+    }
+}
+#[test]
+fn test_resumed_game_deals_what_the_seed_would_have() {
+    let dir = std::env::temp_dir();
+    let id = std::process::id();
+    let save_path = dir.join(format!("azool_midgame_{}.json", id));
+    let copy_path = dir.join(format!("azool_midgame_copy_{}.json", id));
+    let whole_log = dir.join(format!("azool_whole_{}.jsonl", id));
+    let resumed_log = dir.join(format!("azool_resumed_{}.jsonl", id));
+    // far enough in that the resumed game still has rounds left to deal
+    let agents: Vec<Box<dyn PlayerAgent>> = vec![
+        Box::new(GreedyAgent::new()),
+        Box::new(SnapshotAgent {
+            agent: GreedyAgent::new(),
+            num_moves: 0,
+            at: 12,
+            from: save_path.clone(),
+            to: copy_path.clone(),
+        }),
+    ];
+    let options = GameOptions {
+        save_path: Some(save_path.clone()),
+        log_path: Some(whole_log.clone()),
+        ..Default::default()
+    };
+    run_game(agents, 77, &options);
+    let saved = SavedGame::load(&copy_path).unwrap();
+    std::fs::remove_file(&copy_path).unwrap();
+    assert!(saved.num_turns > 0);
+    let agents: Vec<Box<dyn PlayerAgent>> = (0..2)
+        .map(|_| Box::new(GreedyAgent::new()) as Box<dyn PlayerAgent>)
+        .collect();
+    let options = GameOptions {
+        log_path: Some(resumed_log.clone()),
+        ..Default::default()
+    };
+    resume_game(agents, saved, &options);
+    let whole = GameRecord::load(&whole_log).unwrap();
+    let resumed = GameRecord::load(&resumed_log).unwrap();
+    std::fs::remove_file(&whole_log).unwrap();
+    std::fs::remove_file(&resumed_log).unwrap();
+    assert_eq!(
+        whole.positions.last().unwrap().players,
+        resumed.positions.last().unwrap().players
+    );
+}
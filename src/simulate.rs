@@ -0,0 +1,195 @@
+use crate::{agent_from_name, GameState, Observation, PlayerAgent, RuleSet, Variant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+
+// numbers for one entry in the --players list, across every game it played
+#[derive(Clone, Debug, Default)]
+pub struct SeatStats {
+    pub name: String,
+    pub wins: f64, // a shared win counts as 1/n
+    pub scores: Vec<i32>,
+}
+impl SeatStats {
+    pub fn mean_score(&self) -> f64 {
+        if self.scores.is_empty() {
+            return 0.0;
+        }
+        self.scores.iter().sum::<i32>() as f64 / self.scores.len() as f64
+    }
+    pub fn std_dev_score(&self) -> f64 {
+        if self.scores.is_empty() {
+            return 0.0;
+        }
+        let mean = self.mean_score();
+        let variance = self
+            .scores
+            .iter()
+            .map(|score| (*score as f64 - mean).powi(2))
+            .sum::<f64>()
+            / self.scores.len() as f64;
+        variance.sqrt()
+    }
+} // impl SeatStats
+
+#[derive(Clone, Debug, Default)]
+pub struct SimulationReport {
+    pub num_games: u32,
+    pub total_rounds: u32,
+    pub seats: Vec<SeatStats>,
+}
+impl SimulationReport {
+    pub fn mean_rounds(&self) -> f64 {
+        if self.num_games == 0 {
+            return 0.0;
+        }
+        self.total_rounds as f64 / self.num_games as f64
+    }
+}
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} games, {:.2} rounds per game",
+            self.num_games,
+            self.mean_rounds()
+        )?;
+        writeln!(
+            f,
+            "{:<4} {:<12} {:>8} {:>10} {:>10}",
+            "seat", "player", "win %", "mean", "std dev"
+        )?;
+        for (ii, seat) in self.seats.iter().enumerate() {
+            writeln!(
+                f,
+                "{:<4} {:<12} {:>8.2} {:>10.2} {:>10.2}",
+                ii + 1,
+                seat.name,
+                100.0 * seat.wins / self.num_games.max(1) as f64,
+                seat.mean_score(),
+                seat.std_dev_score()
+            )?;
+        }
+        Ok(())
+    }
+} // impl fmt::Display for SimulationReport
+
+// plays one game start to finish with no I/O. agents[ii] sits in seat ii
+pub fn play_game(state: &mut GameState, agents: &mut [Box<dyn PlayerAgent>]) {
+    while !state.is_terminal() {
+        let player = state.current_player();
+        let obs = Observation::from_state(state);
+        let action = agents[player].choose_action(&obs);
+        if let Err(error) = state.apply(action) {
+            // a bot that can't follow the rules forfeits its turn to the first legal move
+            agents[player].on_invalid_action(action, error);
+            state.apply(obs.legal_actions[0]).unwrap();
+        }
+    }
+    for (player, agent) in agents.iter_mut().enumerate() {
+        agent.on_game_end(state.player_board(player).score);
+    }
+} // fn play_game
+
+// runs `num_games` bot-vs-bot games on `variant`'s wall under `rules`. seats rotate every game so
+// nobody keeps the first-move edge
+pub fn run_simulation(
+    player_names: &[String],
+    num_games: u32,
+    seed: u64,
+    variant: Variant,
+    rules: &RuleSet,
+) -> Result<SimulationReport, String> {
+    let num_players = player_names.len();
+    if !(crate::MIN_NUM_PLAYERS..=crate::MAX_NUM_PLAYERS).contains(&num_players) {
+        return Err(format!(
+            "azool needs {}-{} players, got {}",
+            crate::MIN_NUM_PLAYERS,
+            crate::MAX_NUM_PLAYERS,
+            num_players
+        ));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut agents: Vec<Box<dyn PlayerAgent>> = Vec::new();
+    for name in player_names.iter() {
+        if name == "human" {
+            return Err(String::from("simulations are bots only"));
+        }
+        agents.push(
+            agent_from_name(name, rng.gen()).ok_or(format!("unknown player type: {}", name))?,
+        );
+    }
+    let mut report = SimulationReport {
+        num_games,
+        total_rounds: 0,
+        seats: player_names
+            .iter()
+            .map(|name| SeatStats {
+                name: name.clone(),
+                ..Default::default()
+            })
+            .collect(),
+    };
+    for game_idx in 0..num_games as usize {
+        // agents[ii] moves to seat (ii + game_idx) % num_players
+        agents.rotate_right(game_idx % num_players);
+        let mut state = GameState::with_rules(num_players, rng.gen(), variant, rules.clone());
+        play_game(&mut state, &mut agents);
+        agents.rotate_left(game_idx % num_players);
+        report.total_rounds += state.round();
+        let scores = state.scores();
+        // ties on points go to whoever has more full rows, and are only shared after that
+        let winners = state.standings().winners();
+        for (ii, stats) in report.seats.iter_mut().enumerate() {
+            let seat = (ii + game_idx) % num_players;
+            stats.scores.push(scores[seat]);
+            if winners.contains(&seat) {
+                stats.wins += 1.0 / winners.len() as f64;
+            }
+        }
+    }
+    Ok(report)
+} // fn run_simulation
+
+#[test]
+fn test_simulation_report() {
+    let names = vec![String::from("greedy"), String::from("random")];
+    let report = run_simulation(&names, 10, 1, Variant::Standard, &RuleSet::default()).unwrap();
+    assert_eq!(report.num_games, 10);
+    assert_eq!(report.seats.len(), 2);
+    assert_eq!(report.seats[0].scores.len(), 10);
+    assert!((report.seats[0].wins + report.seats[1].wins - 10.0).abs() < 1e-9);
+    assert!(report.mean_rounds() >= 1.0);
+    // greedy should have no trouble with a random player
+    assert!(report.seats[0].wins > report.seats[1].wins);
+}
+#[test]
+fn test_simulation_same_seed_same_result() {
+    let names = vec![
+        String::from("greedy"),
+        String::from("random"),
+        String::from("mcts:5"),
+    ];
+    let rules = RuleSet::default();
+    let report_a = run_simulation(&names, 3, 99, Variant::Standard, &rules).unwrap();
+    let report_b = run_simulation(&names, 3, 99, Variant::Standard, &rules).unwrap();
+    for (seat_a, seat_b) in report_a.seats.iter().zip(report_b.seats.iter()) {
+        assert_eq!(seat_a.scores, seat_b.scores);
+    }
+    assert_eq!(report_a.total_rounds, report_b.total_rounds);
+    assert!(run_simulation(&names[..1], 5, 99, Variant::Standard, &rules).is_err());
+    let humans = [String::from("human"), String::from("greedy")];
+    assert!(run_simulation(&humans, 5, 99, Variant::Standard, &rules).is_err());
+}
+#[test]
+fn test_simulation_takes_variants_and_house_rules() {
+    let names = vec![String::from("greedy"), String::from("random")];
+    for rules in ["standard", "jokers", "crowded"] {
+        let rules = RuleSet::preset(rules).unwrap();
+        for variant in [Variant::Standard, Variant::Gray] {
+            let report = run_simulation(&names, 3, 5, variant, &rules).unwrap();
+            assert_eq!(report.seats[0].scores.len(), 3);
+            assert!((report.seats[0].wins + report.seats[1].wins - 3.0).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::agent::{draw_player_board, draw_shared_tiles};
+use crate::net::send_line;
+use crate::protocol::{self, Reply, TableMessage, TableRequest, TableView};
+use crate::AzoolRequestType;
+use std::io::{BufRead, BufReader};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// the game thread does the sending, so a spectator gets this long to take each board off our
+// hands before we give up on them
+const SEND_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default)]
+struct Audience {
+    streams: Vec<TcpStream>,
+    latest: Option<TableMessage>, // so anybody turning up mid-game sees the table straight away
+}
+
+// everyone watching one game. the table hands them a GET_BOARD with every player's board after
+// each turn, and nothing they send ever reaches the game
+#[derive(Clone, Debug, Default)]
+pub struct Spectators {
+    audience: Arc<Mutex<Audience>>,
+}
+
+impl Spectators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.audience.lock().unwrap().streams.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // starts streaming the game to `reader`'s connection
+    pub fn add(&self, reader: BufReader<TcpStream>) {
+        let mut stream = match reader.get_ref().try_clone() {
+            Ok(stream) => stream,
+            Err(error) => {
+                println!("couldn't clone spectator socket: {}", error);
+                return;
+            }
+        };
+        // it's on the socket, so it covers answer_spectator's replies too
+        let _ = stream.set_write_timeout(Some(SEND_TIMEOUT));
+        {
+            let mut audience = self.audience.lock().unwrap();
+            if let Some(latest) = &audience.latest {
+                if send_line(&mut stream, latest).is_err() {
+                    return;
+                }
+            }
+            audience.streams.push(stream);
+        }
+        let audience = self.audience.clone();
+        thread::spawn(move || answer_spectator(audience, reader));
+    } // fn add
+    pub(crate) fn broadcast(&self, view: TableView) {
+        let msg = TableMessage::GetBoard(view);
+        let mut audience = self.audience.lock().unwrap();
+        // anybody who's gone, or too slow to keep up, gets hung up on
+        audience
+            .streams
+            .retain_mut(|stream| match send_line(stream, &msg) {
+                Ok(()) => true,
+                Err(_) => {
+                    let _ = stream.shutdown(Shutdown::Both);
+                    false
+                }
+            });
+        audience.latest = Some(msg);
+    }
+    // hangs up on everybody once the game is over
+    pub(crate) fn finish(&self) {
+        for stream in self.audience.lock().unwrap().streams.drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+} // impl Spectators
+
+// spectators can ask for the board again, and that's all
+fn answer_spectator(audience: Arc<Mutex<Audience>>, reader: BufReader<TcpStream>) {
+    let mut stream = match reader.get_ref().try_clone() {
+        Ok(stream) => stream,
+        Err(_) => return,
+    };
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        let audience = audience.lock().unwrap();
+        let sent = match (protocol::parse::<TableRequest>(&line), &audience.latest) {
+            (Ok(TableRequest::GetBoard { .. }), Some(latest)) => send_line(&mut stream, latest),
+            (Ok(TableRequest::GetBoard { .. }), None) => send_line(
+                &mut stream,
+                &Reply::refuse(
+                    AzoolRequestType::ReqTypeGetBoard,
+                    "the game hasn't started yet",
+                ),
+            ),
+            (Ok(request), _) => send_line(&mut stream, &refuse(&request)),
+            (Err(error), _) => send_line(
+                &mut stream,
+                &Reply::refuse(AzoolRequestType::ReqTypeInvalid, &error),
+            ),
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+} // fn answer_spectator
+
+// anything else a spectator sends is a request meant for a Player, so turn it down as one
+fn refuse(request: &TableRequest) -> Reply {
+    let req_type = request.req_type();
+    match request {
+        TableRequest::DrawFromFactory(_)
+        | TableRequest::DrawFromPool(_)
+        | TableRequest::DiscardFromFactory(_)
+        | TableRequest::DiscardFromPool(_) => {
+            Reply::refuse(req_type, "spectators can't move tiles")
+        }
+        _ => Reply::refuse(req_type, "spectators can only ask for GET_BOARD"),
+    }
+}
+
+// turns one of the table's GET_BOARD messages into something to put on a screen
+pub(crate) fn draw_table(view: &TableView) -> String {
+    let mut lines = format!("round {}\n", view.round.unwrap_or(0));
+    for event in view.events.iter().flatten() {
+        lines.push_str(&format!("{}\n", event));
+    }
+    lines.push_str(&draw_shared_tiles(
+        &view.factories,
+        &view.pool,
+        view.white_tile_in_pool,
+    ));
+    for (ii, board) in view.players.iter().flatten().enumerate() {
+        lines.push_str(&format!("\nplayer {}\n", ii + 1));
+        if let Some(time_left_ms) = view.time_left_ms.as_ref().and_then(|left| left.get(ii)) {
+            lines.push_str(&format!("clock: {}s left\n", time_left_ms / 1000));
+        }
+        lines.push_str(&draw_player_board(board));
+    }
+    lines
+} // fn draw_table
+
+#[cfg(test)]
+use crate::{host_game, Event, GameOptions, GreedyAgent, RandomAgent, Seat};
+#[cfg(test)]
+use std::io::Write;
+
+#[test]
+fn test_spectators_see_every_board_but_cant_move() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let spectators = Spectators::new();
+    spectators.add(BufReader::new(listener.accept().unwrap().0));
+    assert_eq!(spectators.len(), 1);
+    writeln!(
+        client,
+        r#"{{"req_type": "DRAW_FROM_POOL", "current_player": 1, "tile_color": 0}}"#
+    )
+    .unwrap();
+    let mut lines = BufReader::new(client).lines();
+    let refusal = json::parse(&lines.next().unwrap().unwrap()).unwrap();
+    assert_eq!(refusal["req_type"], "DRAW_FROM_POOL");
+    assert_eq!(refusal["success"], false);
+    let watcher = thread::spawn(move || {
+        lines
+            .map(|line| match protocol::parse(&line.unwrap()).unwrap() {
+                TableMessage::GetBoard(view) => view,
+                msg => panic!("spectators should only get boards, got {:?}", msg),
+            })
+            .collect::<Vec<TableView>>()
+    });
+    let seats = vec![
+        Seat::Local(Box::new(GreedyAgent::new())),
+        Seat::Local(Box::new(RandomAgent::new())),
+    ];
+    let options = GameOptions {
+        spectators: Some(spectators.clone()),
+        ..Default::default()
+    };
+    host_game(seats, 12, None, &options);
+    // the game hangs up on its spectators once it's done
+    let boards = watcher.join().unwrap();
+    assert!(boards.len() > 10);
+    for board in boards.iter() {
+        assert_eq!(board.players.as_ref().unwrap().len(), 2);
+        draw_table(board);
+    }
+    let last = boards.last().unwrap();
+    match last.events.as_ref().unwrap().last().unwrap() {
+        Event::GameEnded { scores } => {
+            for (score, board) in scores.iter().zip(last.players.as_ref().unwrap()) {
+                assert_eq!(board.score, *score);
+            }
+        }
+        event => panic!("game should end with its scores, got {}", event),
+    }
+    assert!(spectators.is_empty());
+}
+#[test]
+fn test_spectator_who_stops_reading_gets_dropped() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let spectators = Spectators::new();
+    spectators.add(BufReader::new(listener.accept().unwrap().0));
+    let view = TableView {
+        players: Some(vec![crate::PlayerBoard::new(); 4]),
+        ..Default::default()
+    };
+    // never read, so the socket fills up sooner or later. the game has to keep going anyway
+    for _ in 0..100_000 {
+        if spectators.is_empty() {
+            break;
+        }
+        spectators.broadcast(view.clone());
+    }
+    assert!(spectators.is_empty());
+}
@@ -0,0 +1,198 @@
+// how a finished game came out: everyone's place, with the end of game bonuses broken out
+use crate::{PlayerBoard, RuleSet, TileColor, NUM_COLORS_AS_USIZE};
+use std::fmt;
+
+// what a wall earns at the end of the game, counted rather than scored
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bonuses {
+    pub rows: i32,    // full horizontal rows, which also break ties
+    pub columns: i32, // full columns
+    pub colors: i32,  // colors with all five tiles on the wall
+}
+impl Bonuses {
+    pub fn count(board: &PlayerBoard) -> Self {
+        let grid = &board.grid;
+        let rows = grid.rows().into_iter().filter(|row| row.iter().all(|x| *x));
+        let columns = grid
+            .columns()
+            .into_iter()
+            .filter(|col| col.iter().all(|x| *x));
+        let colors = (0..NUM_COLORS_AS_USIZE).filter(|ii| {
+            // wherever it went - the gray wall doesn't keep a color to one spot
+            let color = TileColor::from_integer(*ii as i32);
+            (0..NUM_COLORS_AS_USIZE).all(|row_idx| board.has_on_wall(row_idx, color))
+        });
+        Bonuses {
+            rows: rows.count() as i32,
+            columns: columns.count() as i32,
+            colors: colors.count() as i32,
+        }
+    } // fn count
+    pub fn points(&self, rules: &RuleSet) -> i32 {
+        self.rows * rules.row_bonus
+            + self.columns * rules.column_bonus
+            + self.colors * rules.color_bonus
+    }
+} // impl Bonuses
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub player: usize, // seat, from 0
+    pub rank: usize,   // from 1. players still tied after the tie-breaker share one
+    pub score: i32,    // final, bonuses and all
+    pub bonuses: Bonuses,
+    pub bonus_points: i32,
+}
+
+// best first. the official rules: most points wins, then most full rows, and anyone still tied
+// shares the win
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standings {
+    pub places: Vec<Standing>,
+    pub names: Vec<String>, // by seat. leave it empty and the table just has seat numbers
+}
+impl Standings {
+    // `boards` are by seat, with their final scores already on them
+    pub fn new(boards: &[PlayerBoard], rules: &RuleSet, names: &[String]) -> Self {
+        let mut places: Vec<Standing> = boards
+            .iter()
+            .enumerate()
+            .map(|(player, board)| {
+                let bonuses = Bonuses::count(board);
+                Standing {
+                    player,
+                    rank: 0,
+                    score: board.score,
+                    bonuses,
+                    bonus_points: bonuses.points(rules),
+                }
+            })
+            .collect();
+        let key = |standing: &Standing| (standing.score, standing.bonuses.rows);
+        // ties keep seat order, which sort_by_key leaves alone
+        places.sort_by_key(|standing| std::cmp::Reverse(key(standing)));
+        for ii in 0..places.len() {
+            places[ii].rank = match ii {
+                0 => 1,
+                _ if key(&places[ii]) == key(&places[ii - 1]) => places[ii - 1].rank,
+                _ => ii + 1,
+            };
+        }
+        Standings {
+            places,
+            names: names.to_vec(),
+        }
+    } // fn new
+
+    // seats, from 0. more than one if they're still tied on full rows
+    pub fn winners(&self) -> Vec<usize> {
+        self.places
+            .iter()
+            .filter(|standing| standing.rank == 1)
+            .map(|standing| standing.player)
+            .collect()
+    }
+} // impl Standings
+impl fmt::Display for Standings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<5} {:<6} {:<12} {:>6} {:>5} {:>8} {:>7} {:>6}",
+            "rank", "player", "", "score", "rows", "columns", "colors", "bonus"
+        )?;
+        for standing in self.places.iter() {
+            // a shared place gets an = on it
+            let num_tied = self
+                .places
+                .iter()
+                .filter(|other| other.rank == standing.rank)
+                .count();
+            let rank = match num_tied {
+                1 => standing.rank.to_string(),
+                _ => format!("{}=", standing.rank),
+            };
+            writeln!(
+                f,
+                "{:<5} {:<6} {:<12} {:>6} {:>5} {:>8} {:>7} {:>6}",
+                rank,
+                standing.player + 1,
+                self.names
+                    .get(standing.player)
+                    .map_or("", |name| name.as_str()),
+                standing.score,
+                standing.bonuses.rows,
+                standing.bonuses.columns,
+                standing.bonuses.colors,
+                standing.bonus_points
+            )?;
+        }
+        let winners: Vec<String> = self
+            .winners()
+            .iter()
+            .map(|player| (player + 1).to_string())
+            .collect();
+        match winners.len() {
+            1 => write!(f, "winner is player {}", winners[0]),
+            _ => write!(f, "players {} share the win", winners.join(" and ")),
+        }
+    }
+} // impl fmt::Display for Standings
+
+#[cfg(test)]
+fn board_with(score: i32, full_rows: usize) -> PlayerBoard {
+    let mut board = PlayerBoard::new();
+    board.score = score;
+    for row_idx in 0..full_rows {
+        board.grid.row_mut(row_idx).fill(true);
+    }
+    board
+}
+
+#[test]
+fn test_full_rows_break_ties() {
+    let boards = vec![board_with(30, 1), board_with(30, 2), board_with(41, 1)];
+    let standings = Standings::new(&boards, &RuleSet::default(), &[]);
+    let order: Vec<(usize, usize)> = standings
+        .places
+        .iter()
+        .map(|standing| (standing.player, standing.rank))
+        .collect();
+    assert_eq!(order, vec![(2, 1), (1, 2), (0, 3)]);
+    assert_eq!(standings.winners(), vec![2]);
+    assert_eq!(standings.places[1].bonuses.rows, 2);
+    assert_eq!(standings.places[1].bonus_points, 4);
+}
+#[test]
+fn test_still_tied_players_share_the_win() {
+    let boards = vec![
+        board_with(12, 1),
+        board_with(9, 0),
+        board_with(12, 1),
+        board_with(9, 0),
+    ];
+    let names: Vec<String> = ["greedy", "random", "mcts", "human"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    let standings = Standings::new(&boards, &RuleSet::default(), &names);
+    assert_eq!(standings.winners(), vec![0, 2]);
+    let ranks: Vec<usize> = standings
+        .places
+        .iter()
+        .map(|standing| standing.rank)
+        .collect();
+    assert_eq!(ranks, vec![1, 1, 3, 3]);
+    let table = standings.to_string();
+    assert!(table.contains("1=    1      greedy"), "{}", table);
+    assert!(
+        table.ends_with("players 1 and 3 share the win"),
+        "{}",
+        table
+    );
+    // nobody scoring anything is still a game everyone drew, not one player 0 won
+    let boards = vec![board_with(0, 0), board_with(0, 0)];
+    assert_eq!(
+        Standings::new(&boards, &RuleSet::default(), &[]).winners(),
+        vec![0, 1]
+    );
+}
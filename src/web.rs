@@ -0,0 +1,357 @@
+use crate::net::{Connection, Waiting, PEER_NAME, POLL_INTERVAL};
+use crate::protocol::{self, Hello, Reply};
+use crate::{AzoolRequestType, Sessions};
+use serde::Serialize;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::{Message, WebSocket};
+
+pub const DEFAULT_WEB_PORT: u16 = 8080;
+
+const INDEX_HTML: &str = include_str!("../static/index.html");
+// longest request head we'll look at before deciding what somebody wants
+const MAX_REQUEST_HEAD: usize = 8192;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// a browser sitting at the table. the Player thread still keeps the rules and the pattern lines;
+// the browser just gets shown what an agent would see and sends back the move it wants, one
+// message per text frame
+impl Connection for WebSocket<TcpStream> {
+    const SEAT_NAME: &'static str = "web";
+    fn waiting(sessions: &Sessions) -> &Waiting<Self> {
+        &sessions.browsers
+    }
+    fn prepare(&mut self) {
+        let _ = self.get_ref().set_nodelay(true);
+        let _ = self.get_ref().set_read_timeout(Some(POLL_INTERVAL));
+    }
+    fn send<T: Serialize>(&mut self, msg: &T) -> bool {
+        let text = serde_json::to_string(msg).expect("every message serializes");
+        WebSocket::send(self, Message::text(text)).is_ok()
+    }
+    fn poll(&mut self, _partial: &mut Vec<u8>) -> Result<Option<String>, ()> {
+        // tungstenite holds on to half a frame itself, and picks up where it left off
+        match self.read() {
+            Ok(Message::Text(text)) => Ok(Some(text.to_string())),
+            Ok(Message::Close(_)) => Err(()),
+            Ok(_) => Ok(None), // pings and the like
+            Err(tungstenite::Error::Io(error))
+                if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                Ok(None)
+            }
+            Err(_) => Err(()),
+        }
+    }
+    fn hang_up(mut self) {
+        let _ = self.close(None);
+        let _ = self.flush();
+    }
+} // impl Connection for WebSocket<TcpStream>
+
+// reads the request head without taking it off the socket, so tungstenite can still do the handshake.
+// returns it along with how many bytes of the socket it came from
+fn peek_request_head(stream: &TcpStream) -> Option<(String, usize)> {
+    let start = Instant::now();
+    let mut buf = [0u8; MAX_REQUEST_HEAD];
+    loop {
+        let num_bytes = stream.peek(&mut buf).ok()?;
+        let head = String::from_utf8_lossy(&buf[..num_bytes]);
+        if head.contains("\r\n\r\n") || num_bytes == MAX_REQUEST_HEAD {
+            return Some((head.into_owned(), num_bytes));
+        }
+        if num_bytes == 0 || start.elapsed() > REQUEST_TIMEOUT {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+} // fn peek_request_head
+
+fn serve_page(mut stream: TcpStream, head: &str, num_bytes: usize) {
+    // drain the request we peeked at so the browser doesn't see a reset
+    let mut buf = vec![0u8; num_bytes];
+    let _ = stream.read_exact(&mut buf);
+    let path = head.split_whitespace().nth(1).unwrap_or("/");
+    let response = if path == "/" || path == "/index.html" {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            INDEX_HTML.len(),
+            INDEX_HTML
+        )
+    } else {
+        String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+    };
+    let _ = stream.write_all(response.as_bytes());
+} // fn serve_page
+
+// the server's half of the HELLO a browser opens with, same as greet does for a socket. the
+// request timeout is still on, so a page that never says anything gets dropped
+fn greet_browser(socket: &mut WebSocket<TcpStream>) -> Result<Hello, String> {
+    let hello = loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                break protocol::parse::<Hello>(text.as_str())
+                    .map_err(|error| format!("say HELLO first: {}", error))
+                    .and_then(|hello| hello.check().map(|_| hello))
+            }
+            Ok(Message::Close(_)) => return Err(String::from("hung up before saying HELLO")),
+            Ok(_) => continue,
+            Err(error) => return Err(format!("never said HELLO: {}", error)),
+        }
+    };
+    let reply = match &hello {
+        Ok(_) => serde_json::to_string(&Hello::new(PEER_NAME)),
+        Err(error) => serde_json::to_string(&Reply::refuse(AzoolRequestType::ReqTypeHello, error)),
+    };
+    let _ = socket.send(Message::text(reply.expect("every message serializes")));
+    if hello.is_err() {
+        let _ = socket.close(None);
+        let _ = socket.flush();
+    }
+    hello
+} // fn greet_browser
+
+// hands out the browser client over plain http and passes every websocket that connects to the
+// returned channel, except the ones coming back to a seat with `/ws?rejoin=<token>`, which go
+// straight to it. runs for as long as the process does, so people can reload the page mid-game
+pub fn spawn_web_gateway(
+    listener: TcpListener,
+    sessions: Sessions,
+) -> mpsc::Receiver<WebSocket<TcpStream>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    println!("web connection failed: {}", error);
+                    continue;
+                }
+            };
+            // every connection gets its own thread, so a tab that never says anything can't hold
+            // up the rest - least of all somebody racing the grace period to get back to a seat
+            let (tx, sessions) = (tx.clone(), sessions.clone());
+            thread::spawn(move || answer_browser(stream, &tx, &sessions));
+        }
+    });
+    rx
+} // fn spawn_web_gateway
+
+// serves the page, or does the handshake and HELLO and passes the websocket along
+fn answer_browser(stream: TcpStream, tx: &mpsc::Sender<WebSocket<TcpStream>>, sessions: &Sessions) {
+    let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT));
+    let (head, num_bytes) = match peek_request_head(&stream) {
+        Some(peeked) => peeked,
+        None => return,
+    };
+    if !head.to_ascii_lowercase().contains("upgrade: websocket") {
+        serve_page(stream, &head, num_bytes);
+        return;
+    }
+    let path = head.split_whitespace().nth(1).unwrap_or("/");
+    let token = path
+        .split_once("?rejoin=")
+        .map(|(_, token)| token.to_string());
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(error) => {
+            println!("websocket handshake failed: {}", error);
+            return;
+        }
+    };
+    if let Err(error) = greet_browser(&mut socket) {
+        println!("turned away a browser: {}", error);
+        return;
+    }
+    // only the handshakes get a deadline; a person can take as long as they like to move
+    let _ = socket.get_ref().set_read_timeout(None);
+    match token {
+        Some(token) => {
+            let _ = sessions.rejoin(&token, socket);
+        }
+        None => {
+            let _ = tx.send(socket);
+        }
+    }
+} // fn answer_browser
+
+#[cfg(test)]
+use crate::{host_game, GameOptions, RandomAgent, ReconnectOptions, Seat, PROTOCOL_VERSION};
+#[cfg(test)]
+use json::object;
+#[cfg(test)]
+use tungstenite::stream::MaybeTlsStream;
+#[cfg(test)]
+type Browser = WebSocket<MaybeTlsStream<TcpStream>>;
+
+// connects to the gateway at `path` and says HELLO
+#[cfg(test)]
+fn open_browser(addr: std::net::SocketAddr, path: &str) -> Browser {
+    let (mut socket, _) = tungstenite::connect(format!("ws://{}{}", addr, path)).unwrap();
+    let hello = object! {"req_type": "HELLO", "version": PROTOCOL_VERSION, "name": "headless"};
+    socket.send(Message::text(hello.dump())).unwrap();
+    let reply = json::parse(socket.read().unwrap().to_text().unwrap()).unwrap();
+    assert_eq!(reply["version"], PROTOCOL_VERSION);
+    socket
+}
+#[cfg(test)]
+fn next_message(socket: &mut Browser) -> json::JsonValue {
+    loop {
+        if let Message::Text(text) = socket.read().unwrap() {
+            return json::parse(text.as_str()).unwrap();
+        }
+    }
+}
+#[cfg(test)]
+fn http_get(addr: std::net::SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+#[test]
+fn test_gateway_serves_the_page() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _web_sockets = spawn_web_gateway(listener, Sessions::new());
+    let response = http_get(addr, "/");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("new WebSocket("));
+    assert!(http_get(addr, "/nope").starts_with("HTTP/1.1 404"));
+}
+#[test]
+fn test_gateway_doesnt_wait_on_anybody() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _web_sockets = spawn_web_gateway(listener, Sessions::new());
+    let start = Instant::now();
+    // connects and never says a word
+    let _idle = TcpStream::connect(addr).unwrap();
+    assert!(http_get(addr, "/").starts_with("HTTP/1.1 200 OK"));
+    // bytes that aren't utf-8 come out longer once they're a string; only drain what was sent
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET /\xff\xff HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 404"));
+    assert!(start.elapsed() < REQUEST_TIMEOUT);
+}
+#[test]
+fn test_game_in_a_headless_browser() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let web_sockets = spawn_web_gateway(listener, Sessions::new());
+    // plays the first legal move every turn, after trying one that can't be right
+    let browser = thread::spawn(move || {
+        let mut socket = open_browser(addr, "/ws");
+        let mut tried_nonsense = false;
+        loop {
+            let msg = match socket.read().unwrap() {
+                Message::Text(text) => json::parse(text.as_str()).unwrap(),
+                _ => continue,
+            };
+            match msg["req_type"].as_str().unwrap() {
+                "TAKE_TURN" if !tried_nonsense => {
+                    tried_nonsense = true;
+                    let nonsense = object! {"req_type": "DRAW_FROM_POOL", "tile_color": 9};
+                    socket.send(Message::text(nonsense.dump())).unwrap();
+                    let reply = json::parse(socket.read().unwrap().to_text().unwrap()).unwrap();
+                    assert_eq!(reply["req_type"], "INVALID");
+                    assert_eq!(reply["error_type"], "BAD_MESSAGE");
+                    socket
+                        .send(Message::text(msg["legal_actions"][0].dump()))
+                        .unwrap();
+                }
+                "TAKE_TURN" => {
+                    socket
+                        .send(Message::text(msg["legal_actions"][0].dump()))
+                        .unwrap();
+                }
+                "JOIN_GAME" => assert_eq!(msg["current_player"], 1),
+                "END_OF_ROUND" => assert!(msg["my_board"].is_object()),
+                "END_OF_GAME" => return msg["final_score"].as_i32().unwrap(),
+                _ => panic!("unexpected message {}", msg.dump()),
+            }
+        }
+    });
+    let socket = web_sockets.recv().unwrap();
+    let seats = vec![
+        Seat::Web(Box::new(socket)),
+        Seat::Local(Box::new(RandomAgent::new())),
+    ];
+    assert_eq!(seats[0].name(), "web");
+    host_game(seats, 3, None, &GameOptions::default());
+    assert!(browser.join().unwrap() >= 0);
+}
+// a browser takes the first seat and closes the tab on its first turn. returns the gateway's
+// address, the seat's token, and the game, which is left to play out in the background
+#[cfg(test)]
+fn host_with_a_browser_dropout(
+    reconnect: ReconnectOptions,
+) -> (std::net::SocketAddr, String, thread::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let sessions = Sessions::new();
+    let web_sockets = spawn_web_gateway(listener, sessions.clone());
+    let browser = thread::spawn(move || {
+        let mut socket = open_browser(addr, "/ws");
+        let join = next_message(&mut socket);
+        assert_eq!(join["req_type"], "JOIN_GAME");
+        while next_message(&mut socket)["req_type"] != "TAKE_TURN" {}
+        join["token"].as_str().unwrap().to_string()
+    });
+    let socket = web_sockets.recv().unwrap();
+    let options = GameOptions {
+        sessions: Some(sessions),
+        reconnect,
+        ..Default::default()
+    };
+    let game = thread::spawn(move || {
+        let seats = vec![
+            Seat::Web(Box::new(socket)),
+            Seat::Local(Box::new(RandomAgent::new())),
+        ];
+        host_game(seats, 4, None, &options);
+    });
+    (addr, browser.join().unwrap(), game)
+}
+#[test]
+fn test_browser_rejoins_its_seat() {
+    let (addr, token, game) = host_with_a_browser_dropout(ReconnectOptions {
+        grace_period: Duration::from_secs(30),
+        stand_in: None,
+    });
+    let mut stranger = open_browser(addr, "/ws?rejoin=not-a-token");
+    let refusal = next_message(&mut stranger);
+    assert_eq!(refusal["req_type"], "REJOIN_GAME");
+    assert_eq!(refusal["success"], false);
+    // back with the right token, the seat says where we are and asks for the turn we missed
+    let mut socket = open_browser(addr, &format!("/ws?rejoin={}", token));
+    assert_eq!(next_message(&mut socket)["token"], token.as_str());
+    loop {
+        let msg = next_message(&mut socket);
+        match msg["req_type"].as_str().unwrap() {
+            "TAKE_TURN" => socket
+                .send(Message::text(msg["legal_actions"][0].dump()))
+                .unwrap(),
+            "END_OF_GAME" => break,
+            _ => {}
+        }
+    }
+    game.join().unwrap();
+}
+#[test]
+fn test_abandoned_browser_seat_is_played_out() {
+    // nobody comes back, so the seat gets forfeited and the game still finishes
+    let (_, _, game) = host_with_a_browser_dropout(ReconnectOptions {
+        grace_period: Duration::from_millis(200),
+        stand_in: None,
+    });
+    game.join().unwrap();
+}